@@ -0,0 +1,245 @@
+//! Converting between dynamic values and JSON.
+//!
+//! The mapping is meant to be easy to read and write by hand:
+//!
+//! - `()` and `None` are `null`, and `Some(x)` is just `x`.
+//! - Integers are numbers, except that 128-bit integers that don't fit in 64
+//!   bits are strings.
+//! - `char`s and strings are strings.
+//! - Arrays, tuples, sequences, and unnamed fields are arrays.
+//! - Named fields are objects.
+//! - Enum variants without fields are strings, and other variants are objects
+//!   with a single member, named for the variant, holding its fields.
+//!
+//! This means that `Option<()>` and nested `Option`s can't be told apart, so
+//! values of those types won't survive a round trip.
+
+use anyhow::{anyhow, bail, Context, Result};
+use hubpack::dynamic::Value;
+use hubpack::schema::{Fields, Schema};
+use serde_json::{Map, Value as Json};
+
+/// Converts a decoded value to JSON.
+pub fn to_json(value: &Value) -> Json {
+    match value {
+        Value::Unit => Json::Null,
+        Value::Bool(v) => Json::from(*v),
+        Value::U8(v) => Json::from(*v),
+        Value::U16(v) => Json::from(*v),
+        Value::U32(v) => Json::from(*v),
+        Value::U64(v) => Json::from(*v),
+        Value::U128(v) => match u64::try_from(*v) {
+            Ok(v) => Json::from(v),
+            Err(_) => Json::from(v.to_string()),
+        },
+        Value::I8(v) => Json::from(*v),
+        Value::I16(v) => Json::from(*v),
+        Value::I32(v) => Json::from(*v),
+        Value::I64(v) => Json::from(*v),
+        Value::I128(v) => match i64::try_from(*v) {
+            Ok(v) => Json::from(v),
+            Err(_) => Json::from(v.to_string()),
+        },
+        // JSON has no NaN or infinities, so those become `null`.
+        Value::F32(v) => Json::from(*v),
+        Value::F64(v) => Json::from(*v),
+        Value::Char(v) => Json::from(v.to_string()),
+        Value::Option(None) => Json::Null,
+        Value::Option(Some(v)) => to_json(v),
+        Value::List(items) => Json::Array(items.iter().map(to_json).collect()),
+        Value::Str(s) => Json::from(s.as_str()),
+        Value::Record(fields) => Json::Object(
+            fields.iter().map(|(name, v)| (name.clone(), to_json(v))).collect()
+        ),
+        Value::Variant { name, fields, .. } => match **fields {
+            Value::Unit => Json::from(name.as_str()),
+            ref fields => {
+                let mut m = Map::new();
+                m.insert(name.clone(), to_json(fields));
+                Json::Object(m)
+            }
+        },
+    }
+}
+
+/// Converts JSON to a value of the type described by `schema`.
+pub fn from_json(schema: &Schema, json: &Json) -> Result<Value> {
+    Ok(match schema {
+        Schema::Unit => match json {
+            Json::Null => Value::Unit,
+            _ => bail!("expected null, found {json}"),
+        },
+        Schema::Bool => Value::Bool(json.as_bool().ok_or_else(|| expected("a bool", json))?),
+        Schema::U8 => Value::U8(int(json)?),
+        Schema::U16 => Value::U16(int(json)?),
+        Schema::U32 => Value::U32(int(json)?),
+        Schema::U64 => Value::U64(int(json)?),
+        Schema::U128 => Value::U128(int(json)?),
+        Schema::I8 => Value::I8(int(json)?),
+        Schema::I16 => Value::I16(int(json)?),
+        Schema::I32 => Value::I32(int(json)?),
+        Schema::I64 => Value::I64(int(json)?),
+        Schema::I128 => Value::I128(int(json)?),
+        Schema::F32 => Value::F32(float(json)? as f32),
+        Schema::F64 => Value::F64(float(json)?),
+        Schema::Char => {
+            let s = string(json)?;
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Value::Char(c),
+                _ => bail!("expected a single character, found {json}"),
+            }
+        }
+        Schema::Option(inner) => Value::Option(match json {
+            Json::Null => None,
+            _ => Some(Box::new(from_json(inner, json)?)),
+        }),
+        Schema::Array { element, .. } | Schema::Seq { element, .. } => Value::List(
+            array(json)?.iter().map(|j| from_json(element, j)).collect::<Result<_>>()?
+        ),
+        Schema::Tuple(elements) => Value::List(list(elements, json)?),
+        Schema::Str { .. } | Schema::FixedStr { .. } => Value::Str(string(json)?.to_string()),
+        Schema::Struct { fields, .. } => fields_from_json(fields, json)?,
+        Schema::Enum { name, variants } => {
+            let (vname, fields) = match json {
+                Json::String(s) => (s.as_str(), &Json::Null),
+                Json::Object(m) if m.len() == 1 => {
+                    let (k, v) = m.iter().next().unwrap();
+                    (k.as_str(), v)
+                }
+                _ => bail!("expected a variant of {name}, found {json}"),
+            };
+            let v = variants.iter()
+                .find(|v| v.name == vname)
+                .ok_or_else(|| anyhow!("{name} has no variant {vname}"))?;
+            Value::Variant {
+                index: v.index,
+                name: v.name.to_string(),
+                fields: Box::new(
+                    fields_from_json(&v.fields, fields).with_context(|| format!("in {vname}"))?
+                ),
+            }
+        }
+    })
+}
+
+fn fields_from_json(fields: &Fields, json: &Json) -> Result<Value> {
+    Ok(match fields {
+        Fields::Unit => from_json(&Schema::Unit, json)?,
+        Fields::Tuple(elements) => Value::List(list(elements, json)?),
+        Fields::Named(named) => {
+            let Json::Object(m) = json else {
+                bail!("expected an object, found {json}");
+            };
+            if let Some(extra) = m.keys().find(|k| !named.iter().any(|f| f.name == *k)) {
+                bail!("unexpected field {extra}");
+            }
+            Value::Record(named.iter().map(|f| {
+                let v = m.get(f.name).ok_or_else(|| anyhow!("missing field {}", f.name))?;
+                let v = from_json(&f.schema, v).with_context(|| format!("in field {}", f.name))?;
+                Ok((f.name.to_string(), v))
+            }).collect::<Result<_>>()?)
+        }
+    })
+}
+
+fn list(elements: &[Schema], json: &Json) -> Result<Vec<Value>> {
+    let items = array(json)?;
+    if items.len() != elements.len() {
+        bail!("expected {} elements, found {}", elements.len(), items.len());
+    }
+    elements.iter().zip(items).map(|(s, j)| from_json(s, j)).collect()
+}
+
+fn expected(what: &str, json: &Json) -> anyhow::Error {
+    anyhow!("expected {what}, found {json}")
+}
+
+fn array(json: &Json) -> Result<&[Json]> {
+    json.as_array().map(Vec::as_slice).ok_or_else(|| expected("an array", json))
+}
+
+fn string(json: &Json) -> Result<&str> {
+    json.as_str().ok_or_else(|| expected("a string", json))
+}
+
+fn float(json: &Json) -> Result<f64> {
+    json.as_f64().ok_or_else(|| expected("a number", json))
+}
+
+/// Reads an integer of any width. Large integers can also be written as
+/// strings, since JSON numbers don't reliably hold more than 53 bits.
+fn int<T: TryFrom<i128> + TryFrom<u128>>(json: &Json) -> Result<T> {
+    let out_of_range = || anyhow!("{json} is out of range for {}", std::any::type_name::<T>());
+    if let Some(v) = json.as_u64() {
+        T::try_from(u128::from(v)).map_err(|_| out_of_range())
+    } else if let Some(v) = json.as_i64() {
+        T::try_from(i128::from(v)).map_err(|_| out_of_range())
+    } else if let Some(s) = json.as_str() {
+        if let Ok(v) = s.parse::<u128>() {
+            T::try_from(v).map_err(|_| out_of_range())
+        } else if let Ok(v) = s.parse::<i128>() {
+            T::try_from(v).map_err(|_| out_of_range())
+        } else {
+            Err(expected("an integer", json))
+        }
+    } else {
+        Err(expected("an integer", json))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hubpack::dynamic::{decode_dynamic, encode_dynamic};
+    use hubpack::schema::{Field, Variant};
+
+    const MESSAGE: Schema = Schema::Struct {
+        name: "Message",
+        fields: Fields::Named(&[
+            Field { name: "id", schema: Schema::U128 },
+            Field { name: "body", schema: Schema::Enum {
+                name: "Body",
+                variants: &[
+                    Variant { name: "Ping", index: 0, fields: Fields::Unit },
+                    Variant { name: "Pair", index: 1, fields: Fields::Tuple(&[Schema::I8, Schema::Char]) },
+                ],
+            }},
+            Field { name: "note", schema: Schema::Option(&Schema::Str { max_len: Some(8) }) },
+        ]),
+    };
+
+    fn round_trip(text: &str) -> Json {
+        let json: Json = serde_json::from_str(text).unwrap();
+        let value = from_json(&MESSAGE, &json).unwrap();
+        let mut buf = [0; 64];
+        let n = encode_dynamic(&mut buf, &MESSAGE, &value).unwrap();
+        let (decoded, _) = decode_dynamic(&MESSAGE, &buf[..n]).unwrap();
+        assert_eq!(decoded, value);
+        to_json(&decoded)
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let text = r#"{"id":"340282366920938463463374607431768211455","body":{"Pair":[-3,"é"]},"note":"hi"}"#;
+        assert_eq!(round_trip(text).to_string(), text);
+        let text = r#"{"id":5,"body":"Ping","note":null}"#;
+        assert_eq!(round_trip(text).to_string(), text);
+    }
+
+    #[test]
+    fn json_errors() {
+        let bad = [
+            r#"{"id":-1,"body":"Ping","note":null}"#,
+            r#"{"id":1,"body":"Pong","note":null}"#,
+            r#"{"id":1,"body":{"Pair":[1]},"note":null}"#,
+            r#"{"id":1,"body":{"Pair":[1,"ab"]},"note":null}"#,
+            r#"{"id":1,"body":"Ping"}"#,
+            r#"{"id":1,"body":"Ping","note":null,"extra":0}"#,
+        ];
+        for text in bad {
+            let json: Json = serde_json::from_str(text).unwrap();
+            assert!(from_json(&MESSAGE, &json).is_err(), "{text}");
+        }
+    }
+}
@@ -0,0 +1,226 @@
+//! `hubpack`: decode and encode `hubpack` messages from the command line, given
+//! a schema file describing their types.
+//!
+//! See `hubpack_codegen::schema_file` for the schema file format, and `json` for how values
+//! are written as JSON.
+
+mod json;
+mod tree;
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use hubpack::config::BigEndian;
+use hubpack::dynamic::{self, Value};
+use hubpack::schema::Schema;
+use hubpack::ser::SizeCounter;
+use hubpack_codegen::schema_file::SchemaFile;
+
+#[derive(Parser)]
+#[command(name = "hubpack", version, about)]
+struct Args {
+    #[command(subcommand)]
+    cmd: Cmd,
+}
+
+#[derive(Subcommand)]
+enum Cmd {
+    /// Decodes a message, and prints it as JSON or as an annotated tree.
+    Decode {
+        #[command(flatten)]
+        common: Common,
+        /// How to print the decoded value.
+        #[arg(long, short, value_enum, default_value_t = Format::Tree)]
+        format: Format,
+        /// Read raw binary input, rather than hex.
+        #[arg(long)]
+        binary: bool,
+        /// File holding the message. Reads standard input if omitted.
+        input: Option<PathBuf>,
+    },
+    /// Encodes a message given as JSON, and prints it as hex.
+    Encode {
+        #[command(flatten)]
+        common: Common,
+        /// Write raw binary output, rather than hex.
+        #[arg(long)]
+        binary: bool,
+        /// File holding the JSON value. Reads standard input if omitted.
+        input: Option<PathBuf>,
+    },
+}
+
+#[derive(clap::Args)]
+struct Common {
+    /// TOML file describing the message types.
+    #[arg(long, short)]
+    schema: PathBuf,
+    /// Type of the message, if not the schema file's root. This can be any
+    /// type expression, such as `Option<Message>`.
+    #[arg(long, short = 't')]
+    r#type: Option<String>,
+    /// Use big-endian byte order.
+    #[arg(long)]
+    big_endian: bool,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum Format {
+    Json,
+    Tree,
+}
+
+impl Common {
+    fn schema(&self) -> Result<Schema> {
+        let text = std::fs::read_to_string(&self.schema)
+            .with_context(|| format!("can't read {}", self.schema.display()))?;
+        let mut file = SchemaFile::parse(&text)
+            .with_context(|| format!("in {}", self.schema.display()))?;
+        if self.r#type.is_none() && file.root().is_none() {
+            bail!("{} has no root type; pick one with --type", self.schema.display());
+        }
+        file.resolve(self.r#type.as_deref())
+            .with_context(|| format!("in {}", self.schema.display()))
+    }
+}
+
+fn main() -> Result<()> {
+    match Args::parse().cmd {
+        Cmd::Decode { common, format, binary, input } => {
+            let schema = common.schema()?;
+            let raw = read_input(input.as_ref())?;
+            let data = if binary { raw } else { parse_hex(&String::from_utf8(raw)?)? };
+
+            let result = if common.big_endian {
+                dynamic::decode_dynamic_with_config(&schema, &data, BigEndian)
+            } else {
+                dynamic::decode_dynamic(&schema, &data)
+            };
+            let result = result.map_err(|e| anyhow::anyhow!("can't decode: {e}"));
+
+            match format {
+                Format::Json => {
+                    let (value, rest) = result?;
+                    println!("{:#}", json::to_json(&value));
+                    if !rest.is_empty() {
+                        eprintln!("note: {} bytes left over after the message", rest.len());
+                    }
+                }
+                // The tree lists any leftover bytes itself, and shows
+                // everything up to a decoding error, so print it either way.
+                Format::Tree => {
+                    print!("{}", tree::format_tree(&schema, &data, common.big_endian));
+                    result?;
+                }
+            }
+        }
+        Cmd::Encode { common, binary, input } => {
+            let schema = common.schema()?;
+            let raw = read_input(input.as_ref())?;
+            let text = serde_json::from_slice(&raw).context("input isn't JSON")?;
+            let value = json::from_json(&schema, &text)?;
+
+            let bytes = encode(&schema, &value, common.big_endian)
+                .map_err(|e| anyhow::anyhow!("can't encode: {e}"))?;
+            if binary {
+                std::io::stdout().write_all(&bytes)?;
+            } else {
+                let hex = bytes.iter().map(|b| format!("{b:02x}")).collect::<Vec<_>>();
+                println!("{}", hex.join(" "));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn encode(schema: &Schema, value: &Value, big_endian: bool) -> hubpack::Result<Vec<u8>> {
+    let mut counter = SizeCounter::new();
+    dynamic::encode_value(&mut counter, schema, value)?;
+    let mut buf = vec![0; counter.position()];
+    if big_endian {
+        dynamic::encode_dynamic_with_config(&mut buf, schema, value, BigEndian)?;
+    } else {
+        dynamic::encode_dynamic(&mut buf, schema, value)?;
+    }
+    Ok(buf)
+}
+
+fn read_input(path: Option<&PathBuf>) -> Result<Vec<u8>> {
+    match path {
+        Some(p) => std::fs::read(p).with_context(|| format!("can't read {}", p.display())),
+        None => {
+            let mut buf = vec![];
+            std::io::stdin().read_to_end(&mut buf)?;
+            Ok(buf)
+        }
+    }
+}
+
+/// Parses hex bytes, in any of the forms people tend to paste: `01ab`,
+/// `01 ab`, `0x01, 0xab`, or `01:ab`.
+fn parse_hex(text: &str) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    for token in text.split(|c: char| c.is_whitespace() || c == ',' || c == ':') {
+        let digits = token.strip_prefix("0x").unwrap_or(token);
+        if digits.len() % 2 != 0 {
+            bail!("odd number of hex digits in {token:?}");
+        }
+        for i in (0..digits.len()).step_by(2) {
+            let pair = digits.get(i..i + 2)
+                .with_context(|| format!("bad hex {token:?}"))?;
+            bytes.push(u8::from_str_radix(pair, 16)
+                .with_context(|| format!("bad hex {token:?}"))?);
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_formats() {
+        assert_eq!(parse_hex("01ab\n").unwrap(), [0x01, 0xab]);
+        assert_eq!(parse_hex("0x01, 0xAB,").unwrap(), [0x01, 0xab]);
+        assert_eq!(parse_hex("01:ab 02").unwrap(), [0x01, 0xab, 0x02]);
+        assert!(parse_hex("1ab").is_err());
+        assert!(parse_hex("zz").is_err());
+    }
+
+    #[test]
+    fn tree_output() {
+        let mut file = SchemaFile::parse(r#"
+            [types.Message]
+            fields = [
+                { name = "id", type = "u16" },
+                { name = "body", type = "Body" },
+            ]
+            [types.Body]
+            variants = [
+                { name = "Ping" },
+                { name = "Reading", tuple = ["Option<i32>", "String<8>"] },
+            ]
+        "#).unwrap();
+        let schema = file.resolve(Some("Message")).unwrap();
+        let data = parse_hex("0201 01 01 2a000000 02 6869").unwrap();
+        assert_eq!(tree::format_tree(&schema, &data, false), "\
+0000..0000                              Message
+0000..0002  02 01                         id: 258
+0002..0003  01                            body: Body::Reading
+0003..0004  01                              0: Some
+0004..0008  2a 00 00 00                       42
+0008..000b  02 68 69                        1: \"hi\"
+");
+        // A bad message is shown up to where it went wrong.
+        let data = parse_hex("0201 01 02").unwrap();
+        assert_eq!(tree::format_tree(&schema, &data, false), "\
+0000..0000                              Message
+0000..0002  02 01                         id: 258
+0002..0003  01                            body: Body::Reading
+0003..0004  02                          error: invalid/corrupt
+");
+    }
+}
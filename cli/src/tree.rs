@@ -0,0 +1,19 @@
+//! Printing messages as an indented tree, alongside the bytes each part came
+//! from.
+//!
+//! This is `hubpack::fmt::Annotated`, which also lists any bytes left over
+//! after the message.
+
+use hubpack::config::BigEndian;
+use hubpack::fmt::Annotated;
+use hubpack::schema::Schema;
+
+/// Formats the message at the start of `data`, described by `schema`, with
+/// one line per component, giving its byte range and encoding.
+pub fn format_tree(schema: &Schema, data: &[u8], big_endian: bool) -> String {
+    if big_endian {
+        Annotated::with_config(schema, data, BigEndian).to_string()
+    } else {
+        Annotated::new(schema, data).to_string()
+    }
+}
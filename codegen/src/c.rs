@@ -0,0 +1,715 @@
+//! C99 headers.
+//!
+//! [`header`] produces a self-contained header, with no dependencies beyond
+//! the C standard library. For each struct or enum `T`, it defines:
+//!
+//! - a C type `T`, laid out naturally (not packed);
+//! - `T_MAX_SIZE`, the largest encoded size, as a macro;
+//! - `bool T_encode(const T *v, uint8_t *buf, size_t len, size_t *written)`,
+//!   which fails if `buf` is too small;
+//! - `bool T_decode(T *v, const uint8_t *buf, size_t len, size_t *used)`,
+//!   which fails on the same inputs that `hubpack::deserialize` rejects, and
+//!   reports how much of `buf` the value took up.
+//!
+//! Rust types are mapped as follows:
+//!
+//! - Integers become `<stdint.h>` types, and `bool`, `f32`, and `f64` become
+//!   `bool`, `float`, and `double`. A `char` becomes a `uint32_t` holding the
+//!   code point. 128-bit integers aren't supported.
+//! - A struct becomes a C struct with the same field names, or `_0`, `_1`, and
+//!   so on for tuple structs.
+//! - An enum `T` becomes a struct with a `tag`, of type `T_Tag`, whose values
+//!   are named `T_Variant`. Fields of variants are in a union `u`, so the
+//!   fields of `T::Variant` are `u.Variant`.
+//! - `Option<U>` becomes a struct with a `bool present` and a `value`.
+//! - Arrays become C arrays, and `FixedStr<N>` becomes `char[N]`.
+//! - Sequences and strings must have a maximum length `N`, and become structs
+//!   with a `uint16_t len` and an array of `N` `items` or `char`s of `data`.
+//!   On the wire, `len` takes one byte if `N` is at most 255, and two
+//!   otherwise.
+//! - Tuples become structs with fields `_0`, `_1`, and so on.
+//!
+//! Helper structs for options, tuples, sequences, and strings are named after
+//! their contents, like `Option_u16` or `Seq8_Tuple2_u8_bool`.
+
+use std::collections::HashMap;
+
+use hubpack::schema::{Fields, Schema};
+
+use crate::{check_roots, max_size, prefix_size, Error, Result};
+
+/// Generates a C header for the types described by `roots`, and every type
+/// they use. `guard` is the name of the include guard macro.
+///
+/// The roots must be structs or enums.
+pub fn header(guard: &str, roots: &[Schema]) -> Result<String> {
+    check_roots(roots)?;
+    let mut g = Gen::default();
+    for root in roots {
+        g.define(root)?;
+    }
+
+    let mut out = String::new();
+    out.push_str("/* Generated by hubpack-codegen. Do not edit. */\n\n");
+    out.push_str(&format!("#ifndef {guard}\n#define {guard}\n\n"));
+    out.push_str(RUNTIME);
+    out.push_str(&g.out);
+    out.push_str(&format!("#endif /* {guard} */\n"));
+    Ok(out)
+}
+
+/// Definitions shared by all generated headers.
+const RUNTIME: &str = r#"#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <string.h>
+
+#ifndef HUBPACK_RUNTIME
+#define HUBPACK_RUNTIME
+
+typedef struct {
+    uint8_t *buf;
+    size_t len;
+    size_t pos;
+} hubpack_writer;
+
+typedef struct {
+    const uint8_t *buf;
+    size_t len;
+    size_t pos;
+} hubpack_reader;
+
+static inline bool hubpack_write_bytes(hubpack_writer *w, const void *p, size_t n) {
+    if (w->len - w->pos < n) return false;
+    if (n != 0) memcpy(w->buf + w->pos, p, n);
+    w->pos += n;
+    return true;
+}
+
+/* Writes the low `n` bytes of `v`, least significant first. */
+static inline bool hubpack_write_uint(hubpack_writer *w, uint64_t v, size_t n) {
+    uint8_t b[8];
+    size_t i;
+    for (i = 0; i < n; i++) b[i] = (uint8_t)(v >> (8 * i));
+    return hubpack_write_bytes(w, b, n);
+}
+
+static inline bool hubpack_write_f32(hubpack_writer *w, float v) {
+    uint32_t u;
+    memcpy(&u, &v, 4);
+    return hubpack_write_uint(w, u, 4);
+}
+
+static inline bool hubpack_write_f64(hubpack_writer *w, double v) {
+    uint64_t u;
+    memcpy(&u, &v, 8);
+    return hubpack_write_uint(w, u, 8);
+}
+
+static inline bool hubpack_write_str(hubpack_writer *w, const char *s, uint16_t len, size_t max, size_t prefix) {
+    if (len > max) return false;
+    return hubpack_write_uint(w, len, prefix) && hubpack_write_bytes(w, s, len);
+}
+
+/* Writes `s` up to its first NUL, padded with NULs to `n` bytes. */
+static inline bool hubpack_write_fixed_str(hubpack_writer *w, const char *s, size_t n) {
+    size_t i;
+    bool end = false;
+    for (i = 0; i < n; i++) {
+        if (s[i] == 0) end = true;
+        if (!hubpack_write_uint(w, end ? 0 : (uint8_t)s[i], 1)) return false;
+    }
+    return true;
+}
+
+static inline bool hubpack_read_bytes(hubpack_reader *r, void *p, size_t n) {
+    if (r->len - r->pos < n) return false;
+    if (n != 0) memcpy(p, r->buf + r->pos, n);
+    r->pos += n;
+    return true;
+}
+
+static inline bool hubpack_read_uint(hubpack_reader *r, uint64_t *v, size_t n) {
+    uint8_t b[8];
+    size_t i;
+    if (!hubpack_read_bytes(r, b, n)) return false;
+    *v = 0;
+    for (i = 0; i < n; i++) *v |= (uint64_t)b[i] << (8 * i);
+    return true;
+}
+
+/* Reads an `n`-byte two's complement integer, without relying on
+ * implementation-defined conversions. */
+static inline bool hubpack_read_int(hubpack_reader *r, int64_t *v, size_t n) {
+    uint64_t u, sign;
+    if (!hubpack_read_uint(r, &u, n)) return false;
+    sign = (uint64_t)1 << (8 * n - 1);
+    *v = (u & sign) ? (int64_t)(u - sign) - (int64_t)(sign - 1) - 1 : (int64_t)u;
+    return true;
+}
+
+static inline bool hubpack_read_bool(hubpack_reader *r, bool *v) {
+    uint64_t u;
+    if (!hubpack_read_uint(r, &u, 1) || u > 1) return false;
+    *v = u == 1;
+    return true;
+}
+
+static inline bool hubpack_read_f32(hubpack_reader *r, float *v) {
+    uint64_t u;
+    uint32_t u32;
+    if (!hubpack_read_uint(r, &u, 4)) return false;
+    u32 = (uint32_t)u;
+    memcpy(v, &u32, 4);
+    return true;
+}
+
+static inline bool hubpack_read_f64(hubpack_reader *r, double *v) {
+    uint64_t u;
+    if (!hubpack_read_uint(r, &u, 8)) return false;
+    memcpy(v, &u, 8);
+    return true;
+}
+
+static inline bool hubpack_read_char(hubpack_reader *r, uint32_t *v) {
+    uint64_t u;
+    if (!hubpack_read_uint(r, &u, 4)) return false;
+    if (u > 0x10FFFF || (u >= 0xD800 && u <= 0xDFFF)) return false;
+    *v = (uint32_t)u;
+    return true;
+}
+
+static inline bool hubpack_utf8_valid(const uint8_t *p, size_t n) {
+    size_t i = 0, len, k;
+    uint32_t cp, min;
+    while (i < n) {
+        if (p[i] < 0x80) {
+            i++;
+            continue;
+        } else if ((p[i] & 0xE0) == 0xC0) {
+            len = 2; cp = p[i] & 0x1F; min = 0x80;
+        } else if ((p[i] & 0xF0) == 0xE0) {
+            len = 3; cp = p[i] & 0x0F; min = 0x800;
+        } else if ((p[i] & 0xF8) == 0xF0) {
+            len = 4; cp = p[i] & 0x07; min = 0x10000;
+        } else {
+            return false;
+        }
+        if (n - i < len) return false;
+        for (k = 1; k < len; k++) {
+            if ((p[i + k] & 0xC0) != 0x80) return false;
+            cp = (cp << 6) | (p[i + k] & 0x3F);
+        }
+        if (cp < min || cp > 0x10FFFF || (cp >= 0xD800 && cp <= 0xDFFF)) return false;
+        i += len;
+    }
+    return true;
+}
+
+static inline bool hubpack_read_str(hubpack_reader *r, char *s, uint16_t *len, size_t max, size_t prefix) {
+    uint64_t n;
+    if (!hubpack_read_uint(r, &n, prefix) || n > max) return false;
+    if (!hubpack_read_bytes(r, s, (size_t)n)) return false;
+    if (!hubpack_utf8_valid((const uint8_t *)s, (size_t)n)) return false;
+    *len = (uint16_t)n;
+    return true;
+}
+
+/* Reads `n` bytes of NUL-padded string. Like `FixedStr`, this ignores
+ * anything after the first NUL, and clears it. */
+static inline bool hubpack_read_fixed_str(hubpack_reader *r, char *s, size_t n) {
+    size_t len = 0;
+    if (!hubpack_read_bytes(r, s, n)) return false;
+    while (len < n && s[len] != 0) len++;
+    if (!hubpack_utf8_valid((const uint8_t *)s, len)) return false;
+    memset(s + len, 0, n - len);
+    return true;
+}
+
+#endif /* HUBPACK_RUNTIME */
+
+"#;
+
+#[derive(Default)]
+struct Gen {
+    /// Types defined so far, by C name.
+    defined: HashMap<String, Schema>,
+    out: String,
+}
+
+impl Gen {
+    /// Makes sure that the named C type for `schema`, and everything it uses,
+    /// is defined, and returns its name.
+    fn define(&mut self, schema: &Schema) -> Result<String> {
+        let name = type_name(schema)?;
+        if let Some(prev) = self.defined.get(&name) {
+            if prev != schema {
+                return Err(Error::NameClash(name));
+            }
+            return Ok(name);
+        }
+        self.defined.insert(name.clone(), *schema);
+
+        // Dependencies get defined (and written out) as we go, so they end up
+        // ahead of this type.
+        let mut body = vec![];
+        let mut write = vec![];
+        let mut read = vec![];
+        match schema {
+            Schema::Option(inner) => {
+                body.push("bool present;".to_string());
+                body.extend(self.decl(inner, "value")?);
+                write.push("if (!hubpack_write_uint(w, v->present ? 1 : 0, 1)) return false;".into());
+                read.push("if (!hubpack_read_bool(r, &v->present)) return false;".into());
+                let w = self.write(inner, "v->value", 0)?;
+                let r = self.read(inner, "v->value", 0)?;
+                if !w.is_empty() {
+                    write.extend(block("if (v->present)", w));
+                    read.extend(block("if (v->present)", r));
+                }
+            }
+            Schema::Tuple(elements) => {
+                for (i, e) in elements.iter().enumerate() {
+                    let field = format!("_{i}");
+                    body.extend(self.decl(e, &field)?);
+                    write.extend(self.write(e, &format!("v->{field}"), 0)?);
+                    read.extend(self.read(e, &format!("v->{field}"), 0)?);
+                }
+            }
+            Schema::Seq { max_len: Some(max), element } => {
+                let prefix = prefix_size(*max)?;
+                body.push("uint16_t len;".into());
+                body.extend(self.decl(element, &format!("items[{}]", (*max).max(1)))?);
+                write.push(format!("if (v->len > {max}) return false;"));
+                write.push(format!("if (!hubpack_write_uint(w, v->len, {prefix})) return false;"));
+                read.push("uint64_t len;".into());
+                read.push(format!("if (!hubpack_read_uint(r, &len, {prefix}) || len > {max}) return false;"));
+                read.push("v->len = (uint16_t)len;".into());
+                let w = self.write(element, "v->items[i0]", 1)?;
+                let r = self.read(element, "v->items[i0]", 1)?;
+                if !w.is_empty() {
+                    write.extend(block("for (size_t i0 = 0; i0 < v->len; i0++)", w));
+                    read.extend(block("for (size_t i0 = 0; i0 < v->len; i0++)", r));
+                }
+            }
+            Schema::Str { max_len: Some(max) } => {
+                let prefix = prefix_size(*max)?;
+                body.push("uint16_t len;".into());
+                body.push(format!("char data[{}];", (*max).max(1)));
+                write.push(format!("if (!hubpack_write_str(w, v->data, v->len, {max}, {prefix})) return false;"));
+                read.push(format!("if (!hubpack_read_str(r, v->data, &v->len, {max}, {prefix})) return false;"));
+            }
+            Schema::Struct { fields, .. } => {
+                for (field, schema) in members(fields) {
+                    body.extend(self.decl(schema, &field)?);
+                    write.extend(self.write(schema, &format!("v->{field}"), 0)?);
+                    read.extend(self.read(schema, &format!("v->{field}"), 0)?);
+                }
+            }
+            Schema::Enum { variants, .. } => {
+                if variants.is_empty() {
+                    return Err(Error::Unsupported(format!("enum {name}, which has no variants,")));
+                }
+                let mut tags = vec![];
+                let mut union = vec![];
+                let mut write_cases = vec![];
+                let mut read_cases = vec![];
+                for v in variants.iter() {
+                    let tag = format!("{name}_{}", v.name);
+                    tags.push(format!("{tag} = {},", v.index));
+
+                    let mut fields = vec![];
+                    let mut w = vec![format!("if (!hubpack_write_uint(w, {}, 1)) return false;", v.index)];
+                    let mut r = vec![format!("v->tag = {tag};")];
+                    for (field, schema) in members(&v.fields) {
+                        fields.extend(self.decl(schema, &field)?);
+                        let expr = format!("v->u.{}.{field}", v.name);
+                        w.extend(self.write(schema, &expr, 0)?);
+                        r.extend(self.read(schema, &expr, 0)?);
+                    }
+                    if !fields.is_empty() {
+                        union.extend(block("struct", fields));
+                        // Attach the member name to the closing brace.
+                        if let Some(last) = union.last_mut() {
+                            *last = format!("}} {};", v.name);
+                        }
+                    }
+                    w.push("break;".into());
+                    r.push("break;".into());
+                    write_cases.extend(block(&format!("case {tag}:"), w));
+                    read_cases.extend(block(&format!("case {}:", v.index), r));
+                }
+                write_cases.extend(["default:".to_string(), "    return false;".into()]);
+                read_cases.extend(["default:".to_string(), "    return false;".into()]);
+
+                self.out.push_str(&format!("typedef enum {{\n{}}} {name}_Tag;\n\n", lines(&tags, 1)));
+                body.push(format!("{name}_Tag tag;"));
+                if !union.is_empty() {
+                    body.extend(block("union", union));
+                    if let Some(last) = body.last_mut() {
+                        *last = "} u;".into();
+                    }
+                }
+                write.extend(block("switch (v->tag)", write_cases));
+                read.push("uint64_t tag;".into());
+                read.push("if (!hubpack_read_uint(r, &tag, 1)) return false;".into());
+                read.extend(block("switch (tag)", read_cases));
+            }
+            _ => unreachable!("only compound types have names"),
+        }
+
+        if body.is_empty() {
+            // C doesn't allow empty structs.
+            body.push("uint8_t _unused;".into());
+        }
+        // Keep compilers quiet about the parameters of types with nothing to
+        // encode.
+        for (code, param) in [(&mut write, "w"), (&mut read, "r")] {
+            if code.is_empty() {
+                code.push(format!("(void){param};"));
+                code.push("(void)v;".into());
+            }
+            code.push("return true;".into());
+        }
+
+        self.out.push_str(&format!("typedef struct {{\n{}}} {name};\n\n", lines(&body, 1)));
+        self.out.push_str(&format!(
+            "static inline bool {name}_write(hubpack_writer *w, const {name} *v) {{\n{}}}\n\n",
+            lines(&write, 1),
+        ));
+        self.out.push_str(&format!(
+            "static inline bool {name}_read(hubpack_reader *r, {name} *v) {{\n{}}}\n\n",
+            lines(&read, 1),
+        ));
+        if matches!(schema, Schema::Struct { .. } | Schema::Enum { .. }) {
+            self.out.push_str(&format!(
+                "#define {name}_MAX_SIZE {max}\n\n\
+                static inline bool {name}_encode(const {name} *v, uint8_t *buf, size_t len, size_t *written) {{\n    \
+                    hubpack_writer w = {{ buf, len, 0 }};\n    \
+                    if (!{name}_write(&w, v)) return false;\n    \
+                    if (written) *written = w.pos;\n    \
+                    return true;\n\
+                }}\n\n\
+                static inline bool {name}_decode({name} *v, const uint8_t *buf, size_t len, size_t *used) {{\n    \
+                    hubpack_reader r = {{ buf, len, 0 }};\n    \
+                    if (!{name}_read(&r, v)) return false;\n    \
+                    if (used) *used = r.pos;\n    \
+                    return true;\n\
+                }}\n\n",
+                max = max_size(schema)?,
+            ));
+        }
+        Ok(name)
+    }
+
+    /// Returns the declaration of a member `name` holding `schema`, if it
+    /// takes up any space.
+    fn decl(&mut self, schema: &Schema, name: &str) -> Result<Option<String>> {
+        Ok(match schema {
+            Schema::Unit => None,
+            Schema::Tuple([]) => None,
+            Schema::Array { len: 0, .. } => {
+                return Err(Error::Unsupported(format!("the empty array {name}")));
+            }
+            Schema::Array { len, element } => self.decl(element, &format!("{name}[{len}]"))?,
+            Schema::FixedStr { len } => Some(format!("char {name}[{}];", (*len).max(1))),
+            _ => Some(format!("{} {name};", self.c_type(schema)?)),
+        })
+    }
+
+    /// Returns the C type for `schema`, defining it if needed. Arrays are
+    /// handled by `decl`, since C puts their sizes on the name.
+    fn c_type(&mut self, schema: &Schema) -> Result<String> {
+        Ok(match schema {
+            Schema::Bool => "bool".into(),
+            Schema::U8 => "uint8_t".into(),
+            Schema::U16 => "uint16_t".into(),
+            Schema::U32 | Schema::Char => "uint32_t".into(),
+            Schema::U64 => "uint64_t".into(),
+            Schema::I8 => "int8_t".into(),
+            Schema::I16 => "int16_t".into(),
+            Schema::I32 => "int32_t".into(),
+            Schema::I64 => "int64_t".into(),
+            Schema::F32 => "float".into(),
+            Schema::F64 => "double".into(),
+            _ => self.define(schema)?,
+        })
+    }
+
+    /// Returns statements that write the value of `schema` at `expr`.
+    fn write(&mut self, schema: &Schema, expr: &str, depth: usize) -> Result<Vec<String>> {
+        let call = |f: String| vec![format!("if (!{f}) return false;")];
+        Ok(match schema {
+            Schema::Unit => vec![],
+            Schema::Tuple([]) => vec![],
+            Schema::Bool => call(format!("hubpack_write_uint(w, {expr} ? 1 : 0, 1)")),
+            Schema::U8 | Schema::U16 | Schema::U32 | Schema::U64 | Schema::Char => {
+                call(format!("hubpack_write_uint(w, {expr}, {})", max_size(schema)?))
+            }
+            Schema::I8 | Schema::I16 | Schema::I32 | Schema::I64 => {
+                call(format!("hubpack_write_uint(w, (uint64_t)(int64_t){expr}, {})", max_size(schema)?))
+            }
+            Schema::F32 => call(format!("hubpack_write_f32(w, {expr})")),
+            Schema::F64 => call(format!("hubpack_write_f64(w, {expr})")),
+            Schema::U128 | Schema::I128 => return Err(unsupported_128()),
+            Schema::FixedStr { len } => call(format!("hubpack_write_fixed_str(w, {expr}, {len})")),
+            Schema::Array { len, element } => {
+                let i = format!("i{depth}");
+                let inner = self.write(element, &format!("{expr}[{i}]"), depth + 1)?;
+                block(&format!("for (size_t {i} = 0; {i} < {len}; {i}++)"), inner)
+            }
+            _ => {
+                let name = self.define(schema)?;
+                call(format!("{name}_write(w, &{expr})"))
+            }
+        })
+    }
+
+    /// Returns statements that read a value of `schema` into `expr`.
+    fn read(&mut self, schema: &Schema, expr: &str, depth: usize) -> Result<Vec<String>> {
+        let call = |f: String| vec![format!("if (!{f}) return false;")];
+        Ok(match schema {
+            Schema::Unit => vec![],
+            Schema::Tuple([]) => vec![],
+            Schema::Bool => call(format!("hubpack_read_bool(r, &{expr})")),
+            Schema::U8 | Schema::U16 | Schema::U32 | Schema::U64 => {
+                let ty = self.c_type(schema)?;
+                block("", vec![
+                    "uint64_t t;".into(),
+                    format!("if (!hubpack_read_uint(r, &t, {})) return false;", max_size(schema)?),
+                    format!("{expr} = ({ty})t;"),
+                ])
+            }
+            Schema::I8 | Schema::I16 | Schema::I32 | Schema::I64 => {
+                let ty = self.c_type(schema)?;
+                block("", vec![
+                    "int64_t t;".into(),
+                    format!("if (!hubpack_read_int(r, &t, {})) return false;", max_size(schema)?),
+                    format!("{expr} = ({ty})t;"),
+                ])
+            }
+            Schema::F32 => call(format!("hubpack_read_f32(r, &{expr})")),
+            Schema::F64 => call(format!("hubpack_read_f64(r, &{expr})")),
+            Schema::Char => call(format!("hubpack_read_char(r, &{expr})")),
+            Schema::U128 | Schema::I128 => return Err(unsupported_128()),
+            Schema::FixedStr { len } => call(format!("hubpack_read_fixed_str(r, {expr}, {len})")),
+            Schema::Array { len, element } => {
+                let i = format!("i{depth}");
+                let inner = self.read(element, &format!("{expr}[{i}]"), depth + 1)?;
+                block(&format!("for (size_t {i} = 0; {i} < {len}; {i}++)"), inner)
+            }
+            _ => {
+                let name = self.define(schema)?;
+                call(format!("{name}_read(r, &{expr})"))
+            }
+        })
+    }
+}
+
+/// Returns the name of the C type for a compound schema.
+fn type_name(schema: &Schema) -> Result<String> {
+    Ok(match schema {
+        Schema::Unit => "unit".into(),
+        Schema::Bool => "bool".into(),
+        Schema::U8 => "u8".into(),
+        Schema::U16 => "u16".into(),
+        Schema::U32 => "u32".into(),
+        Schema::U64 => "u64".into(),
+        Schema::I8 => "i8".into(),
+        Schema::I16 => "i16".into(),
+        Schema::I32 => "i32".into(),
+        Schema::I64 => "i64".into(),
+        Schema::F32 => "f32".into(),
+        Schema::F64 => "f64".into(),
+        Schema::Char => "char".into(),
+        Schema::U128 | Schema::I128 => return Err(unsupported_128()),
+        Schema::Option(inner) => format!("Option_{}", type_name(inner)?),
+        // Counts keep names unambiguous when these are nested.
+        Schema::Array { len, element } => format!("Array{len}_{}", type_name(element)?),
+        Schema::Tuple(elements) => {
+            let mut name = format!("Tuple{}", elements.len());
+            for e in elements.iter() {
+                name.push('_');
+                name.push_str(&type_name(e)?);
+            }
+            name
+        }
+        Schema::Seq { max_len: Some(max), element } => format!("Seq{max}_{}", type_name(element)?),
+        Schema::Str { max_len: Some(max) } => format!("Str{max}"),
+        Schema::Seq { max_len: None, element } => {
+            return Err(Error::Unbounded(format!("a sequence of {}", type_name(element)?)));
+        }
+        Schema::Str { max_len: None } => return Err(Error::Unbounded("a string".into())),
+        Schema::FixedStr { len } => format!("FixedStr{len}"),
+        Schema::Struct { name, .. } | Schema::Enum { name, .. } => name.to_string(),
+    })
+}
+
+/// Returns the C names and types of a list of fields.
+fn members(fields: &Fields) -> Vec<(String, &Schema)> {
+    match fields {
+        Fields::Unit => vec![],
+        Fields::Tuple(list) => list.iter().enumerate().map(|(i, s)| (format!("_{i}"), s)).collect(),
+        Fields::Named(list) => list.iter().map(|f| (ident(f.name), &f.schema)).collect(),
+    }
+}
+
+/// Turns a Rust field name into a C identifier, avoiding C keywords.
+fn ident(name: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "auto", "char", "const", "default", "do", "double", "extern", "float",
+        "goto", "int", "long", "register", "short", "signed", "sizeof",
+        "static", "switch", "typedef", "union", "unsigned", "void", "volatile",
+        "inline", "restrict", "bool",
+    ];
+    if KEYWORDS.contains(&name) {
+        format!("{name}_")
+    } else {
+        name.to_string()
+    }
+}
+
+fn unsupported_128() -> Error {
+    Error::Unsupported("a 128-bit integer, which C99 lacks,".into())
+}
+
+/// Wraps `body` in braces, after `head`.
+fn block(head: &str, body: Vec<String>) -> Vec<String> {
+    let open = if head.is_empty() { "{".to_string() } else { format!("{head} {{") };
+    let mut out = vec![open];
+    out.extend(body.into_iter().map(|line| format!("    {line}")));
+    out.push("}".into());
+    out
+}
+
+/// Joins lines, indented by `level` steps, each ending in a newline.
+fn lines(code: &[String], level: usize) -> String {
+    let mut out = String::new();
+    for line in code {
+        out.push_str(&"    ".repeat(level));
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    use hubpack::{HubpackSchema, SerializedSize};
+
+    use crate::tests::{frames, hex, invalid_frames, scratch_dir, Body, Frame};
+
+    const MAIN: &str = r#"
+#include <stdio.h>
+#include <stdlib.h>
+#include "frame.h"
+
+int main(int argc, char **argv) {
+    uint8_t in[Frame_MAX_SIZE + 4], out[Frame_MAX_SIZE];
+    size_t n = 0, used, written, i;
+    const char *hex = argv[1];
+    Frame f;
+    (void)argc;
+    while (hex[2 * n]) {
+        unsigned b;
+        sscanf(hex + 2 * n, "%2x", &b);
+        in[n++] = (uint8_t)b;
+    }
+    if (!Frame_decode(&f, in, n, &used)) {
+        printf("invalid\n");
+        return 0;
+    }
+    if (!Frame_encode(&f, out, sizeof(out), &written)) return 1;
+    printf("%u %u ", (unsigned)Frame_MAX_SIZE, (unsigned)used);
+    for (i = 0; i < written; i++) printf("%02x", out[i]);
+    printf(" %d %.*s %d\n", (int)f.delta, (int)sizeof f.name, f.name, (int)f.body.tag);
+    return 0;
+}
+"#;
+
+    /// Builds a program using the header for `Frame`, or returns `None` if
+    /// there's no C compiler.
+    fn build() -> Option<PathBuf> {
+        let dir = scratch_dir("c");
+        std::fs::write(dir.join("frame.h"), header("FRAME_H", &[Frame::SCHEMA]).unwrap()).unwrap();
+        std::fs::write(dir.join("main.c"), MAIN).unwrap();
+        let exe = dir.join("main");
+        let status = Command::new("cc")
+            .args(["-std=c99", "-Wall", "-Wextra", "-pedantic", "-Werror", "-o"])
+            .arg(&exe)
+            .arg(dir.join("main.c"))
+            .status();
+        match status {
+            Ok(s) => {
+                assert!(s.success(), "generated C didn't compile");
+                Some(exe)
+            }
+            Err(e) => {
+                eprintln!("skipping C test: can't run cc: {e}");
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn c_round_trip() {
+        let Some(exe) = build() else { return };
+        let run = |input: &str| {
+            let out = Command::new(&exe).arg(input).output().unwrap();
+            assert!(out.status.success());
+            String::from_utf8(out.stdout).unwrap().trim().to_string()
+        };
+
+        let frames = frames();
+        for (f, tag) in frames.iter().zip([0, 1, 2]) {
+            let mut buf = [0; Frame::MAX_SIZE];
+            let n = hubpack::serialize(&mut buf, f).unwrap();
+            let encoded = hex(&buf[..n]);
+            // Trailing bytes aren't part of the message.
+            let expected = format!(
+                "{} {n} {encoded} {} {} {tag}",
+                Frame::MAX_SIZE, f.delta, f.name.as_str(),
+            );
+            assert_eq!(run(&format!("{encoded}ff")), expected);
+        }
+        for (what, bad) in invalid_frames() {
+            assert_eq!(run(&hex(&bad)), "invalid", "{what}");
+        }
+
+        std::fs::remove_dir_all(exe.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn c_errors() {
+        assert_eq!(
+            header("H", &[Schema::U8]),
+            Err(Error::Unsupported("a root type that isn't a struct or enum".into())),
+        );
+        assert!(matches!(
+            header("H", &[<Option<&str>>::SCHEMA]),
+            Err(Error::Unsupported(_)),
+        ));
+
+        #[derive(HubpackSchema)]
+        #[allow(dead_code)]
+        struct Unbounded<'a>(&'a [u8]);
+        assert_eq!(
+            header("H", &[Unbounded::SCHEMA]),
+            Err(Error::Unbounded("a sequence of u8".into())),
+        );
+
+        mod other {
+            #[derive(hubpack::HubpackSchema)]
+            #[allow(dead_code)]
+            pub struct Body(u8);
+        }
+        #[derive(HubpackSchema)]
+        #[allow(dead_code)]
+        struct Both<'a>(Body<'a>, other::Body);
+        assert_eq!(header("H", &[Both::SCHEMA]), Err(Error::NameClash("Body".into())));
+    }
+}
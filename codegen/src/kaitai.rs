@@ -0,0 +1,401 @@
+//! Kaitai Struct specifications.
+//!
+//! [`ksy`] produces a `.ksy` file describing a message type, which the Kaitai
+//! Struct compiler can turn into parsers for many languages, and which the
+//! Kaitai IDE can use to explore captured messages.
+//!
+//! The spec follows the format closely:
+//!
+//! - Structs become types whose `seq` lists their fields, with tuple fields
+//!   named `f0`, `f1`, and so on.
+//! - An enum `T` becomes a type with a `tag`, which uses the Kaitai enum
+//!   `t_tag` and is checked against the known variants, followed by `fields`,
+//!   whose type is switched on the tag. Variants without fields have no case.
+//! - An `Option` field `x` becomes a `u1` named `x_present`, followed by `x`,
+//!   which is only present if `x_present` is 1.
+//! - Sequences and strings must have a maximum length, and become a length
+//!   named `x_len`, followed by the elements or bytes. The length is a `u1` if
+//!   the maximum is at most 255, and a `u2` otherwise.
+//! - `bool`s and `char`s are validated like `hubpack` does. 128-bit integers,
+//!   which Kaitai lacks, are left as 16 raw bytes.
+//!
+//! Kaitai's `repeat` and `if` can only be used once per field, so an option,
+//! array, sequence, or string inside another one is given a helper type,
+//! named after its contents, like `option_u2` or `array4_str8`.
+//!
+//! Names are converted to Kaitai's `lower_snake_case`.
+
+use std::collections::HashMap;
+
+use hubpack::schema::{Fields, Schema};
+
+use crate::{check_roots, prefix_size, snake, Error, Result};
+
+/// Generates a Kaitai Struct specification for `root`, and every type it
+/// uses. The spec's ID is the name of `root` in `lower_snake_case`.
+///
+/// The root must be a struct or enum.
+pub fn ksy(root: &Schema) -> Result<String> {
+    check_roots(core::slice::from_ref(root))?;
+    let mut g = Gen::default();
+    let (Schema::Struct { name, .. } | Schema::Enum { name, .. }) = root else {
+        unreachable!("checked above")
+    };
+    g.sources.insert(snake(name), *root);
+    let seq = g.body(root)?;
+
+    let mut out = String::new();
+    out.push_str("# Generated by hubpack-codegen. Do not edit.\n");
+    out.push_str("meta:\n");
+    out.push_str(&format!("  id: {}\n", snake(name)));
+    out.push_str(&format!("  title: {name}\n"));
+    out.push_str("  endian: le\n");
+    out.push_str("  encoding: UTF-8\n");
+    out.push_str(&format!("doc: A hubpack-encoded {name}.\n"));
+    out.push_str(&seq_yaml(&seq, 0));
+    if !g.types.is_empty() {
+        out.push_str("types:\n");
+        for (name, seq) in &g.types {
+            out.push_str(&format!("  {name}:\n"));
+            out.push_str(&seq_yaml(seq, 4));
+        }
+    }
+    if !g.enums.is_empty() {
+        out.push_str("enums:\n");
+        for (name, values) in &g.enums {
+            out.push_str(&format!("  {name}:\n"));
+            for (index, variant) in values {
+                out.push_str(&format!("    {index}: {variant}\n"));
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Accepts the values that are `char`s: not surrogates, and not too large.
+const CHAR_VALID: &str = "  expr: _ < 0xd800 or (_ > 0xdfff and _ <= 0x10ffff)";
+
+/// One entry in a `seq`: its `id`, and its other keys as YAML lines.
+struct Attr {
+    id: String,
+    keys: Vec<String>,
+}
+
+impl Attr {
+    fn new(id: &str, keys: &[&str]) -> Self {
+        Self { id: id.to_string(), keys: keys.iter().map(|k| k.to_string()).collect() }
+    }
+
+    /// Checks whether this can have `repeat` or `if` added.
+    fn is_simple(&self) -> bool {
+        !self.keys.iter().any(|k| k.starts_with("repeat") || k.starts_with("if"))
+    }
+}
+
+#[derive(Default)]
+struct Gen {
+    /// Types defined so far, in order, with their `seq`s.
+    types: Vec<(String, Vec<Attr>)>,
+    /// The schema each type was made from, to catch name clashes.
+    sources: HashMap<String, Schema>,
+    /// Enums for tags, with their values.
+    enums: Vec<(String, Vec<(u8, String)>)>,
+}
+
+impl Gen {
+    /// Makes sure that the type for a struct, enum, tuple, or helper for
+    /// `schema` is defined, and returns its name.
+    fn define(&mut self, schema: &Schema) -> Result<String> {
+        let name = type_name(schema);
+        if let Some(prev) = self.sources.get(&name) {
+            if prev != schema {
+                return Err(Error::NameClash(name));
+            }
+            return Ok(name);
+        }
+        self.sources.insert(name.clone(), *schema);
+        let seq = match schema {
+            Schema::Struct { .. } | Schema::Enum { .. } | Schema::Tuple(_) => self.body(schema)?,
+            _ => self.attrs("value", schema)?,
+        };
+        self.types.push((name.clone(), seq));
+        Ok(name)
+    }
+
+    /// Returns the `seq` for a struct, enum, or tuple.
+    fn body(&mut self, schema: &Schema) -> Result<Vec<Attr>> {
+        match schema {
+            Schema::Struct { fields, .. } => self.fields(fields),
+            Schema::Tuple(elements) => self.fields(&Fields::Tuple(elements)),
+            Schema::Enum { name, variants } => {
+                let tag_enum = format!("{}_tag", snake(name));
+                let mut values = vec![];
+                let mut any_of = vec!["valid:".to_string(), "  any-of:".into()];
+                let mut cases = vec![];
+                for v in variants.iter() {
+                    let variant = snake(v.name);
+                    values.push((v.index, variant.clone()));
+                    any_of.push(format!("    - {tag_enum}::{variant}"));
+
+                    let seq = self.fields(&v.fields)?;
+                    if !seq.is_empty() {
+                        let type_name = format!("{}_{variant}", snake(name));
+                        if self.sources.contains_key(&type_name) {
+                            return Err(Error::NameClash(type_name));
+                        }
+                        self.sources.insert(type_name.clone(), *schema);
+                        self.types.push((type_name.clone(), seq));
+                        cases.push(format!("    '{tag_enum}::{variant}': {type_name}"));
+                    }
+                }
+                self.enums.push((tag_enum.clone(), values));
+
+                let mut tag = Attr::new("tag", &["type: u1", &format!("enum: {tag_enum}")]);
+                tag.keys.extend(any_of);
+                let mut seq = vec![tag];
+                if !cases.is_empty() {
+                    let mut keys = vec!["type:".to_string(), "  switch-on: tag".into(), "  cases:".into()];
+                    keys.extend(cases);
+                    seq.push(Attr { id: "fields".into(), keys });
+                }
+                Ok(seq)
+            }
+            _ => unreachable!("only called for compound types"),
+        }
+    }
+
+    fn fields(&mut self, fields: &Fields) -> Result<Vec<Attr>> {
+        let mut seq = vec![];
+        match fields {
+            Fields::Unit => (),
+            Fields::Tuple(list) => {
+                for (i, s) in list.iter().enumerate() {
+                    seq.extend(self.attrs(&format!("f{i}"), s)?);
+                }
+            }
+            Fields::Named(list) => {
+                for f in list.iter() {
+                    seq.extend(self.attrs(&ident(f.name), &f.schema)?);
+                }
+            }
+        }
+        Ok(seq)
+    }
+
+    /// Returns the attributes for a field `id` holding `schema`.
+    fn attrs(&mut self, id: &str, schema: &Schema) -> Result<Vec<Attr>> {
+        let len_id = format!("{id}_len");
+        let length = |max_len: &Option<usize>, what: &str| -> Result<Attr> {
+            let max = max_len.ok_or_else(|| Error::Unbounded(what.into()))?;
+            let ty = if prefix_size(max)? == 1 { "type: u1" } else { "type: u2" };
+            Ok(Attr::new(&len_id, &[ty, "valid:", &format!("  max: {max}")]))
+        };
+        Ok(match schema {
+            Schema::Unit | Schema::Tuple([]) => vec![],
+            Schema::Bool => vec![Attr::new(id, &["type: u1", "valid:", "  max: 1"])],
+            Schema::U8 => vec![Attr::new(id, &["type: u1"])],
+            Schema::U16 => vec![Attr::new(id, &["type: u2"])],
+            Schema::U32 => vec![Attr::new(id, &["type: u4"])],
+            Schema::U64 => vec![Attr::new(id, &["type: u8"])],
+            Schema::I8 => vec![Attr::new(id, &["type: s1"])],
+            Schema::I16 => vec![Attr::new(id, &["type: s2"])],
+            Schema::I32 => vec![Attr::new(id, &["type: s4"])],
+            Schema::I64 => vec![Attr::new(id, &["type: s8"])],
+            Schema::U128 | Schema::I128 => vec![Attr::new(id, &["size: 16"])],
+            Schema::F32 => vec![Attr::new(id, &["type: f4"])],
+            Schema::F64 => vec![Attr::new(id, &["type: f8"])],
+            Schema::Char => vec![Attr::new(id, &["type: u4", "valid:", CHAR_VALID])],
+            Schema::FixedStr { len } => vec![Attr::new(id, &["type: strz", &format!("size: {len}")])],
+            Schema::Str { max_len } => vec![
+                length(max_len, "a string")?,
+                Attr::new(id, &["type: str", &format!("size: {len_id}")]),
+            ],
+            Schema::Option(inner) => {
+                let present = format!("{id}_present");
+                let mut value = self.element(id, inner)?;
+                value.keys.push(format!("if: {present} == 1"));
+                vec![Attr::new(&present, &["type: u1", "valid:", "  max: 1"]), value]
+            }
+            Schema::Array { len, element } => {
+                let mut value = self.element(id, element)?;
+                value.keys.extend(["repeat: expr".to_string(), format!("repeat-expr: {len}")]);
+                vec![value]
+            }
+            Schema::Seq { max_len, element } => {
+                let mut value = self.element(id, element)?;
+                value.keys.extend(["repeat: expr".to_string(), format!("repeat-expr: {len_id}")]);
+                vec![length(max_len, "a sequence")?, value]
+            }
+            Schema::Tuple(_) | Schema::Struct { .. } | Schema::Enum { .. } => {
+                let name = self.define(schema)?;
+                vec![Attr::new(id, &[&format!("type: {name}")])]
+            }
+        })
+    }
+
+    /// Returns a single attribute for a field `id` holding `schema`, which
+    /// can have `repeat` or `if` added, using a helper type if needed.
+    fn element(&mut self, id: &str, schema: &Schema) -> Result<Attr> {
+        let mut attrs = self.attrs(id, schema)?;
+        if attrs.len() == 1 && attrs[0].is_simple() {
+            return Ok(attrs.remove(0));
+        }
+        let name = self.define(schema)?;
+        Ok(Attr::new(id, &[&format!("type: {name}")]))
+    }
+}
+
+/// Formats a `seq`, indented by `indent` spaces.
+fn seq_yaml(seq: &[Attr], indent: usize) -> String {
+    let pad = " ".repeat(indent);
+    if seq.is_empty() {
+        return format!("{pad}seq: []\n");
+    }
+    let mut out = format!("{pad}seq:\n");
+    for attr in seq {
+        out.push_str(&format!("{pad}  - id: {}\n", attr.id));
+        for key in &attr.keys {
+            out.push_str(&format!("{pad}    {key}\n"));
+        }
+    }
+    out
+}
+
+/// Returns the name of the type for a compound or helper schema.
+fn type_name(schema: &Schema) -> String {
+    match schema {
+        Schema::Unit => "unit".into(),
+        Schema::Bool => "bool".into(),
+        Schema::U8 => "u1".into(),
+        Schema::U16 => "u2".into(),
+        Schema::U32 => "u4".into(),
+        Schema::U64 => "u8".into(),
+        Schema::U128 => "u16".into(),
+        Schema::I8 => "s1".into(),
+        Schema::I16 => "s2".into(),
+        Schema::I32 => "s4".into(),
+        Schema::I64 => "s8".into(),
+        Schema::I128 => "s16".into(),
+        Schema::F32 => "f4".into(),
+        Schema::F64 => "f8".into(),
+        Schema::Char => "char".into(),
+        Schema::Option(inner) => format!("option_{}", type_name(inner)),
+        // Counts keep names unambiguous when these are nested.
+        Schema::Array { len, element } => format!("array{len}_{}", type_name(element)),
+        Schema::Tuple(elements) => {
+            let mut name = format!("tuple{}", elements.len());
+            for e in elements.iter() {
+                name.push('_');
+                name.push_str(&type_name(e));
+            }
+            name
+        }
+        Schema::Seq { max_len: Some(max), element } => format!("seq{max}_{}", type_name(element)),
+        Schema::Seq { max_len: None, element } => format!("seq_{}", type_name(element)),
+        Schema::Str { max_len: Some(max) } => format!("str{max}"),
+        Schema::Str { max_len: None } => "str".into(),
+        Schema::FixedStr { len } => format!("fixed_str{len}"),
+        Schema::Struct { name, .. } | Schema::Enum { name, .. } => snake(name),
+    }
+}
+
+/// Turns a Rust field name into a Kaitai identifier, which must start with a
+/// letter.
+fn ident(name: &str) -> String {
+    let name = snake(name);
+    if name.starts_with(|c: char| c.is_ascii_lowercase()) {
+        name
+    } else {
+        format!("f{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Frame;
+    use hubpack::HubpackSchema;
+
+    #[test]
+    fn frame_spec() {
+        let spec = ksy(&Frame::SCHEMA).unwrap();
+        assert!(spec.contains("meta:\n  id: frame\n  title: Frame\n  endian: le\n"));
+        for expected in [
+            // Options, arrays and sequences.
+            "  - id: flags\n    type: u1\n    valid:\n      max: 1\n    repeat: expr\n    repeat-expr: 2\n",
+            "  - id: name\n    type: strz\n    size: 6\n",
+            "  - id: tail_present\n    type: u1\n    valid:\n      max: 1\n\
+             \x20 - id: tail\n    type: tuple2_u1_s1\n    if: tail_present == 1\n",
+            "  - id: samples_len\n    type: u1\n    valid:\n      max: 3\n\
+             \x20 - id: samples\n    type: s2\n    repeat: expr\n    repeat-expr: samples_len\n",
+            // The enum and its variants.
+            "  body:\n    seq:\n      - id: tag\n        type: u1\n        enum: body_tag\n",
+            "            - body_tag::text\n      - id: fields\n        type:\n          switch-on: tag\n",
+            "            'body_tag::reading': body_reading\n            'body_tag::text': body_text\n",
+            "  body_text:\n    seq:\n      - id: text_len\n",
+            "        type: str\n        size: text_len\n      - id: long\n        type: s8\n",
+            "enums:\n  body_tag:\n    0: ping\n    1: reading\n    2: text\n",
+        ] {
+            assert!(spec.contains(expected), "missing:\n{expected}\nin:\n{spec}");
+        }
+        // Variants without fields have no case.
+        assert!(!spec.contains("body_ping"));
+    }
+
+    #[derive(HubpackSchema)]
+    #[allow(dead_code)]
+    struct Nested {
+        grid: [[u8; 2]; 3],
+        maybe: Option<Option<u16>>,
+        #[serde(with = "hubpack::MaxLen::<4>")]
+        names: &'static [FixedStrs],
+        _reserved: (),
+    }
+
+    #[derive(HubpackSchema)]
+    #[allow(dead_code)]
+    struct FixedStrs(hubpack::FixedStr<4>, u128);
+
+    #[test]
+    fn helper_types() {
+        let spec = ksy(&Nested::SCHEMA).unwrap();
+        for expected in [
+            "  - id: grid\n    type: array2_u1\n    repeat: expr\n    repeat-expr: 3\n",
+            "  - id: maybe\n    type: option_u2\n    if: maybe_present == 1\n",
+            "  - id: names\n    type: fixed_strs\n",
+            "  array2_u1:\n    seq:\n      - id: value\n        type: u1\n        repeat: expr\n",
+            "  option_u2:\n    seq:\n      - id: value_present\n",
+            "  fixed_strs:\n    seq:\n      - id: f0\n        type: strz\n        size: 4\n\
+             \x20     - id: f1\n        size: 16\n",
+        ] {
+            assert!(spec.contains(expected), "missing:\n{expected}\nin:\n{spec}");
+        }
+        // Units take no space, so have no attributes.
+        assert!(!spec.contains("reserved"));
+    }
+
+    #[derive(HubpackSchema)]
+    #[allow(dead_code)]
+    struct Log(Event, EventStart);
+
+    #[derive(HubpackSchema)]
+    #[allow(dead_code)]
+    enum Event {
+        Start(u8),
+    }
+
+    #[derive(HubpackSchema)]
+    #[allow(dead_code)]
+    struct EventStart(u16);
+
+    #[derive(HubpackSchema)]
+    #[allow(dead_code)]
+    struct Unbounded(&'static str);
+
+    #[test]
+    fn kaitai_errors() {
+        assert_eq!(ksy(&Log::SCHEMA), Err(Error::NameClash("event_start".into())));
+        assert!(matches!(ksy(&<(u8, u8)>::SCHEMA), Err(Error::Unsupported(_))));
+        assert_eq!(ksy(&Unbounded::SCHEMA), Err(Error::Unbounded("a string".into())));
+    }
+}
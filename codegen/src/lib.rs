@@ -0,0 +1,337 @@
+//! Generating `hubpack` encoders and decoders for other languages.
+//!
+//! Peers that aren't written in Rust have to re-implement the format, and
+//! hand-written implementations tend to drift from the Rust types they're
+//! meant to match. This crate generates them instead, from the [`Schema`]s
+//! that `#[derive(HubpackSchema)]` produces, so that they stay in sync with
+//! the Rust definitions.
+//!
+//! The generators are plain functions from schemas to source text, so they can
+//! be called from a build script, a test, or a small program in the crate that
+//! defines the types:
+//!
+//! ```
+//! use hubpack::HubpackSchema;
+//!
+//! #[derive(HubpackSchema)]
+//! struct Reading {
+//!     sensor: u8,
+//!     value: Option<i16>,
+//! }
+//!
+//! let header = hubpack_codegen::c::header("READING_H", &[Reading::SCHEMA]).unwrap();
+//! assert!(header.contains("#define Reading_MAX_SIZE 4"));
+//! ```
+//!
+//! Generated code only handles the default little-endian variant of the
+//! format.
+
+use std::fmt;
+
+use hubpack::schema::{Fields, Schema};
+
+pub mod c;
+pub mod kaitai;
+pub mod python;
+pub mod rust;
+pub mod schema_file;
+pub mod wireshark;
+
+/// Reasons a schema can't be turned into code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// A sequence or string has no maximum length, so it can't be stored in
+    /// a fixed-size buffer. The payload describes where it was found.
+    Unbounded(String),
+    /// Two different types have the same name.
+    NameClash(String),
+    /// The schema uses something the target language can't represent.
+    Unsupported(String),
+    /// A schema file couldn't be read. The payload describes the problem and
+    /// where it was found.
+    Invalid(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unbounded(what) => write!(f, "{what} has no maximum length"),
+            Self::NameClash(name) => write!(f, "two different types are named {name}"),
+            Self::Unsupported(what) => write!(f, "{what} isn't supported"),
+            Self::Invalid(problem) => f.write_str(problem),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Computes the largest encoded size of a value described by `schema`. This
+/// matches `SerializedSize::MAX_SIZE` for the type the schema came from.
+pub fn max_size(schema: &Schema) -> Result<usize> {
+    Ok(match schema {
+        Schema::Unit => 0,
+        Schema::Bool | Schema::U8 | Schema::I8 => 1,
+        Schema::U16 | Schema::I16 => 2,
+        Schema::U32 | Schema::I32 | Schema::F32 | Schema::Char => 4,
+        Schema::U64 | Schema::I64 | Schema::F64 => 8,
+        Schema::U128 | Schema::I128 => 16,
+        Schema::Option(inner) => 1 + max_size(inner)?,
+        Schema::Array { len, element } => len * max_size(element)?,
+        Schema::Tuple(elements) => sum_sizes(elements.iter())?,
+        Schema::Seq { max_len: Some(n), element } => prefix_size(*n)? + n * max_size(element)?,
+        Schema::Seq { max_len: None, .. } => return Err(Error::Unbounded("a sequence".into())),
+        Schema::Str { max_len: Some(n) } => prefix_size(*n)? + n,
+        Schema::Str { max_len: None } => return Err(Error::Unbounded("a string".into())),
+        Schema::FixedStr { len } => *len,
+        Schema::Struct { fields, .. } => fields_max_size(fields)?,
+        Schema::Enum { variants, .. } => {
+            let mut max = 0;
+            for v in variants.iter() {
+                max = max.max(fields_max_size(&v.fields)?);
+            }
+            1 + max
+        }
+    })
+}
+
+/// Returns the size of the length prefix of a sequence or string whose
+/// maximum length is `max_len`, like `hubpack::size::len_prefix_size`, but
+/// failing for maximums too long to encode.
+pub(crate) fn prefix_size(max_len: usize) -> Result<usize> {
+    if max_len > usize::from(u16::MAX) {
+        return Err(Error::Unsupported(format!("a maximum length of {max_len}")));
+    }
+    Ok(hubpack::size::len_prefix_size(max_len))
+}
+
+fn fields_max_size(fields: &Fields) -> Result<usize> {
+    match fields {
+        Fields::Unit => Ok(0),
+        Fields::Tuple(list) => sum_sizes(list.iter()),
+        Fields::Named(list) => sum_sizes(list.iter().map(|f| &f.schema)),
+    }
+}
+
+fn sum_sizes<'a>(schemas: impl Iterator<Item = &'a Schema>) -> Result<usize> {
+    let mut total = 0;
+    for s in schemas {
+        total += max_size(s)?;
+    }
+    Ok(total)
+}
+
+/// Checks that generators were given structs or enums to start from.
+fn check_roots(roots: &[Schema]) -> Result<()> {
+    for root in roots {
+        if !matches!(root, Schema::Struct { .. } | Schema::Enum { .. }) {
+            return Err(Error::Unsupported("a root type that isn't a struct or enum".into()));
+        }
+    }
+    Ok(())
+}
+
+/// Returns every struct and enum used by `roots`, including the roots, with
+/// each type after the types it uses.
+fn named_types(roots: &[Schema]) -> Result<Vec<Schema>> {
+    fn visit(schema: &Schema, out: &mut Vec<Schema>) -> Result<()> {
+        let fields = |fields: &Fields| -> Vec<&Schema> {
+            match fields {
+                Fields::Unit => vec![],
+                Fields::Tuple(list) => list.iter().collect(),
+                Fields::Named(list) => list.iter().map(|f| &f.schema).collect(),
+            }
+        };
+        let (name, children) = match schema {
+            Schema::Option(inner) => return visit(inner, out),
+            Schema::Array { element, .. } | Schema::Seq { element, .. } => {
+                return visit(element, out);
+            }
+            Schema::Tuple(list) => {
+                return list.iter().try_for_each(|s| visit(s, out));
+            }
+            Schema::Struct { name, fields: f } => (name, fields(f)),
+            Schema::Enum { name, variants } => {
+                (name, variants.iter().flat_map(|v| fields(&v.fields)).collect())
+            }
+            _ => return Ok(()),
+        };
+        let same_name = |s: &Schema| matches!(
+            s,
+            Schema::Struct { name: n, .. } | Schema::Enum { name: n, .. } if n == name
+        );
+        if let Some(prev) = out.iter().find(|s| same_name(s)) {
+            return if prev == schema { Ok(()) } else { Err(Error::NameClash(name.to_string())) };
+        }
+        for child in children {
+            visit(child, out)?;
+        }
+        out.push(*schema);
+        Ok(())
+    }
+
+    check_roots(roots)?;
+    let mut out = vec![];
+    for root in roots {
+        visit(root, &mut out)?;
+    }
+    Ok(out)
+}
+
+/// Converts a Rust name, like `HttpRequest` or `raw_len`, to
+/// `lower_snake_case`.
+pub(crate) fn snake(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut out = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i != 0 {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            if prev.is_lowercase() || prev.is_ascii_digit() || (prev.is_uppercase() && next_lower) {
+                out.push('_');
+            }
+        }
+        out.extend(c.to_lowercase());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    use hubpack::{FixedStr, HubpackSchema, SerializedSize};
+    use serde::Serialize;
+
+    /// A message using most kinds of types, for testing generated code.
+    #[derive(Serialize, HubpackSchema, SerializedSize)]
+    pub(crate) struct Frame<'a> {
+        pub id: u16,
+        pub delta: i32,
+        pub flags: [bool; 2],
+        pub name: FixedStr<6>,
+        pub tail: Option<(u8, i8)>,
+        #[serde(with = "hubpack::MaxLen::<3>")]
+        pub samples: &'a [i16],
+        pub body: Body<'a>,
+    }
+
+    #[derive(Serialize, HubpackSchema, SerializedSize)]
+    pub(crate) enum Body<'a> {
+        Ping,
+        Reading(char, f32),
+        Text {
+            #[serde(with = "hubpack::MaxLen::<8>")]
+            text: &'a str,
+            long: i64,
+        },
+    }
+
+    /// Frames using each variant of `Body`, in order.
+    pub(crate) fn frames() -> [Frame<'static>; 3] {
+        [
+            Frame {
+                id: 0,
+                delta: 0,
+                flags: [false, false],
+                name: FixedStr::new(),
+                tail: Some((0, 0)),
+                samples: &[1, 2, 3],
+                body: Body::Ping,
+            },
+            Frame {
+                id: 0x1234,
+                delta: -5,
+                flags: [true, false],
+                name: FixedStr::try_from("abc").unwrap(),
+                tail: Some((7, -7)),
+                samples: &[-1, 300],
+                body: Body::Reading('é', 1.5),
+            },
+            Frame {
+                id: 1,
+                delta: i32::MIN,
+                flags: [false, true],
+                name: FixedStr::try_from("sixsix").unwrap(),
+                tail: None,
+                samples: &[],
+                body: Body::Text { text: "hi ✓", long: -1 },
+            },
+        ]
+    }
+
+    /// Encodings that generated decoders should reject as a `Frame`, with
+    /// descriptions.
+    pub(crate) fn invalid_frames() -> Vec<(&'static str, Vec<u8>)> {
+        let mut buf = [0; Frame::MAX_SIZE];
+        let n = hubpack::serialize(&mut buf, &frames()[1]).unwrap();
+        let good = &buf[..n];
+        let corrupt = |offset: usize, byte: u8| {
+            let mut bad = good.to_vec();
+            bad[offset] = byte;
+            bad
+        };
+        vec![
+            ("too short", good[..n - 1].to_vec()),
+            ("bool that isn't 0 or 1", corrupt(6, 2)),
+            ("option tag that isn't 0 or 1", corrupt(14, 2)),
+            ("too many samples", corrupt(17, 4)),
+            ("unknown variant", corrupt(n - 9, 3)),
+            ("surrogate char", corrupt(n - 7, 0xD8)),
+            ("name that isn't UTF-8", corrupt(8, 0xFF)),
+        ]
+    }
+
+    pub(crate) fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    /// Makes an empty directory for a test's generated files.
+    pub(crate) fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("hubpack-codegen-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[derive(HubpackSchema, SerializedSize)]
+    #[allow(dead_code)]
+    enum Message {
+        Ping,
+        Data([u8; 3], Option<u32>),
+        Named { a: (u8, i64), b: hubpack::FixedStr<5> },
+    }
+
+    #[test]
+    fn test_frames() {
+        // Check that the test data means what it's meant to.
+        let mut buf = [0; Frame::MAX_SIZE];
+        for f in frames() {
+            let n = hubpack::serialize(&mut buf, &f).unwrap();
+            assert!(hubpack::dynamic::decode_dynamic(&Frame::SCHEMA, &buf[..n]).is_ok());
+        }
+        for (what, bad) in invalid_frames() {
+            assert!(hubpack::dynamic::decode_dynamic(&Frame::SCHEMA, &bad).is_err(), "{what}");
+        }
+    }
+
+    #[test]
+    fn max_sizes_match() {
+        assert_eq!(max_size(&Message::SCHEMA), Ok(Message::MAX_SIZE));
+        assert_eq!(max_size(&<Option<char>>::SCHEMA), Ok(<Option<char>>::MAX_SIZE));
+        assert_eq!(
+            max_size(&<&str>::SCHEMA),
+            Err(Error::Unbounded("a string".into())),
+        );
+    }
+
+    #[test]
+    fn snake_case() {
+        assert_eq!(snake("HttpRequest"), "http_request");
+        assert_eq!(snake("HTTPRequest2Go"), "http_request2_go");
+        assert_eq!(snake("raw_len"), "raw_len");
+    }
+}
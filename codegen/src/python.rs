@@ -0,0 +1,592 @@
+//! Python modules.
+//!
+//! [`module`] produces a pure-Python module, with no dependencies outside the
+//! standard library. Each struct becomes a dataclass, and each enum `T`
+//! becomes a class `T` with a dataclass subclass `T_Variant` for each variant.
+//! These all have:
+//!
+//! - `encode(self) -> bytes`, which raises `EncodeError` if a value doesn't
+//!   fit its type, such as an integer out of range or an overlong list;
+//! - `decode(data) -> (value, rest)`, a class method that returns the decoded
+//!   value and the bytes after it, and raises `DecodeError` on the same inputs
+//!   that `hubpack::deserialize` rejects;
+//! - `MAX_SIZE`, the largest encoded size, or `None` if there's no limit.
+//!
+//! The module also has `encode(value)` and `decode(data)` functions, where
+//! `decode` decodes the first root type.
+//!
+//! Integers become `int`, `f32` and `f64` become `float`, and `char`s and all
+//! kinds of strings become `str`. `()` is `None`, and so is a missing
+//! `Option`. An `Option` of `()` or of another `Option` holds a present value
+//! `v` as the tuple `(v,)`, so that it can't be mistaken for a missing one.
+//! Arrays and sequences are lists, and tuples are tuples. Fields of tuple
+//! structs and variants are named `_0`, `_1`, and so on.
+
+use hubpack::schema::{Fields, Schema};
+
+use crate::{max_size, named_types, prefix_size, Error, Result};
+
+/// Generates a Python module for the types described by `roots`, and every
+/// type they use.
+///
+/// The roots must be structs or enums.
+pub fn module(roots: &[Schema]) -> Result<String> {
+    let types = named_types(roots)?;
+
+    let mut out = String::new();
+    out.push_str("# Generated by hubpack-codegen. Do not edit.\n");
+    out.push_str(RUNTIME);
+    for schema in &types {
+        out.push_str("\n\n");
+        match schema {
+            Schema::Struct { name, fields } => {
+                out.push_str(&lines(&class(name, "_Message", schema, fields, None)?));
+            }
+            Schema::Enum { name, variants } => {
+                let mut code = vec![
+                    format!("class {name}(_Message):"),
+                    format!("    MAX_SIZE = {}", max(schema)),
+                    String::new(),
+                    "    @staticmethod".into(),
+                    "    def _read(r):".into(),
+                    "        tag = r.uint(1)".into(),
+                ];
+                for v in variants.iter() {
+                    code.push(format!("        if tag == {}:", v.index));
+                    let call = construct(&format!("{name}_{}", v.name), &v.fields)?;
+                    code.extend(call.into_iter().map(|l| format!("            {l}")));
+                }
+                code.push(format!("        raise DecodeError(f\"invalid {name} variant {{tag}}\")"));
+                out.push_str(&lines(&code));
+                for v in variants.iter() {
+                    out.push_str("\n\n");
+                    let class_name = format!("{name}_{}", v.name);
+                    let code = class(&class_name, name, schema, &v.fields, Some(v.index))?;
+                    out.push_str(&lines(&code));
+                }
+            }
+            _ => unreachable!("only structs and enums are named"),
+        }
+    }
+
+    out.push_str("\n\ndef encode(value) -> bytes:\n");
+    out.push_str("    \"\"\"Encodes a message.\"\"\"\n");
+    out.push_str("    return value.encode()\n");
+    if let Some(Schema::Struct { name, .. } | Schema::Enum { name, .. }) = roots.first() {
+        out.push_str(&format!("\n\ndef decode(data) -> Tuple[{name}, bytes]:\n"));
+        out.push_str(&format!(
+            "    \"\"\"Decodes a {name}, returning it and the bytes after it.\"\"\"\n",
+        ));
+        out.push_str(&format!("    return {name}.decode(data)\n"));
+    }
+    Ok(out)
+}
+
+/// Definitions shared by all generated modules.
+const RUNTIME: &str = r#"
+from __future__ import annotations
+
+import struct
+from dataclasses import dataclass
+from typing import List, Optional, Tuple
+
+
+class DecodeError(ValueError):
+    """Raised when bytes aren't a valid encoding of the expected type."""
+
+
+class EncodeError(ValueError):
+    """Raised when a value can't be encoded as its type."""
+
+
+class _Reader:
+    def __init__(self, data):
+        self.data = bytes(data)
+        self.pos = 0
+
+    def take(self, n):
+        if len(self.data) - self.pos < n:
+            raise DecodeError("truncated")
+        b = self.data[self.pos:self.pos + n]
+        self.pos += n
+        return b
+
+    def uint(self, n):
+        return int.from_bytes(self.take(n), "little")
+
+    def sint(self, n):
+        return int.from_bytes(self.take(n), "little", signed=True)
+
+    def boolean(self):
+        b = self.uint(1)
+        if b > 1:
+            raise DecodeError(f"invalid bool {b}")
+        return b == 1
+
+    def f32(self):
+        return struct.unpack("<f", self.take(4))[0]
+
+    def f64(self):
+        return struct.unpack("<d", self.take(8))[0]
+
+    def char(self):
+        c = self.uint(4)
+        if c > 0x10FFFF or 0xD800 <= c <= 0xDFFF:
+            raise DecodeError(f"invalid char {c:#x}")
+        return chr(c)
+
+    def seq_len(self, max_len, prefix):
+        n = self.uint(prefix)
+        if n > max_len:
+            raise DecodeError(f"length {n} is over the maximum of {max_len}")
+        return n
+
+    def string(self, max_len, prefix):
+        return self.utf8(self.take(self.seq_len(max_len, prefix)))
+
+    def fixed_str(self, n):
+        # Like `FixedStr`, the string ends at the first NUL.
+        return self.utf8(self.take(n).split(b"\0", 1)[0])
+
+    @staticmethod
+    def utf8(b):
+        try:
+            return b.decode("utf-8")
+        except UnicodeDecodeError:
+            raise DecodeError("invalid UTF-8") from None
+
+
+class _Writer:
+    def __init__(self):
+        self.out = bytearray()
+
+    def uint(self, v, n):
+        try:
+            self.out += int(v).to_bytes(n, "little")
+        except OverflowError:
+            raise EncodeError(f"{v} doesn't fit in a u{8 * n}") from None
+
+    def sint(self, v, n):
+        try:
+            self.out += int(v).to_bytes(n, "little", signed=True)
+        except OverflowError:
+            raise EncodeError(f"{v} doesn't fit in an i{8 * n}") from None
+
+    def boolean(self, v):
+        self.out.append(1 if v else 0)
+
+    def f32(self, v):
+        self.out += struct.pack("<f", v)
+
+    def f64(self, v):
+        self.out += struct.pack("<d", v)
+
+    def char(self, v):
+        if len(v) != 1:
+            raise EncodeError(f"{v!r} isn't a single character")
+        self.uint(ord(v), 4)
+
+    def count(self, v, n):
+        if len(v) != n:
+            raise EncodeError(f"expected {n} elements, found {len(v)}")
+
+    def seq_len(self, n, max_len, prefix):
+        if n > max_len:
+            raise EncodeError(f"length {n} is over the maximum of {max_len}")
+        self.uint(n, prefix)
+
+    def string(self, v, max_len, prefix):
+        b = v.encode("utf-8")
+        self.seq_len(len(b), max_len, prefix)
+        self.out += b
+
+    def fixed_str(self, v, n):
+        b = v.encode("utf-8")
+        if len(b) > n or b"\0" in b:
+            raise EncodeError(f"{v!r} doesn't fit in {n} bytes without NULs")
+        self.out += b.ljust(n, b"\0")
+
+    @staticmethod
+    def expect(v, cls):
+        if not isinstance(v, cls):
+            raise EncodeError(f"expected {cls.__name__}, found {v!r}")
+
+
+class _Message:
+    def encode(self) -> bytes:
+        """Encodes this value."""
+        w = _Writer()
+        self._write(w)
+        return bytes(w.out)
+
+    @classmethod
+    def decode(cls, data):
+        """Decodes a value from the start of `data`, returning it and the
+        bytes after it."""
+        r = _Reader(data)
+        return cls._read(r), r.data[r.pos:]
+"#;
+
+/// Returns a dataclass for a struct, or for a variant with the given index.
+fn class(
+    name: &str,
+    base: &str,
+    schema: &Schema,
+    fields: &Fields,
+    index: Option<u8>,
+) -> Result<Vec<String>> {
+    let members = members(fields);
+    let mut code = vec!["@dataclass".into(), format!("class {name}({base}):")];
+    for (field, schema) in &members {
+        code.push(format!("    {field}: {}", py_type(schema)));
+    }
+    if !members.is_empty() {
+        code.push(String::new());
+    }
+
+    let mut write = vec![];
+    match index {
+        Some(index) => write.push(format!("w.uint({index}, 1)")),
+        None => {
+            code.push(format!("    MAX_SIZE = {}", max(schema)));
+            code.push(String::new());
+            code.push("    @staticmethod".into());
+            code.push("    def _read(r):".into());
+            let call = construct(name, fields)?;
+            code.extend(call.into_iter().map(|l| format!("        {l}")));
+            code.push(String::new());
+        }
+    }
+    for (field, schema) in &members {
+        write.extend(self::write(schema, &format!("self.{field}"), 0)?);
+    }
+    if write.is_empty() {
+        write.push("pass".into());
+    }
+    code.push("    def _write(self, w):".into());
+    code.extend(write.into_iter().map(|line| format!("        {line}")));
+    Ok(code)
+}
+
+/// Returns the Python names and types of a list of fields.
+fn members(fields: &Fields) -> Vec<(String, &Schema)> {
+    match fields {
+        Fields::Unit => vec![],
+        Fields::Tuple(list) => list.iter().enumerate().map(|(i, s)| (format!("_{i}"), s)).collect(),
+        Fields::Named(list) => list.iter().map(|f| (ident(f.name), &f.schema)).collect(),
+    }
+}
+
+/// Returns a statement that reads each of `fields`, in order, and returns a
+/// `class` made from them.
+fn construct(class: &str, fields: &Fields) -> Result<Vec<String>> {
+    let exprs = members(fields)
+        .into_iter()
+        .map(|(_, s)| read(s))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(match exprs.len() {
+        0 => vec![format!("return {class}()")],
+        1 => vec![format!("return {class}({})", exprs[0])],
+        _ => {
+            let mut code = vec![format!("return {class}(")];
+            code.extend(exprs.into_iter().map(|e| format!("    {e},")));
+            code.push(")".into());
+            code
+        }
+    })
+}
+
+/// Returns an expression that reads a value of `schema` from `r`.
+fn read(schema: &Schema) -> Result<String> {
+    Ok(match schema {
+        Schema::Unit => "None".into(),
+        Schema::Bool => "r.boolean()".into(),
+        Schema::U8 | Schema::U16 | Schema::U32 | Schema::U64 | Schema::U128 => {
+            format!("r.uint({})", max_size(schema)?)
+        }
+        Schema::I8 | Schema::I16 | Schema::I32 | Schema::I64 | Schema::I128 => {
+            format!("r.sint({})", max_size(schema)?)
+        }
+        Schema::F32 => "r.f32()".into(),
+        Schema::F64 => "r.f64()".into(),
+        Schema::Char => "r.char()".into(),
+        // The condition is evaluated first, so this reads the tag before the
+        // value.
+        Schema::Option(inner) if is_none(inner) => {
+            format!("(({},) if r.boolean() else None)", read(inner)?)
+        }
+        Schema::Option(inner) => format!("({} if r.boolean() else None)", read(inner)?),
+        Schema::Array { len, element } => format!("[{} for _ in range({len})]", read(element)?),
+        Schema::Tuple(elements) => {
+            let exprs = elements.iter().map(read).collect::<Result<Vec<_>>>()?;
+            match exprs.len() {
+                0 => "()".into(),
+                1 => format!("({},)", exprs[0]),
+                _ => format!("({})", exprs.join(", ")),
+            }
+        }
+        Schema::Seq { max_len, element } => format!(
+            "[{} for _ in range(r.seq_len({}))]",
+            read(element)?,
+            py_len(*max_len, "a sequence")?,
+        ),
+        Schema::Str { max_len } => format!("r.string({})", py_len(*max_len, "a string")?),
+        Schema::FixedStr { len } => format!("r.fixed_str({len})"),
+        Schema::Struct { name, .. } | Schema::Enum { name, .. } => format!("{name}._read(r)"),
+    })
+}
+
+/// Returns statements that write the value of `schema` at `expr` to `w`.
+fn write(schema: &Schema, expr: &str, depth: usize) -> Result<Vec<String>> {
+    let item = format!("v{depth}");
+    let nested = |lines: Vec<String>| lines.into_iter().map(|l| format!("    {l}"));
+    Ok(match schema {
+        Schema::Unit => vec![],
+        Schema::Bool => vec![format!("w.boolean({expr})")],
+        Schema::U8 | Schema::U16 | Schema::U32 | Schema::U64 | Schema::U128 => {
+            vec![format!("w.uint({expr}, {})", max_size(schema)?)]
+        }
+        Schema::I8 | Schema::I16 | Schema::I32 | Schema::I64 | Schema::I128 => {
+            vec![format!("w.sint({expr}, {})", max_size(schema)?)]
+        }
+        Schema::F32 => vec![format!("w.f32({expr})")],
+        Schema::F64 => vec![format!("w.f64({expr})")],
+        Schema::Char => vec![format!("w.char({expr})")],
+        Schema::Option(inner) => {
+            let mut code = vec![
+                format!("if {expr} is None:"),
+                "    w.boolean(False)".into(),
+                "else:".into(),
+                "    w.boolean(True)".into(),
+            ];
+            if is_none(inner) {
+                code.push(format!("    w.count({expr}, 1)"));
+                code.extend(nested(write(inner, &format!("{expr}[0]"), depth)?));
+            } else {
+                code.extend(nested(write(inner, expr, depth)?));
+            }
+            code
+        }
+        Schema::Array { len, element } => {
+            let mut code = vec![format!("w.count({expr}, {len})")];
+            let inner = write(element, &item, depth + 1)?;
+            if !inner.is_empty() {
+                code.push(format!("for {item} in {expr}:"));
+                code.extend(nested(inner));
+            }
+            code
+        }
+        Schema::Tuple(elements) => {
+            let mut code = vec![format!("w.count({expr}, {})", elements.len())];
+            for (i, e) in elements.iter().enumerate() {
+                code.extend(write(e, &format!("{expr}[{i}]"), depth)?);
+            }
+            code
+        }
+        Schema::Seq { max_len, element } => {
+            let mut code = vec![format!("w.seq_len(len({expr}), {})", py_len(*max_len, "a sequence")?)];
+            let inner = write(element, &item, depth + 1)?;
+            if !inner.is_empty() {
+                code.push(format!("for {item} in {expr}:"));
+                code.extend(nested(inner));
+            }
+            code
+        }
+        Schema::Str { max_len } => {
+            vec![format!("w.string({expr}, {})", py_len(*max_len, "a string")?)]
+        }
+        Schema::FixedStr { len } => vec![format!("w.fixed_str({expr}, {len})")],
+        Schema::Struct { name, .. } | Schema::Enum { name, .. } => vec![
+            format!("w.expect({expr}, {name})"),
+            format!("{expr}._write(w)"),
+        ],
+    })
+}
+
+/// Checks whether a value of `schema` can be `None`, so that an `Option` of
+/// it has to wrap a present value to tell the two apart.
+fn is_none(schema: &Schema) -> bool {
+    matches!(schema, Schema::Unit | Schema::Option(_))
+}
+
+/// Returns the type annotation for a value of `schema`.
+fn py_type(schema: &Schema) -> String {
+    match schema {
+        Schema::Unit => "None".into(),
+        Schema::Bool => "bool".into(),
+        Schema::F32 | Schema::F64 => "float".into(),
+        Schema::Char | Schema::Str { .. } | Schema::FixedStr { .. } => "str".into(),
+        Schema::Option(inner) if is_none(inner) => format!("Optional[Tuple[{}]]", py_type(inner)),
+        Schema::Option(inner) => format!("Optional[{}]", py_type(inner)),
+        Schema::Array { element, .. } | Schema::Seq { element, .. } => {
+            format!("List[{}]", py_type(element))
+        }
+        Schema::Tuple([]) => "Tuple[()]".into(),
+        Schema::Tuple(elements) => {
+            let types = elements.iter().map(py_type).collect::<Vec<_>>();
+            format!("Tuple[{}]", types.join(", "))
+        }
+        Schema::Struct { name, .. } | Schema::Enum { name, .. } => name.to_string(),
+        _ => "int".into(),
+    }
+}
+
+fn max(schema: &Schema) -> String {
+    match max_size(schema) {
+        Ok(n) => n.to_string(),
+        Err(_) => "None".into(),
+    }
+}
+
+/// Returns the maximum length and prefix size arguments for a sequence or
+/// string, which must be bounded.
+fn py_len(max_len: Option<usize>, what: &str) -> Result<String> {
+    let max = max_len.ok_or_else(|| Error::Unbounded(what.into()))?;
+    Ok(format!("{max}, {}", prefix_size(max)?))
+}
+
+/// Turns a Rust field name into a Python identifier, avoiding keywords.
+fn ident(name: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "False", "None", "True", "and", "as", "assert", "async", "await",
+        "break", "class", "continue", "def", "del", "elif", "else", "except",
+        "finally", "for", "from", "global", "if", "import", "in", "is",
+        "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try",
+        "while", "with", "yield",
+    ];
+    if KEYWORDS.contains(&name) {
+        format!("{name}_")
+    } else {
+        name.to_string()
+    }
+}
+
+/// Joins lines, each ending in a newline.
+fn lines(code: &[String]) -> String {
+    let mut out = String::new();
+    for line in code {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    use hubpack::{HubpackSchema, SerializedSize};
+    use serde::Serialize;
+
+    use crate::tests::{frames, hex, invalid_frames, scratch_dir, Frame};
+
+    const CHECK: &str = r#"
+import dataclasses
+import sys
+
+import frame
+
+data = bytes.fromhex(sys.argv[1])
+try:
+    f, rest = frame.decode(data)
+except frame.DecodeError:
+    print("invalid")
+    sys.exit(0)
+print(frame.Frame.MAX_SIZE, len(data) - len(rest), frame.encode(f).hex(), repr(f))
+
+if len(sys.argv) > 2:
+    bad = [
+        dataclasses.replace(f, id=0x10000),
+        dataclasses.replace(f, delta=-(2 ** 31) - 1),
+        dataclasses.replace(f, flags=[True]),
+        dataclasses.replace(f, name="seven!!"),
+        dataclasses.replace(f, name="a\0b"),
+        dataclasses.replace(f, samples=[1, 2, 3, 4]),
+        dataclasses.replace(f, tail=(1,)),
+        dataclasses.replace(f, body=frame.Body_Text("123456789", 0)),
+        dataclasses.replace(f, body=frame.Body_Reading("ab", 0.0)),
+        dataclasses.replace(f, body=None),
+    ]
+    for b in bad:
+        try:
+            b.encode()
+            print("encoded", b)
+        except frame.EncodeError:
+            pass
+"#;
+
+    /// Options whose present values would be `None` without wrapping.
+    #[derive(Serialize, HubpackSchema, SerializedSize)]
+    struct Maybe {
+        unit: Option<()>,
+        nested: Option<Option<u8>>,
+    }
+
+    const CHECK_MAYBE: &str = r#"
+import sys
+
+import frame
+
+for arg in sys.argv[1:]:
+    m, rest = frame.Maybe.decode(bytes.fromhex(arg))
+    assert not rest
+    print(m.encode().hex(), repr(m))
+"#;
+
+    #[test]
+    fn python_round_trip() {
+        let dir = scratch_dir("python");
+        let code = module(&[Frame::SCHEMA, Maybe::SCHEMA]).unwrap();
+        std::fs::write(dir.join("frame.py"), code).unwrap();
+        std::fs::write(dir.join("check.py"), CHECK).unwrap();
+        std::fs::write(dir.join("check_maybe.py"), CHECK_MAYBE).unwrap();
+        let run_script = |script: &str, args: &[&str]| {
+            let out = match Command::new("python3").arg(dir.join(script)).args(args).output() {
+                Ok(out) => out,
+                Err(e) => {
+                    eprintln!("skipping Python test: can't run python3: {e}");
+                    return None;
+                }
+            };
+            assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+            Some(String::from_utf8(out.stdout).unwrap().trim().to_string())
+        };
+        let run = |args: &[&str]| run_script("check.py", args);
+
+        let reprs = [
+            "Frame(id=0, delta=0, flags=[False, False], name='', tail=(0, 0), \
+                samples=[1, 2, 3], body=Body_Ping())",
+            "Frame(id=4660, delta=-5, flags=[True, False], name='abc', tail=(7, -7), \
+                samples=[-1, 300], body=Body_Reading(_0='é', _1=1.5))",
+            "Frame(id=1, delta=-2147483648, flags=[False, True], name='sixsix', tail=None, \
+                samples=[], body=Body_Text(text='hi ✓', long=-1))",
+        ];
+        for (f, repr) in frames().iter().zip(reprs) {
+            let mut buf = [0; Frame::MAX_SIZE];
+            let n = hubpack::serialize(&mut buf, f).unwrap();
+            let encoded = hex(&buf[..n]);
+            // Trailing bytes aren't part of the message.
+            let Some(out) = run(&[&format!("{encoded}ff"), "errors"]) else { return };
+            assert_eq!(out, format!("{} {n} {encoded} {repr}", Frame::MAX_SIZE));
+        }
+        for (what, bad) in invalid_frames() {
+            assert_eq!(run(&[&hex(&bad)]).unwrap(), "invalid", "{what}");
+        }
+
+        let maybes = [
+            (Maybe { unit: None, nested: None }, "Maybe(unit=None, nested=None)"),
+            (Maybe { unit: Some(()), nested: Some(None) }, "Maybe(unit=(None,), nested=(None,))"),
+            (Maybe { unit: None, nested: Some(Some(5)) }, "Maybe(unit=None, nested=(5,))"),
+        ];
+        for (m, repr) in maybes {
+            let mut buf = [0; Maybe::MAX_SIZE];
+            let n = hubpack::serialize(&mut buf, &m).unwrap();
+            let encoded = hex(&buf[..n]);
+            let out = run_script("check_maybe.py", &[&encoded]).unwrap();
+            assert_eq!(out, format!("{encoded} {repr}"));
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,323 @@
+//! Rust types.
+//!
+//! [`module`] produces Rust definitions of types with the same encoding as the
+//! ones described by some schemas, for crates that need to talk to a peer but
+//! can't depend on the crate defining its messages. [`from_schema_file`] does
+//! the same for the types in a [schema file](crate::schema_file), which suits
+//! a build script:
+//!
+//! ```no_run
+//! // build.rs
+//! let text = std::fs::read_to_string("protocol.toml").unwrap();
+//! let code = hubpack_codegen::rust::from_schema_file(&text).unwrap();
+//! let out = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("protocol.rs");
+//! std::fs::write(out, code).unwrap();
+//! println!("cargo:rerun-if-changed=protocol.toml");
+//! ```
+//!
+//! The result can then be brought in with
+//! `include!(concat!(env!("OUT_DIR"), "/protocol.rs"))`.
+//!
+//! Every type is public, with public fields, and derives `Clone`, `Debug`,
+//! `PartialEq`, `serde`'s `Serialize` and `Deserialize`, and `hubpack`'s
+//! `SerializedSize` and `HubpackSchema`. The crate using them needs `serde`,
+//! with its `derive` feature, and if they have sequences or strings,
+//! `heapless`, with its `serde` feature, and `hubpack`'s `heapless` feature.
+//!
+//! Types are mapped as follows:
+//!
+//! - Sequences and strings must have a maximum length `N`, since
+//!   `SerializedSize` needs one, and become `heapless::Vec<T, N>` and
+//!   `heapless::String<N>`, declared with `#[serde(with =
+//!   "::hubpack::MaxLen::<N>")]`. That only applies to a whole field, so they
+//!   can't be nested inside other types, like `Option`, except as fields of
+//!   a struct or enum.
+//! - `usize` and `isize` become `u64` and `i64`, which are encoded the same
+//!   way.
+//! - Everything else becomes the type it describes, such as `[T; N]` or
+//!   `hubpack::FixedStr<N>`.
+//!
+//! Enums must have variants numbered 0, 1, 2, and so on, because `serde`
+//! numbers variants by their position.
+
+use hubpack::schema::{Fields, Schema};
+
+use crate::schema_file::SchemaFile;
+use crate::{named_types, Error, Result};
+
+const DERIVES: &str = "#[derive(Clone, Debug, PartialEq, ::serde::Serialize, \
+    ::serde::Deserialize, ::hubpack::SerializedSize, ::hubpack::HubpackSchema)]";
+
+/// Generates Rust definitions of the types described by `roots`, and every
+/// type they use.
+///
+/// The roots must be structs or enums.
+pub fn module(roots: &[Schema]) -> Result<String> {
+    let types = named_types(roots)?;
+
+    let mut out = String::new();
+    out.push_str("// Generated by hubpack-codegen. Do not edit.\n");
+    for schema in &types {
+        out.push('\n');
+        out.push_str(DERIVES);
+        out.push('\n');
+        match schema {
+            Schema::Struct { name, fields } => {
+                let name = ident(name, "type")?;
+                match fields {
+                    Fields::Unit => out.push_str(&format!("pub struct {name};\n")),
+                    Fields::Tuple(list) => {
+                        let types = list.iter()
+                            .map(|s| Ok(format!("{}pub {}", max_len_attr(s, " "), field_type(s)?)))
+                            .collect::<Result<Vec<_>>>()?;
+                        out.push_str(&format!("pub struct {name}({});\n", types.join(", ")));
+                    }
+                    Fields::Named(_) => {
+                        out.push_str(&format!("pub struct {name} {{\n"));
+                        out.push_str(&fields_code(fields, "    pub ")?);
+                        out.push_str("}\n");
+                    }
+                }
+            }
+            Schema::Enum { name, variants } => {
+                out.push_str(&format!("pub enum {} {{\n", ident(name, "type")?));
+                for (i, v) in variants.iter().enumerate() {
+                    if usize::from(v.index) != i {
+                        return Err(Error::Unsupported(format!(
+                            "enum {name}, whose variants aren't numbered 0, 1, 2, and so on,",
+                        )));
+                    }
+                    let variant = ident(v.name, "variant")?;
+                    match v.fields {
+                        Fields::Unit => out.push_str(&format!("    {variant},\n")),
+                        Fields::Tuple(list) => {
+                            let types = list.iter()
+                                .map(|s| Ok(format!("{}{}", max_len_attr(s, " "), field_type(s)?)))
+                                .collect::<Result<Vec<_>>>()?;
+                            out.push_str(&format!("    {variant}({}),\n", types.join(", ")));
+                        }
+                        Fields::Named(_) => {
+                            out.push_str(&format!("    {variant} {{\n"));
+                            out.push_str(&fields_code(&v.fields, "        ")?);
+                            out.push_str("    },\n");
+                        }
+                    }
+                }
+                out.push_str("}\n");
+            }
+            _ => unreachable!("only structs and enums are named"),
+        }
+    }
+    Ok(out)
+}
+
+/// Generates Rust definitions of every type in a
+/// [schema file](crate::schema_file).
+pub fn from_schema_file(text: &str) -> Result<String> {
+    let mut file = SchemaFile::parse(text)?;
+    module(&file.resolve_all()?)
+}
+
+/// Returns the lines declaring named fields, each starting with `prefix`.
+fn fields_code(fields: &Fields, prefix: &str) -> Result<String> {
+    let Fields::Named(list) = fields else {
+        unreachable!("only called for named fields")
+    };
+    let mut out = String::new();
+    for f in list.iter() {
+        let attr = max_len_attr(&f.schema, "\n");
+        if !attr.is_empty() {
+            // On its own line, indented like the field.
+            out.push_str(prefix.trim_end_matches("pub "));
+            out.push_str(&attr);
+        }
+        out.push_str(&format!("{prefix}{}: {},\n", ident(f.name, "field")?, field_type(&f.schema)?));
+    }
+    Ok(out)
+}
+
+/// Returns the `MaxLen` attribute a field described by `schema` needs, if it's
+/// a sequence or string, followed by `sep`.
+fn max_len_attr(schema: &Schema, sep: &str) -> String {
+    match schema {
+        Schema::Seq { max_len: Some(n), .. } | Schema::Str { max_len: Some(n) } => {
+            format!("#[serde(with = \"::hubpack::MaxLen::<{n}>\")]{sep}")
+        }
+        _ => String::new(),
+    }
+}
+
+/// Returns the type of a field described by `schema`. Unlike [`rust_type`],
+/// this allows sequences and strings, which the field declares the maximum
+/// length of.
+fn field_type(schema: &Schema) -> Result<String> {
+    Ok(match schema {
+        Schema::Seq { max_len: Some(n), element } => {
+            format!("::heapless::Vec<{}, {n}>", rust_type(element)?)
+        }
+        Schema::Str { max_len: Some(n) } => format!("::heapless::String<{n}>"),
+        _ => rust_type(schema)?,
+    })
+}
+
+fn rust_type(schema: &Schema) -> Result<String> {
+    Ok(match schema {
+        Schema::Unit | Schema::Tuple([]) => "()".into(),
+        Schema::Bool => "bool".into(),
+        Schema::U8 => "u8".into(),
+        Schema::U16 => "u16".into(),
+        Schema::U32 => "u32".into(),
+        Schema::U64 => "u64".into(),
+        Schema::U128 => "u128".into(),
+        Schema::I8 => "i8".into(),
+        Schema::I16 => "i16".into(),
+        Schema::I32 => "i32".into(),
+        Schema::I64 => "i64".into(),
+        Schema::I128 => "i128".into(),
+        Schema::F32 => "f32".into(),
+        Schema::F64 => "f64".into(),
+        Schema::Char => "char".into(),
+        Schema::Option(inner) => format!("Option<{}>", rust_type(inner)?),
+        Schema::Array { len, element } => format!("[{}; {len}]", rust_type(element)?),
+        Schema::Tuple([single]) => format!("({},)", rust_type(single)?),
+        Schema::Tuple(elements) => {
+            let types = elements.iter().map(rust_type).collect::<Result<Vec<_>>>()?;
+            format!("({})", types.join(", "))
+        }
+        Schema::Seq { max_len: None, .. } => return Err(Error::Unbounded("a sequence".into())),
+        Schema::Str { max_len: None } => return Err(Error::Unbounded("a string".into())),
+        // Only fields can declare a maximum length; see `field_type`.
+        Schema::Seq { .. } => {
+            return Err(Error::Unsupported("a sequence inside another type".into()));
+        }
+        Schema::Str { .. } => {
+            return Err(Error::Unsupported("a string inside another type".into()));
+        }
+        Schema::FixedStr { len } => format!("::hubpack::FixedStr<{len}>"),
+        Schema::Struct { name, .. } | Schema::Enum { name, .. } => ident(name, "type")?,
+    })
+}
+
+/// Rust keywords, which can only be used as names in their raw form, `r#x`.
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do",
+    "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe",
+    "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// Checks that a name from a schema can be used in Rust, at least in its raw
+/// form.
+fn is_ident(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+        && name != "_"
+        && !["self", "Self", "super", "crate"].contains(&name)
+}
+
+/// Returns a name from a schema as a Rust identifier, using `r#` for
+/// keywords. `what` says what kind of name it is, for errors.
+fn ident(name: &str, what: &str) -> Result<String> {
+    if !is_ident(name) {
+        return Err(Error::Unsupported(format!("the {what} name {name:?}")));
+    }
+    Ok(if KEYWORDS.contains(&name) { format!("r#{name}") } else { name.to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{frames, Frame};
+    use hubpack::{HubpackSchema, SerializedSize};
+
+    /// The output of `module(&[Frame::SCHEMA])`, checked in so that it gets
+    /// compiled along with the tests.
+    mod generated {
+        include!("../testdata/frame.rs");
+    }
+
+    #[test]
+    fn frame_types() {
+        let code = module(&[Frame::SCHEMA]).unwrap();
+        assert!(code.starts_with("// Generated by hubpack-codegen. Do not edit.\n"));
+        for expected in [
+            "pub enum Body {\n    Ping,\n    Reading(char, f32),\n    Text {\n        \
+             #[serde(with = \"::hubpack::MaxLen::<8>\")]\n        \
+             text: ::heapless::String<8>,\n        long: i64,\n    },\n}\n",
+            "pub struct Frame {\n    pub id: u16,\n    pub delta: i32,\n    pub flags: [bool; 2],\n    \
+             pub name: ::hubpack::FixedStr<6>,\n    pub tail: Option<(u8, i8)>,\n    \
+             #[serde(with = \"::hubpack::MaxLen::<3>\")]\n    \
+             pub samples: ::heapless::Vec<i16, 3>,\n    pub body: Body,\n}\n",
+        ] {
+            assert!(code.contains(&format!("{DERIVES}\n{expected}")), "missing:\n{expected}\nin:\n{code}");
+        }
+        // Types come after the types they use.
+        assert!(code.find("pub enum Body").unwrap() < code.find("pub struct Frame").unwrap());
+        assert_eq!(code, include_str!("../testdata/frame.rs"), "testdata/frame.rs is out of date");
+    }
+
+    #[test]
+    fn generated_frame_round_trip() {
+        assert_eq!(generated::Frame::SCHEMA, Frame::SCHEMA);
+        assert_eq!(generated::Frame::MAX_SIZE, Frame::MAX_SIZE);
+        for frame in frames() {
+            let mut buf = [0; Frame::MAX_SIZE];
+            let n = hubpack::serialize(&mut buf, &frame).unwrap();
+            let (decoded, rest) = hubpack::deserialize::<generated::Frame>(&buf[..n]).unwrap();
+            assert!(rest.is_empty());
+            let mut again = [0; Frame::MAX_SIZE];
+            let m = hubpack::serialize(&mut again, &decoded).unwrap();
+            assert_eq!(&again[..m], &buf[..n]);
+        }
+    }
+
+    #[test]
+    fn schema_file_types() {
+        let code = from_schema_file(r#"
+            [types.Empty]
+            [types.Pair]
+            tuple = ["(usize,)", "Vec<Option<u8>, 2>"]
+            [types.Tagged]
+            variants = [{ name = "Of", fields = [{ name = "type", type = "()" }] }]
+        "#).unwrap();
+        assert_eq!(code, format!("\
+// Generated by hubpack-codegen. Do not edit.
+
+{DERIVES}
+pub struct Empty;
+
+{DERIVES}
+pub struct Pair(pub (u64,), #[serde(with = \"::hubpack::MaxLen::<2>\")] pub ::heapless::Vec<Option<u8>, 2>);
+
+{DERIVES}
+pub enum Tagged {{
+    Of {{
+        r#type: (),
+    }},
+}}
+"));
+    }
+
+    #[test]
+    fn rust_errors() {
+        let file = |text: &str| from_schema_file(text).unwrap_err();
+        assert_eq!(
+            file("[types.A]\nfields = [{ name = \"b\", type = \"Vec<u8>\" }]"),
+            Error::Unbounded("a sequence".into()),
+        );
+        assert_eq!(
+            file("[types.A]\ntuple = [\"Option<String<2>>\"]"),
+            Error::Unsupported("a string inside another type".into()),
+        );
+        assert!(matches!(
+            file("[types.A]\nvariants = [{ name = \"B\", index = 1 }]"),
+            Error::Unsupported(_),
+        ));
+        assert!(matches!(
+            file("[types.A]\nfields = [{ name = \"not ok\", type = \"u8\" }]"),
+            Error::Unsupported(_),
+        ));
+        assert!(matches!(file("[types.A]\ntuple = [\"B\"]"), Error::Invalid(_)));
+    }
+}
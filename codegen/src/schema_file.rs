@@ -0,0 +1,523 @@
+//! Loading schemas from TOML files.
+//!
+//! A schema file defines named types, whose fields are written as Rust-like
+//! type expressions:
+//!
+//! ```toml
+//! root = "Message"
+//!
+//! [types.Message]
+//! fields = [
+//!     { name = "id", type = "u16" },
+//!     { name = "body", type = "Body" },
+//!     { name = "tags", type = "Vec<u8>", max_len = 4 },
+//! ]
+//!
+//! [types.Body]
+//! variants = [
+//!     { name = "Ping" },
+//!     { name = "Reading", fields = [{ name = "value", type = "Option<i32>" }] },
+//!     { name = "Raw", tuple = ["[u8; 4]"] },
+//! ]
+//! ```
+//!
+//! A type with `variants` is an enum; anything else is a struct, with either
+//! named `fields`, unnamed `tuple` fields, or neither. Variants are numbered in
+//! order, unless they give an explicit `index`.
+//!
+//! Type expressions can be:
+//!
+//! - primitives: `()`, `bool`, `u8` through `u128`, `usize`, `i8` through
+//!   `i128`, `isize`, `f32`, `f64`, and `char`
+//! - `Option<T>`
+//! - arrays, `[T; N]`, and tuples, `(A, B, ...)`
+//! - length-prefixed sequences, `Vec<T>` or `[T]`, optionally with a maximum
+//!   length, `Vec<T, N>`
+//! - length-prefixed strings, `String` or `str`, optionally with a maximum
+//!   length, `String<N>`
+//! - fixed-size strings, `FixedStr<N>`
+//! - the name of a type defined in the file
+//!
+//! Sequences and strings can only be encoded and decoded with a maximum
+//! length, like fields declared with `hubpack::MaxLen`, given either in the
+//! type or as a field's `max_len`.
+//!
+//! This is the format read by the `hubpack` command-line tool, and by
+//! [`rust::from_schema_file`](crate::rust::from_schema_file).
+
+use std::collections::{BTreeMap, HashMap};
+
+use hubpack::schema::{Field, Fields, Schema, Variant};
+use serde::Deserialize;
+
+use crate::{Error, Result};
+
+/// Returns early with an [`Error::Invalid`] built like `format!`.
+macro_rules! bail {
+    ($($arg:tt)*) => {
+        return Err(Error::Invalid(format!($($arg)*)))
+    };
+}
+
+/// Adds where an [`Error::Invalid`] happened to its description.
+fn context<T>(result: Result<T>, place: impl FnOnce() -> String) -> Result<T> {
+    result.map_err(|e| match e {
+        Error::Invalid(msg) => Error::Invalid(format!("{}: {msg}", place())),
+        e => e,
+    })
+}
+
+/// The contents of a schema file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileDef {
+    root: Option<String>,
+    #[serde(default)]
+    types: BTreeMap<String, TypeDef>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TypeDef {
+    fields: Option<Vec<FieldDef>>,
+    tuple: Option<Vec<String>>,
+    variants: Option<Vec<VariantDef>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FieldDef {
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+    max_len: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VariantDef {
+    name: String,
+    index: Option<u8>,
+    fields: Option<Vec<FieldDef>>,
+    tuple: Option<Vec<String>>,
+}
+
+/// A loaded schema file.
+///
+/// `Schema` only holds `'static` references, so everything resolved from a
+/// file is leaked. That's fine for tools and build scripts, which load a file
+/// or two and exit.
+pub struct SchemaFile {
+    root: Option<String>,
+    defs: BTreeMap<String, TypeDef>,
+    resolved: HashMap<String, Schema>,
+}
+
+impl SchemaFile {
+    /// Parses a schema file. Types are checked as they're resolved.
+    pub fn parse(text: &str) -> Result<Self> {
+        let file: FileDef = toml::from_str(text)
+            .map_err(|e| Error::Invalid(e.to_string()))?;
+        Ok(Self { root: file.root, defs: file.types, resolved: HashMap::new() })
+    }
+
+    /// Resolves a type expression, which may refer to types defined in the
+    /// file. With no expression, resolves the file's `root` type.
+    pub fn resolve(&mut self, expr: Option<&str>) -> Result<Schema> {
+        let expr = match (expr, &self.root) {
+            (Some(e), _) => e.to_string(),
+            (None, Some(root)) => root.clone(),
+            (None, None) => bail!("schema file has no root type"),
+        };
+        let ty = parse_type(&expr)?;
+        self.resolve_expr(&ty, &mut Vec::new())
+    }
+
+    /// Returns the name of the file's `root` type, if it has one.
+    pub fn root(&self) -> Option<&str> {
+        self.root.as_deref()
+    }
+
+    /// Resolves every type defined in the file, in order of name.
+    pub fn resolve_all(&mut self) -> Result<Vec<Schema>> {
+        let names: Vec<String> = self.defs.keys().cloned().collect();
+        names.iter().map(|name| self.resolve_named(name, &mut Vec::new())).collect()
+    }
+
+    fn resolve_expr(&mut self, ty: &TypeExpr, stack: &mut Vec<String>) -> Result<Schema> {
+        Ok(match ty {
+            TypeExpr::Prim(s) => *s,
+            TypeExpr::Option(inner) => Schema::Option(leak(self.resolve_expr(inner, stack)?)),
+            TypeExpr::Array(inner, len) => Schema::Array {
+                len: *len,
+                element: leak(self.resolve_expr(inner, stack)?),
+            },
+            TypeExpr::Tuple(items) => Schema::Tuple(self.resolve_list(items, stack)?),
+            TypeExpr::Seq(inner, max_len) => Schema::Seq {
+                max_len: *max_len,
+                element: leak(self.resolve_expr(inner, stack)?),
+            },
+            TypeExpr::Str(max_len) => Schema::Str { max_len: *max_len },
+            TypeExpr::FixedStr(len) => Schema::FixedStr { len: *len },
+            TypeExpr::Named(name) => self.resolve_named(name, stack)?,
+        })
+    }
+
+    fn resolve_list(
+        &mut self,
+        items: &[TypeExpr],
+        stack: &mut Vec<String>,
+    ) -> Result<&'static [Schema]> {
+        let list = items.iter()
+            .map(|t| self.resolve_expr(t, stack))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(list.leak())
+    }
+
+    fn resolve_named(&mut self, name: &str, stack: &mut Vec<String>) -> Result<Schema> {
+        if let Some(s) = self.resolved.get(name) {
+            return Ok(*s);
+        }
+        if stack.iter().any(|n| n == name) {
+            bail!("type {name} contains itself, which hubpack can't encode");
+        }
+        // Take the definition out while we work on it, so that we can borrow
+        // `self` mutably to resolve the types it uses.
+        let Some(def) = self.defs.remove(name) else {
+            bail!("unknown type {name}");
+        };
+        stack.push(name.to_string());
+        let result = self.resolve_def(name, &def, stack);
+        stack.pop();
+        self.defs.insert(name.to_string(), def);
+
+        let schema = context(result, || format!("in type {name}"))?;
+        self.resolved.insert(name.to_string(), schema);
+        Ok(schema)
+    }
+
+    fn resolve_def(
+        &mut self,
+        name: &str,
+        def: &TypeDef,
+        stack: &mut Vec<String>,
+    ) -> Result<Schema> {
+        let name = leak_str(name);
+        match &def.variants {
+            None => Ok(Schema::Struct {
+                name,
+                fields: self.resolve_fields(&def.fields, &def.tuple, stack)?,
+            }),
+            Some(_) if def.fields.is_some() || def.tuple.is_some() => {
+                bail!("a type can't have both fields and variants")
+            }
+            Some(variants) => {
+                let mut list = Vec::with_capacity(variants.len());
+                let mut next_index = 0usize;
+                for v in variants {
+                    let index = match v.index.or(u8::try_from(next_index).ok()) {
+                        Some(i) => i,
+                        None => bail!("too many variants"),
+                    };
+                    if list.iter().any(|other: &Variant| other.index == index) {
+                        bail!("variant index {index} is used twice");
+                    }
+                    next_index = usize::from(index) + 1;
+                    let fields = context(
+                        self.resolve_fields(&v.fields, &v.tuple, stack),
+                        || format!("in variant {}", v.name),
+                    )?;
+                    list.push(Variant { name: leak_str(&v.name), index, fields });
+                }
+                Ok(Schema::Enum { name, variants: list.leak() })
+            }
+        }
+    }
+
+    fn resolve_fields(
+        &mut self,
+        named: &Option<Vec<FieldDef>>,
+        tuple: &Option<Vec<String>>,
+        stack: &mut Vec<String>,
+    ) -> Result<Fields> {
+        match (named, tuple) {
+            (None, None) => Ok(Fields::Unit),
+            (Some(_), Some(_)) => bail!("can't have both named and tuple fields"),
+            (Some(named), None) => {
+                let mut list = Vec::with_capacity(named.len());
+                for f in named {
+                    let place = || format!("in field {}", f.name);
+                    let ty = context(parse_type(&f.ty), place)?;
+                    let mut schema = context(self.resolve_expr(&ty, stack), place)?;
+                    if let Some(max_len) = f.max_len {
+                        schema = schema.with_max_len(max_len);
+                    }
+                    list.push(Field { name: leak_str(&f.name), schema });
+                }
+                Ok(Fields::Named(list.leak()))
+            }
+            (None, Some(tuple)) => {
+                let types = tuple.iter()
+                    .map(|t| parse_type(t))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(Fields::Tuple(self.resolve_list(&types, stack)?))
+            }
+        }
+    }
+}
+
+fn leak(s: Schema) -> &'static Schema {
+    Box::leak(Box::new(s))
+}
+
+fn leak_str(s: &str) -> &'static str {
+    s.to_string().leak()
+}
+
+/// A parsed type expression, before names are resolved.
+#[derive(Debug, PartialEq)]
+enum TypeExpr {
+    Prim(Schema),
+    Option(Box<TypeExpr>),
+    Array(Box<TypeExpr>, usize),
+    Tuple(Vec<TypeExpr>),
+    Seq(Box<TypeExpr>, Option<usize>),
+    Str(Option<usize>),
+    FixedStr(usize),
+    Named(String),
+}
+
+fn parse_type(text: &str) -> Result<TypeExpr> {
+    let mut p = Parser { text, pos: 0 };
+    let ty = p.ty()?;
+    p.skip_ws();
+    if p.pos != text.len() {
+        bail!("unexpected {:?} in type {text:?}", &text[p.pos..]);
+    }
+    Ok(ty)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_ws(&mut self) {
+        let rest = &self.text[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_ws();
+        if self.text[self.pos..].starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            bail!("expected {token:?} at {:?} in type {:?}", &self.text[self.pos..], self.text)
+        }
+    }
+
+    fn ident(&mut self) -> Result<&'a str> {
+        self.skip_ws();
+        let text = self.text;
+        let rest = &text[self.pos..];
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            bail!("expected a type at {rest:?} in type {:?}", self.text);
+        }
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    fn number(&mut self) -> Result<usize> {
+        let n = self.ident()?;
+        match n.replace('_', "").parse() {
+            Ok(n) => Ok(n),
+            Err(_) => bail!("expected a number, not {n:?}, in type {:?}", self.text),
+        }
+    }
+
+    /// Parses an optional `<N>` suffix.
+    fn len_param(&mut self) -> Result<Option<usize>> {
+        if self.eat("<") {
+            let n = self.number()?;
+            self.expect(">")?;
+            Ok(Some(n))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn ty(&mut self) -> Result<TypeExpr> {
+        if self.eat("(") {
+            let mut items = vec![];
+            while !self.eat(")") {
+                items.push(self.ty()?);
+                if !self.eat(",") {
+                    self.expect(")")?;
+                    break;
+                }
+            }
+            return Ok(if items.is_empty() {
+                TypeExpr::Prim(Schema::Unit)
+            } else {
+                TypeExpr::Tuple(items)
+            });
+        }
+        if self.eat("[") {
+            let inner = Box::new(self.ty()?);
+            if self.eat("]") {
+                return Ok(TypeExpr::Seq(inner, None));
+            }
+            self.expect(";")?;
+            let len = self.number()?;
+            self.expect("]")?;
+            return Ok(TypeExpr::Array(inner, len));
+        }
+
+        let name = self.ident()?.to_string();
+        Ok(match name.as_str() {
+            "bool" => TypeExpr::Prim(Schema::Bool),
+            "u8" => TypeExpr::Prim(Schema::U8),
+            "u16" => TypeExpr::Prim(Schema::U16),
+            "u32" => TypeExpr::Prim(Schema::U32),
+            "u64" | "usize" => TypeExpr::Prim(Schema::U64),
+            "u128" => TypeExpr::Prim(Schema::U128),
+            "i8" => TypeExpr::Prim(Schema::I8),
+            "i16" => TypeExpr::Prim(Schema::I16),
+            "i32" => TypeExpr::Prim(Schema::I32),
+            "i64" | "isize" => TypeExpr::Prim(Schema::I64),
+            "i128" => TypeExpr::Prim(Schema::I128),
+            "f32" => TypeExpr::Prim(Schema::F32),
+            "f64" => TypeExpr::Prim(Schema::F64),
+            "char" => TypeExpr::Prim(Schema::Char),
+            "str" => TypeExpr::Str(None),
+            "String" => TypeExpr::Str(self.len_param()?),
+            "FixedStr" => {
+                self.expect("<")?;
+                let n = self.number()?;
+                self.expect(">")?;
+                TypeExpr::FixedStr(n)
+            }
+            "Option" => {
+                self.expect("<")?;
+                let inner = self.ty()?;
+                self.expect(">")?;
+                TypeExpr::Option(Box::new(inner))
+            }
+            "Vec" => {
+                self.expect("<")?;
+                let inner = self.ty()?;
+                let max_len = if self.eat(",") { Some(self.number()?) } else { None };
+                self.expect(">")?;
+                TypeExpr::Seq(Box::new(inner), max_len)
+            }
+            _ => TypeExpr::Named(name),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = r#"
+        root = "Message"
+
+        [types.Message]
+        fields = [
+            { name = "id", type = "u16" },
+            { name = "body", type = "Body" },
+            { name = "tags", type = "Vec<u8>", max_len = 4 },
+        ]
+
+        [types.Body]
+        variants = [
+            { name = "Ping" },
+            { name = "Reading", fields = [{ name = "value", type = "Option<i32>" }] },
+            { name = "Raw", index = 7, tuple = ["[u8; 2]", "(char, String<8>, FixedStr<3>)"] },
+        ]
+    "#;
+
+    #[test]
+    fn parses_types() {
+        assert_eq!(parse_type("u8").unwrap(), TypeExpr::Prim(Schema::U8));
+        assert_eq!(parse_type(" ( ) ").unwrap(), TypeExpr::Prim(Schema::Unit));
+        assert_eq!(
+            parse_type("Vec<[Option<Foo>; 1_000], 3>").unwrap(),
+            TypeExpr::Seq(
+                Box::new(TypeExpr::Array(
+                    Box::new(TypeExpr::Option(Box::new(TypeExpr::Named("Foo".into())))),
+                    1000,
+                )),
+                Some(3),
+            ),
+        );
+        assert_eq!(
+            parse_type("(u8,)").unwrap(),
+            TypeExpr::Tuple(vec![TypeExpr::Prim(Schema::U8)]),
+        );
+        assert!(parse_type("Option<u8").is_err());
+        assert!(parse_type("u8 u8").is_err());
+        assert!(parse_type("[u8; x]").is_err());
+    }
+
+    #[test]
+    fn resolves_files() {
+        let mut file = SchemaFile::parse(FILE).unwrap();
+        let Schema::Struct { name: "Message", fields: Fields::Named(fields) } =
+            file.resolve(None).unwrap()
+        else {
+            panic!("wrong root");
+        };
+        assert_eq!(fields[0].schema, Schema::U16);
+        assert_eq!(fields[2].schema, Schema::Seq { max_len: Some(4), element: &Schema::U8 });
+
+        let Schema::Enum { variants, .. } = fields[1].schema else {
+            panic!("wrong body");
+        };
+        assert_eq!(variants.iter().map(|v| v.index).collect::<Vec<_>>(), [0, 1, 7]);
+        assert_eq!(
+            variants[2].fields,
+            Fields::Tuple(&[
+                Schema::Array { len: 2, element: &Schema::U8 },
+                Schema::Tuple(&[
+                    Schema::Char,
+                    Schema::Str { max_len: Some(8) },
+                    Schema::FixedStr { len: 3 },
+                ]),
+            ]),
+        );
+
+        assert_eq!(file.resolve(Some("Option<Body>")).unwrap(), Schema::Option(leak(fields[1].schema)));
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        let mut file = SchemaFile::parse(r#"
+            [types.A]
+            fields = [{ name = "b", type = "B" }]
+            [types.B]
+            tuple = ["Option<A>"]
+        "#).unwrap();
+        assert_eq!(
+            file.resolve(Some("A")).unwrap_err().to_string(),
+            "in type A: in field b: in type B: type A contains itself, which hubpack can't encode",
+        );
+        assert!(file.resolve(Some("C")).is_err());
+        assert!(file.resolve(None).is_err());
+
+        assert!(SchemaFile::parse("[types.A]\nfeilds = []").is_err());
+    }
+}
@@ -0,0 +1,630 @@
+//! Wireshark dissectors.
+//!
+//! [`dissector`] produces a Lua plugin that makes Wireshark decode a message
+//! type sent over UDP, showing each field in the packet details and setting
+//! the Info column to the variant name of an enum message. Copying it into
+//! Wireshark's plugin directory is enough to load it.
+//!
+//! The protocol is named after the message type in `lower_snake_case`, and is
+//! registered on a UDP port given when generating the plugin, which can be
+//! changed in Wireshark's protocol preferences.
+//!
+//! Every value gets a field that can be used in display filters, named after
+//! its path, like `request.read.addr` for the `addr` field of a `Read`
+//! variant of the `Request` message. Fields of other types are named after the
+//! type, like `request.header.seq` for the `seq` field of a `Header` struct,
+//! wherever it's used. In addition:
+//!
+//! - Enum tags are fields named `tag`, showing the variant name.
+//! - An `Option` field `x` has a `x.present` field for its tag.
+//! - Sequences and strings, which must have a maximum length, have a `x.len`
+//!   field for their length, checked against the maximum.
+//! - The elements of arrays and sequences are fields named `x.item`, and
+//!   elements of tuples are named `x.f0`, `x.f1`, and so on.
+//! - Bytes after the message are shown as `trailing`.
+//!
+//! Messages are checked like `hubpack::deserialize` does, and problems, such
+//! as an unknown variant or a truncated message, are reported as expert info
+//! on the bytes involved.
+
+use hubpack::schema::{Fields, Schema};
+
+use crate::{named_types, prefix_size, snake, Error, Result};
+
+/// Generates a Wireshark Lua dissector for `root`, registered on UDP port
+/// `port` by default.
+///
+/// The root must be a struct or enum.
+pub fn dissector(root: &Schema, port: u16) -> Result<String> {
+    let types = named_types(core::slice::from_ref(root))?;
+    let (Schema::Struct { name, .. } | Schema::Enum { name, .. }) = root else {
+        unreachable!("checked by named_types")
+    };
+    let mut g = Gen { proto: snake(name), fields: vec![] };
+
+    let mut functions = String::new();
+    for schema in &types {
+        functions.push('\n');
+        functions.push_str(&lines(&g.function(schema, schema == root)?));
+    }
+    let trailing = g.field(&format!("{}.trailing", g.proto), "trailing", "bytes", "")?;
+
+    let proto = &g.proto;
+    let mut out = String::new();
+    out.push_str("-- Generated by hubpack-codegen. Do not edit.\n");
+    out.push_str(&format!("local proto = Proto(\"{proto}\", \"{name} (hubpack)\")\n\n"));
+    out.push_str("local F = {\n");
+    for (abbr, decl) in &g.fields {
+        out.push_str(&format!("    [\"{abbr}\"] = {decl},\n"));
+    }
+    out.push_str("}\n");
+    out.push_str("proto.fields = F\n\n");
+    out.push_str(&format!(
+        "local malformed = ProtoExpert.new(\"{proto}.malformed\", \"Malformed {name}\",\n",
+    ));
+    out.push_str("    expert.group.MALFORMED, expert.severity.ERROR)\n");
+    out.push_str("proto.experts = { malformed }\n");
+    out.push_str(RUNTIME);
+    out.push_str(&functions);
+    out.push_str(&format!(
+        r#"
+function proto.dissector(buf, pinfo, tree)
+    pinfo.cols.protocol = "{name}"
+    local item = tree:add(proto, buf())
+    local ok, off, variant = pcall(dissect_{proto}, buf, item, 0)
+    if not ok then
+        local err = off
+        if type(err) ~= "table" then
+            error(err, 0)
+        end
+        if err.range then
+            item:add_tvb_expert_info(malformed, err.range, err.msg)
+        else
+            item:add_proto_expert_info(malformed, err.msg)
+        end
+        pinfo.cols.info = "Malformed: " .. err.msg
+        return buf:len()
+    end
+    if variant then
+        pinfo.cols.info = variant
+    end
+    if off < buf:len() then
+        item:add({trailing}, buf(off))
+    end
+    return buf:len()
+end
+
+proto.prefs.port = Pref.uint("UDP port", {port}, "UDP port to decode as {name}")
+
+local registered_port = {port}
+DissectorTable.get("udp.port"):add(registered_port, proto)
+
+function proto.prefs_changed()
+    if proto.prefs.port ~= registered_port then
+        local udp = DissectorTable.get("udp.port")
+        if registered_port ~= 0 then
+            udp:remove(registered_port, proto)
+        end
+        registered_port = proto.prefs.port
+        if registered_port ~= 0 then
+            udp:add(registered_port, proto)
+        end
+    end
+end
+"#
+    ));
+    Ok(out)
+}
+
+/// Helpers used by the generated code. Each `add_*` function adds a value at
+/// `off` to `tree`, and returns the offset after it. Problems are raised as
+/// tables, which the dissector turns into expert info.
+const RUNTIME: &str = r#"
+local function fail(range, msg)
+    error({ range = range, msg = msg }, 0)
+end
+
+local function take(buf, off, n)
+    if off + n > buf:len() then
+        fail(nil, "truncated")
+    end
+    return buf(off, n)
+end
+
+local function add(buf, tree, off, field, n)
+    tree:add_le(field, take(buf, off, n))
+    return off + n
+end
+
+local function add_bool(buf, tree, off, field)
+    local r = take(buf, off, 1)
+    if r:uint() > 1 then
+        fail(r, "invalid bool")
+    end
+    tree:add(field, r)
+    return off + 1
+end
+
+local function add_option(buf, tree, off, field)
+    local r = take(buf, off, 1)
+    if r:uint() > 1 then
+        fail(r, "invalid option tag")
+    end
+    tree:add(field, r)
+    return off + 1, r:uint() == 1
+end
+
+local function add_len(buf, tree, off, field, size, max)
+    local r = take(buf, off, size)
+    local n = r:le_uint()
+    tree:add_le(field, r)
+    if n > max then
+        fail(r, "length over the maximum of " .. max)
+    end
+    return off + size, n
+end
+
+local function utf8_char(c)
+    if c < 0x80 then
+        return string.char(c)
+    elseif c < 0x800 then
+        return string.char(0xC0 + math.floor(c / 0x40), 0x80 + c % 0x40)
+    elseif c < 0x10000 then
+        return string.char(0xE0 + math.floor(c / 0x1000),
+            0x80 + math.floor(c / 0x40) % 0x40, 0x80 + c % 0x40)
+    end
+    return string.char(0xF0 + math.floor(c / 0x40000), 0x80 + math.floor(c / 0x1000) % 0x40,
+        0x80 + math.floor(c / 0x40) % 0x40, 0x80 + c % 0x40)
+end
+
+local function add_char(buf, tree, off, field)
+    local r = take(buf, off, 4)
+    local c = r:le_uint()
+    if c > 0x10FFFF or (c >= 0xD800 and c <= 0xDFFF) then
+        fail(r, "invalid char")
+    end
+    tree:add_le(field, r):append_text(" (" .. utf8_char(c) .. ")")
+    return off + 4
+end
+
+-- Checks that the string `s`, from the range `r`, is UTF-8.
+local function check_utf8(r, s)
+    local lead = { 0, 0xC0, 0xE0, 0xF0 }
+    local min = { 0, 0x80, 0x800, 0x10000 }
+    local i = 1
+    while i <= #s do
+        local b = s:byte(i)
+        local n
+        if b < 0x80 then
+            n = 1
+        elseif b >= 0xC2 and b < 0xE0 then
+            n = 2
+        elseif b >= 0xE0 and b < 0xF0 then
+            n = 3
+        elseif b >= 0xF0 and b < 0xF5 then
+            n = 4
+        else
+            fail(r, "invalid UTF-8")
+        end
+        if i + n - 1 > #s then
+            fail(r, "invalid UTF-8")
+        end
+        local c = b - lead[n]
+        for j = i + 1, i + n - 1 do
+            local cb = s:byte(j)
+            if cb < 0x80 or cb > 0xBF then
+                fail(r, "invalid UTF-8")
+            end
+            c = c * 0x40 + (cb - 0x80)
+        end
+        if c < min[n] or c > 0x10FFFF or (c >= 0xD800 and c <= 0xDFFF) then
+            fail(r, "invalid UTF-8")
+        end
+        i = i + n
+    end
+end
+
+local function add_str(buf, tree, off, len_field, field, size, max)
+    local n
+    off, n = add_len(buf, tree, off, len_field, size, max)
+    local r = take(buf, off, n)
+    local s = r:raw()
+    check_utf8(r, s)
+    tree:add(field, r, s)
+    return off + n
+end
+
+local function add_fixed_str(buf, tree, off, field, n)
+    local r = take(buf, off, n)
+    local s = r:raw()
+    local nul = s:find("\0", 1, true)
+    if nul then
+        s = s:sub(1, nul - 1)
+    end
+    check_utf8(r, s)
+    tree:add(field, r, s)
+    return off + n
+end
+"#;
+
+struct Gen {
+    /// The protocol name, which starts every field name.
+    proto: String,
+    /// Every field, with its declaration.
+    fields: Vec<(String, String)>,
+}
+
+impl Gen {
+    /// Declares a field with a `ProtoField` constructor and extra arguments,
+    /// and returns the Lua expression for it. Declaring the same field again
+    /// is fine, but declaring a different one with the same name isn't.
+    fn field(&mut self, abbr: &str, label: &str, kind: &str, args: &str) -> Result<String> {
+        let decl = format!("ProtoField.{kind}(\"{abbr}\", \"{label}\"{args})");
+        match self.fields.iter().find(|(a, _)| a == abbr) {
+            Some((_, prev)) if *prev != decl => return Err(Error::NameClash(abbr.to_string())),
+            Some(_) => (),
+            None => self.fields.push((abbr.to_string(), decl)),
+        }
+        Ok(format!("F[\"{abbr}\"]"))
+    }
+
+    /// Returns the function dissecting a struct or enum, which adds its
+    /// fields to `tree` and returns the offset after it, and for enums the
+    /// variant name.
+    fn function(&mut self, schema: &Schema, is_root: bool) -> Result<Vec<String>> {
+        let (Schema::Struct { name, .. } | Schema::Enum { name, .. }) = schema else {
+            unreachable!("only structs and enums are named")
+        };
+        let prefix = if is_root {
+            self.proto.clone()
+        } else {
+            format!("{}.{}", self.proto, snake(name))
+        };
+        let mut code = vec![format!("local function dissect_{}(buf, tree, off)", type_id(schema))];
+        match schema {
+            Schema::Struct { fields, .. } => {
+                code.extend(indent(self.fields_code(fields, &prefix, "tree", 1)?));
+                code.push("    return off".into());
+            }
+            Schema::Enum { variants, .. } => {
+                let mut names = vec![];
+                for v in variants.iter() {
+                    names.push(format!("[{}] = \"{}\"", v.index, v.name));
+                }
+                let args = format!(", base.DEC, {{ {} }}", names.join(", "));
+                let tag = self.field(&format!("{prefix}.tag"), "tag", "uint8", &args)?;
+                code.extend([
+                    "    local r = take(buf, off, 1)".into(),
+                    "    local tag = r:uint()".into(),
+                    format!("    tree:add({tag}, r)"),
+                    "    off = off + 1".into(),
+                ]);
+                for (i, v) in variants.iter().enumerate() {
+                    let keyword = if i == 0 { "if" } else { "elseif" };
+                    code.push(format!("    {keyword} tag == {} then", v.index));
+                    code.push(format!("        tree:append_text(\": {}\")", v.name));
+                    let variant_prefix = format!("{prefix}.{}", snake(v.name));
+                    let fields = self.fields_code(&v.fields, &variant_prefix, "tree", 1)?;
+                    code.extend(indent(indent(fields)));
+                    code.push(format!("        return off, \"{}\"", v.name));
+                }
+                if !variants.is_empty() {
+                    code.push("    end".into());
+                }
+                code.push(format!("    fail(r, \"invalid {name} variant \" .. tag)"));
+            }
+            _ => unreachable!(),
+        }
+        code.push("end".into());
+        Ok(code)
+    }
+
+    fn fields_code(
+        &mut self,
+        fields: &Fields,
+        prefix: &str,
+        tree: &str,
+        depth: usize,
+    ) -> Result<Vec<String>> {
+        let mut code = vec![];
+        match fields {
+            Fields::Unit => (),
+            Fields::Tuple(list) => {
+                for (i, s) in list.iter().enumerate() {
+                    let name = format!("f{i}");
+                    code.extend(self.value(s, &format!("{prefix}.{name}"), &name, tree, depth)?);
+                }
+            }
+            Fields::Named(list) => {
+                for f in list.iter() {
+                    let abbr = format!("{prefix}.{}", snake(f.name));
+                    code.extend(self.value(&f.schema, &abbr, f.name, tree, depth)?);
+                }
+            }
+        }
+        Ok(code)
+    }
+
+    /// Returns code adding a value described by `schema`, as the field
+    /// `abbr`, to the tree in the variable `tree`. `depth` keeps the names
+    /// of variables used by nested values apart.
+    fn value(
+        &mut self,
+        schema: &Schema,
+        abbr: &str,
+        label: &str,
+        tree: &str,
+        depth: usize,
+    ) -> Result<Vec<String>> {
+        let simple = |g: &mut Self, kind: &str, args: &str, n: usize| -> Result<Vec<String>> {
+            let field = g.field(abbr, label, kind, args)?;
+            Ok(vec![format!("off = add(buf, {tree}, off, {field}, {n})")])
+        };
+        let dec = ", base.DEC";
+        Ok(match schema {
+            Schema::Unit | Schema::Tuple([]) => vec![],
+            Schema::U8 => simple(self, "uint8", dec, 1)?,
+            Schema::U16 => simple(self, "uint16", dec, 2)?,
+            Schema::U32 => simple(self, "uint32", dec, 4)?,
+            Schema::U64 => simple(self, "uint64", dec, 8)?,
+            Schema::I8 => simple(self, "int8", dec, 1)?,
+            Schema::I16 => simple(self, "int16", dec, 2)?,
+            Schema::I32 => simple(self, "int32", dec, 4)?,
+            Schema::I64 => simple(self, "int64", dec, 8)?,
+            // Wireshark has no 128-bit integers.
+            Schema::U128 | Schema::I128 => simple(self, "bytes", "", 16)?,
+            Schema::F32 => simple(self, "float", "", 4)?,
+            Schema::F64 => simple(self, "double", "", 8)?,
+            Schema::Bool => {
+                let field = self.field(abbr, label, "bool", "")?;
+                vec![format!("off = add_bool(buf, {tree}, off, {field})")]
+            }
+            Schema::Char => {
+                let field = self.field(abbr, label, "uint32", ", base.HEX")?;
+                vec![format!("off = add_char(buf, {tree}, off, {field})")]
+            }
+            Schema::Str { max_len } => {
+                let (len, args) = self.length(abbr, label, *max_len, "a string")?;
+                let field = self.field(abbr, label, "string", "")?;
+                vec![format!("off = add_str(buf, {tree}, off, {len}, {field}, {args})")]
+            }
+            Schema::FixedStr { len } => {
+                let field = self.field(abbr, label, "string", "")?;
+                vec![format!("off = add_fixed_str(buf, {tree}, off, {field}, {len})")]
+            }
+            Schema::Option(inner) => {
+                let present = self.field(
+                    &format!("{abbr}.present"),
+                    &format!("{label} present"),
+                    "bool",
+                    "",
+                )?;
+                let mut code = vec![
+                    "do".into(),
+                    "    local present".into(),
+                    format!("    off, present = add_option(buf, {tree}, off, {present})"),
+                    "    if present then".into(),
+                ];
+                code.extend(indent(indent(self.value(inner, abbr, label, tree, depth)?)));
+                code.extend(["    end".into(), "end".into()]);
+                code
+            }
+            Schema::Array { .. }
+            | Schema::Seq { .. }
+            | Schema::Tuple(_)
+            | Schema::Struct { .. }
+            | Schema::Enum { .. } => {
+                let item = format!("t{depth}");
+                let start = format!("s{depth}");
+                let field = self.field(abbr, label, "none", "")?;
+                let mut code = vec![
+                    "do".into(),
+                    format!("    local {start}, {item} = off, {tree}:add({field}, buf(off, 0))"),
+                ];
+                let mut body = vec![];
+                match schema {
+                    Schema::Array { len, element } => {
+                        let element_abbr = format!("{abbr}.item");
+                        body.push(format!("for _ = 1, {len} do"));
+                        let e = self.value(element, &element_abbr, "item", &item, depth + 1)?;
+                        body.extend(indent(e));
+                        body.push("end".into());
+                    }
+                    Schema::Seq { max_len, element } => {
+                        let (len, args) = self.length(abbr, label, *max_len, "a sequence")?;
+                        let count = format!("n{depth}");
+                        body.push(format!("local {count}"));
+                        body.push(format!("off, {count} = add_len(buf, {item}, off, {len}, {args})"));
+                        let element_abbr = format!("{abbr}.item");
+                        body.push(format!("for _ = 1, {count} do"));
+                        let e = self.value(element, &element_abbr, "item", &item, depth + 1)?;
+                        body.extend(indent(e));
+                        body.push("end".into());
+                    }
+                    Schema::Tuple(elements) => {
+                        let fields = Fields::Tuple(elements);
+                        body.extend(self.fields_code(&fields, abbr, &item, depth + 1)?);
+                    }
+                    _ => body.push(format!("off = dissect_{}(buf, {item}, off)", type_id(schema))),
+                }
+                code.extend(indent(body));
+                code.extend([format!("    {item}:set_len(off - {start})"), "end".into()]);
+                code
+            }
+        })
+    }
+
+    /// Declares the length field of a sequence or string, which must be
+    /// bounded, returning it along with the size and maximum arguments for
+    /// `add_len`.
+    fn length(
+        &mut self,
+        abbr: &str,
+        label: &str,
+        max_len: Option<usize>,
+        what: &str,
+    ) -> Result<(String, String)> {
+        let max = max_len.ok_or_else(|| Error::Unbounded(what.into()))?;
+        let size = prefix_size(max)?;
+        let kind = if size == 1 { "uint8" } else { "uint16" };
+        let field = self.field(&format!("{abbr}.len"), &format!("{label} length"), kind, ", base.DEC")?;
+        Ok((field, format!("{size}, {max}")))
+    }
+}
+
+/// Returns the name used for a struct or enum in Lua identifiers.
+fn type_id(schema: &Schema) -> String {
+    match schema {
+        Schema::Struct { name, .. } | Schema::Enum { name, .. } => snake(name),
+        _ => unreachable!("only structs and enums have functions"),
+    }
+}
+
+fn indent(code: Vec<String>) -> Vec<String> {
+    code.into_iter().map(|l| format!("    {l}")).collect()
+}
+
+fn lines(code: &[String]) -> String {
+    let mut out = String::new();
+    for l in code {
+        out.push_str(l);
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Frame;
+    use hubpack::HubpackSchema;
+
+    /// Checks that every block in generated Lua is closed, which is the
+    /// easiest mistake to make generating it.
+    fn check_blocks(lua: &str) {
+        let mut open = 0;
+        for line in lua.lines().map(str::trim) {
+            if line.starts_with("function ")
+                || line.starts_with("local function ")
+                || line == "do"
+                || line.ends_with(" do")
+                || (line.ends_with(" then") && !line.starts_with("elseif "))
+            {
+                open += 1;
+            } else if line == "end" {
+                assert!(open > 0, "unmatched end in:\n{lua}");
+                open -= 1;
+            }
+        }
+        assert_eq!(open, 0, "unclosed block in:\n{lua}");
+    }
+
+    #[test]
+    fn frame_dissector() {
+        let lua = dissector(&Frame::SCHEMA, 7777).unwrap();
+        check_blocks(&lua);
+        for expected in [
+            "local proto = Proto(\"frame\", \"Frame (hubpack)\")\n",
+            "    [\"frame.body.tag\"] = ProtoField.uint8(\"frame.body.tag\", \"tag\", base.DEC, \
+             { [0] = \"Ping\", [1] = \"Reading\", [2] = \"Text\" }),\n",
+            "    [\"frame.samples.len\"] = ProtoField.uint8(\"frame.samples.len\", \
+             \"samples length\", base.DEC),\n",
+            // Each kind of value.
+            "    off = add(buf, tree, off, F[\"frame.id\"], 2)\n",
+            "            off = add_bool(buf, t1, off, F[\"frame.flags.item\"])\n",
+            "    off = add_fixed_str(buf, tree, off, F[\"frame.name\"], 6)\n",
+            "        off, present = add_option(buf, tree, off, F[\"frame.tail.present\"])\n\
+             \x20       if present then\n\
+             \x20           do\n\
+             \x20               local s1, t1 = off, tree:add(F[\"frame.tail\"], buf(off, 0))\n\
+             \x20               off = add(buf, t1, off, F[\"frame.tail.f0\"], 1)\n",
+            "        off, n1 = add_len(buf, t1, off, F[\"frame.samples.len\"], 1, 3)\n\
+             \x20       for _ = 1, n1 do\n",
+            "        off = dissect_body(buf, t1, off)\n        t1:set_len(off - s1)\n",
+            // Enum variants.
+            "    elseif tag == 1 then\n\
+             \x20       tree:append_text(\": Reading\")\n\
+             \x20       off = add_char(buf, tree, off, F[\"frame.body.reading.f0\"])\n\
+             \x20       off = add(buf, tree, off, F[\"frame.body.reading.f1\"], 4)\n\
+             \x20       return off, \"Reading\"\n",
+            "        off = add_str(buf, tree, off, F[\"frame.body.text.text.len\"], \
+             F[\"frame.body.text.text\"], 1, 8)\n",
+            "    fail(r, \"invalid Body variant \" .. tag)\n",
+            // Registration.
+            "proto.prefs.port = Pref.uint(\"UDP port\", 7777, \"UDP port to decode as Frame\")\n",
+            "DissectorTable.get(\"udp.port\"):add(registered_port, proto)\n",
+        ] {
+            assert!(lua.contains(expected), "missing:\n{expected}\nin:\n{lua}");
+        }
+        // Functions are defined before they're used.
+        assert!(lua.find("function dissect_body").unwrap() < lua.find("dissect_body(buf").unwrap());
+    }
+
+    #[derive(HubpackSchema)]
+    #[allow(dead_code)]
+    enum Message {
+        Nested(Option<Option<u8>>, [Option<(bool, char)>; 2]),
+        Other { pos: Pos },
+    }
+
+    #[derive(HubpackSchema)]
+    #[allow(dead_code)]
+    struct Pos {
+        #[serde(with = "hubpack::MaxLen::<4>")]
+        points: &'static [(u128, f64)],
+    }
+
+    #[test]
+    fn nested_values() {
+        let lua = dissector(&Message::SCHEMA, 1).unwrap();
+        check_blocks(&lua);
+        for expected in [
+            // The root enum's fields are named after the protocol, and
+            // others after their type.
+            "    [\"message.tag\"] = ",
+            "    [\"message.other.pos\"] = ProtoField.none(",
+            "    off, n1 = add_len(buf, t1, off, F[\"message.pos.points.len\"], 1, 4)\n",
+            "    [\"message.pos.points.item.f0\"] = ProtoField.bytes(",
+            // Nested options share their fields.
+            "            off, present = add_option(buf, tree, off, F[\"message.nested.f0.present\"])\n\
+             \x20           if present then\n\
+             \x20               do\n\
+             \x20                   local present\n\
+             \x20                   off, present = add_option(buf, tree, off, F[\"message.nested.f0.present\"])\n",
+            "                            local s2, t2 = off, t1:add(F[\"message.nested.f1.item\"], buf(off, 0))\n\
+             \x20                           off = add_bool(buf, t2, off, F[\"message.nested.f1.item.f0\"])\n",
+        ] {
+            assert!(lua.contains(expected), "missing:\n{expected}\nin:\n{lua}");
+        }
+    }
+
+    #[derive(HubpackSchema)]
+    #[allow(dead_code)]
+    struct Clash {
+        body: Option<u8>,
+        other: Body,
+    }
+
+    #[derive(HubpackSchema)]
+    #[allow(dead_code)]
+    struct Body {
+        present: u16,
+    }
+
+    #[derive(HubpackSchema)]
+    #[allow(dead_code)]
+    struct Unbounded(&'static [u8]);
+
+    #[test]
+    fn wireshark_errors() {
+        assert_eq!(
+            dissector(&Clash::SCHEMA, 1),
+            Err(Error::NameClash("clash.body.present".into())),
+        );
+        assert!(matches!(dissector(&u8::SCHEMA, 1), Err(Error::Unsupported(_))));
+        assert_eq!(
+            dissector(&Unbounded::SCHEMA, 1),
+            Err(Error::Unbounded("a sequence".into())),
+        );
+    }
+}
@@ -0,0 +1,24 @@
+// Generated by hubpack-codegen. Do not edit.
+
+#[derive(Clone, Debug, PartialEq, ::serde::Serialize, ::serde::Deserialize, ::hubpack::SerializedSize, ::hubpack::HubpackSchema)]
+pub enum Body {
+    Ping,
+    Reading(char, f32),
+    Text {
+        #[serde(with = "::hubpack::MaxLen::<8>")]
+        text: ::heapless::String<8>,
+        long: i64,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, ::serde::Serialize, ::serde::Deserialize, ::hubpack::SerializedSize, ::hubpack::HubpackSchema)]
+pub struct Frame {
+    pub id: u16,
+    pub delta: i32,
+    pub flags: [bool; 2],
+    pub name: ::hubpack::FixedStr<6>,
+    pub tail: Option<(u8, i8)>,
+    #[serde(with = "::hubpack::MaxLen::<3>")]
+    pub samples: ::heapless::Vec<i16, 3>,
+    pub body: Body,
+}
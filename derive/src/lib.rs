@@ -2,23 +2,27 @@ extern crate proc_macro;
 
 use proc_macro::TokenStream;
 use quote::quote_spanned;
+use syn::ext::IdentExt;
 use syn::spanned::Spanned;
 
-#[proc_macro_derive(SerializedSize)]
+#[proc_macro_derive(SerializedSize, attributes(hubpack, serde))]
 pub fn size_derive(input: TokenStream) -> TokenStream {
     let input = syn::parse_macro_input!(input as syn::DeriveInput);
 
     let name = input.ident;
 
-    let generics = add_trait_bounds(input.generics);
+    let generics = add_trait_bounds(input.generics, syn::parse_quote!(::hubpack::SerializedSize));
     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
-    let dispatch = gen_dispatch(&name, &input.data);
+    let max_size = gen_dispatch(&name, &input.data, Bound::Max);
+    let min_size = gen_dispatch(&name, &input.data, Bound::Min);
 
     let expanded = quote_spanned! {name.span()=>
         impl #impl_generics ::hubpack::SerializedSize for #name #ty_generics
         #where_clause {
-            const MAX_SIZE: usize = #dispatch;
+            const MAX_SIZE: usize = #max_size;
+            const MIN_SIZE: usize = #min_size;
+            const IS_FIXED_SIZE: bool = Self::MIN_SIZE == Self::MAX_SIZE;
         }
     };
 
@@ -28,38 +32,218 @@ pub fn size_derive(input: TokenStream) -> TokenStream {
 /// Naively slaps a bound on every generic type parameter. This leads to
 /// overconstrained impls but it's sure easy -- and it's essentially what the
 /// built in derives do.
-fn add_trait_bounds(mut generics: syn::Generics) -> syn::Generics {
+fn add_trait_bounds(mut generics: syn::Generics, bound: syn::TypeParamBound) -> syn::Generics {
     for param in &mut generics.params {
         if let syn::GenericParam::Type(type_param) = param {
             type_param
                 .bounds
-                .push(syn::parse_quote!(::hubpack::SerializedSize));
+                .push(bound.clone());
         }
     }
     generics
 }
 
-fn gen_dispatch(ty: &syn::Ident, data: &syn::Data) -> proc_macro2::TokenStream {
+#[proc_macro_derive(HubpackSchema, attributes(hubpack, serde))]
+pub fn schema_derive(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+
+    let name = input.ident;
+
+    let generics = add_trait_bounds(
+        input.generics,
+        syn::parse_quote!(::hubpack::schema::HubpackSchema),
+    );
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let schema = gen_schema(&name, &input.data);
+    let hash = match hash_without_names(&input.attrs) {
+        Ok(false) => quote_spanned! {name.span()=> Self::SCHEMA.hash() },
+        Ok(true) => quote_spanned! {name.span()=> Self::SCHEMA.hash_without_names() },
+        Err(e) => e.to_compile_error(),
+    };
+
+    let expanded = quote_spanned! {name.span()=>
+        impl #impl_generics ::hubpack::schema::HubpackSchema for #name #ty_generics
+        #where_clause {
+            const SCHEMA: ::hubpack::schema::Schema = #schema;
+            const SCHEMA_HASH: u64 = #hash;
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
+/// Generates the `Schema` expression for a struct or enum.
+fn gen_schema(ty: &syn::Ident, data: &syn::Data) -> proc_macro2::TokenStream {
+    let name = ty.unraw().to_string();
     match data {
-        syn::Data::Struct(data) => gen_fields(ty, &data.fields),
+        syn::Data::Struct(data) => {
+            let fields = gen_schema_fields(&data.fields);
+            quote_spanned! {ty.span()=>
+                ::hubpack::schema::Schema::Struct {
+                    name: #name,
+                    fields: #fields,
+                }
+            }
+        }
         syn::Data::Enum(data) => {
-            let variants = data.variants.iter().map(|v| gen_fields(ty, &v.fields));
+            if data.variants.len() > 256 {
+                return syn::Error::new(ty.span(), "hubpack supports at most 256 variants")
+                    .to_compile_error();
+            }
+            let variants = data.variants.iter().enumerate().map(|(i, v)| {
+                let vname = v.ident.unraw().to_string();
+                let index = i as u8;
+                let fields = gen_schema_fields(&v.fields);
+                quote_spanned! {v.span()=>
+                    ::hubpack::schema::Variant {
+                        name: #vname,
+                        index: #index,
+                        fields: #fields,
+                    }
+                }
+            });
+            quote_spanned! {ty.span()=>
+                ::hubpack::schema::Schema::Enum {
+                    name: #name,
+                    variants: &[ #( #variants ),* ],
+                }
+            }
+        }
+        syn::Data::Union(_) => {
+            unimplemented!("Unions are not supported")
+        }
+    }
+}
 
-            // We now need to take the maximum of the variant sizes, and
-            // then add one for the variant index.
-            quote_spanned! {ty.span() =>
-                {
-                    let mut __max__ = 0usize;
+/// Generates the `Fields` expression for a struct or enum variant.
+fn gen_schema_fields(fields: &syn::Fields) -> proc_macro2::TokenStream {
+    match fields {
+        syn::Fields::Named(fields) => {
+            let named = fields.named.iter().map(|f| {
+                // Named fields always have an ident.
+                let fname = f.ident.as_ref().unwrap().unraw().to_string();
+                let schema = gen_field_schema(f);
+                quote_spanned! {f.span()=>
+                    ::hubpack::schema::Field { name: #fname, schema: #schema }
+                }
+            });
+            quote_spanned! {fields.span()=>
+                ::hubpack::schema::Fields::Named(&[ #( #named ),* ])
+            }
+        }
+        syn::Fields::Unnamed(fields) => {
+            let unnamed = fields.unnamed.iter().map(gen_field_schema);
+            quote_spanned! {fields.span()=>
+                ::hubpack::schema::Fields::Tuple(&[ #( #unnamed ),* ])
+            }
+        }
+        syn::Fields::Unit => quote_spanned! {fields.span()=>
+            ::hubpack::schema::Fields::Unit
+        },
+    }
+}
 
-                    #(
-                        let __next__ = #variants;
-                        if __next__ > __max__ {
-                            __max__ = __next__;
-                        }
-                    )*
+/// Generates the `Schema` expression for a single field.
+fn gen_field_schema(f: &syn::Field) -> proc_macro2::TokenStream {
+    let ty = &f.ty;
+    match field_max_len(f) {
+        Ok(None) => quote_spanned! {f.span()=>
+            <#ty as ::hubpack::schema::HubpackSchema>::SCHEMA
+        },
+        Ok(Some(max_len)) => quote_spanned! {f.span()=>
+            <#ty as ::hubpack::schema::HubpackSchema>::SCHEMA.with_max_len(#max_len::MAX_LEN)
+        },
+        Err(e) => e.to_compile_error(),
+    }
+}
 
-                        __max__ + 1
-                }
+/// Derives `Packable`, using a byte array of `MAX_SIZE` bytes as the buffer.
+/// Naming that type needs a `MAX_SIZE` that doesn't depend on any generic
+/// parameters, which rules out types with type or const parameters. Lifetimes
+/// don't affect the size, so we measure the type with all of them set to
+/// `'static`.
+#[proc_macro_derive(Packable)]
+pub fn packable_derive(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+
+    let name = input.ident;
+    let generics = input.generics;
+
+    if let Some(param) = generics.type_params().next() {
+        return unsized_param(param.span());
+    }
+    if let Some(param) = generics.const_params().next() {
+        return unsized_param(param.span());
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let statics = generics.lifetimes().map(|_| quote_spanned! {name.span()=> 'static });
+
+    let expanded = quote_spanned! {name.span()=>
+        impl #impl_generics ::hubpack::packed::Packable for #name #ty_generics
+        #where_clause {
+            type Buffer = [u8; <#name<#(#statics),*> as ::hubpack::SerializedSize>::MAX_SIZE];
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
+fn unsized_param(span: proc_macro2::Span) -> TokenStream {
+    let msg = "Packable can't be derived for types with type or const parameters; \
+        implement it for each instance you need instead";
+    TokenStream::from(syn::Error::new(span, msg).to_compile_error())
+}
+
+/// Which end of the size range we're generating an expression for.
+#[derive(Copy, Clone)]
+enum Bound {
+    Min,
+    Max,
+}
+
+fn gen_dispatch(ty: &syn::Ident, data: &syn::Data, bound: Bound) -> proc_macro2::TokenStream {
+    match data {
+        syn::Data::Struct(data) => gen_fields(ty, &data.fields, bound),
+        syn::Data::Enum(data) if data.variants.is_empty() => {
+            // Nothing can be encoded, but we keep the size consistent with a
+            // lone variant index.
+            quote_spanned! {ty.span() => 1 }
+        }
+        syn::Data::Enum(data) => {
+            let variants = data.variants.iter().map(|v| gen_fields(ty, &v.fields, bound));
+
+            // We now need to take the maximum (or minimum) of the variant
+            // sizes, and then add one for the variant index.
+            match bound {
+                Bound::Max => quote_spanned! {ty.span() =>
+                    {
+                        let mut __max__ = 0usize;
+
+                        #(
+                            let __next__ = #variants;
+                            if __next__ > __max__ {
+                                __max__ = __next__;
+                            }
+                        )*
+
+                            __max__ + 1
+                    }
+                },
+                Bound::Min => quote_spanned! {ty.span() =>
+                    {
+                        let mut __min__ = usize::MAX;
+
+                        #(
+                            let __next__ = #variants;
+                            if __next__ < __min__ {
+                                __min__ = __next__;
+                            }
+                        )*
+
+                            __min__ + 1
+                    }
+                },
             }
         }
         syn::Data::Union(_) => {
@@ -72,14 +256,31 @@ fn gen_dispatch(ty: &syn::Ident, data: &syn::Data) -> proc_macro2::TokenStream {
 fn gen_fields_size<'a>(
     ty: &syn::Ident,
     fields: impl IntoIterator<Item = &'a syn::Field>,
+    bound: Bound,
 ) -> proc_macro2::TokenStream {
 
     let mut stmts = fields
         .into_iter()
         .map(|f| {
             let ty = &f.ty;
-            quote_spanned! {f.span()=>
-                <#ty as ::hubpack::SerializedSize>::MAX_SIZE
+            match (field_max_len(f), bound) {
+                (Ok(None), Bound::Max) => quote_spanned! {f.span()=>
+                    <#ty as ::hubpack::SerializedSize>::MAX_SIZE
+                },
+                (Ok(None), Bound::Min) => quote_spanned! {f.span()=>
+                    <#ty as ::hubpack::SerializedSize>::MIN_SIZE
+                },
+                (Ok(Some(max_len)), Bound::Max) => quote_spanned! {f.span()=>
+                    (#max_len::PREFIX_SIZE
+                        + #max_len::MAX_LEN * <#ty as ::hubpack::size::BoundedSize>::ELEMENT_MAX_SIZE)
+                },
+                // Variable-length data can always be empty.
+                (Ok(Some(max_len)), Bound::Min) => quote_spanned! {f.span()=>
+                    #max_len::PREFIX_SIZE
+                },
+                (Err(e), Bound::Max) => e.to_compile_error(),
+                // Already reported when generating `MAX_SIZE`.
+                (Err(_), Bound::Min) => quote_spanned! {f.span()=> 0 },
             }
         })
         .peekable();
@@ -94,10 +295,11 @@ fn gen_fields_size<'a>(
 fn gen_fields(
     ty: &syn::Ident,
     fields: &syn::Fields,
+    bound: Bound,
 ) -> proc_macro2::TokenStream {
     match fields {
-        syn::Fields::Named(fields) => gen_named_struct(ty, fields),
-        syn::Fields::Unnamed(fields) => gen_tuple_struct(ty, fields),
+        syn::Fields::Named(fields) => gen_named_struct(ty, fields, bound),
+        syn::Fields::Unnamed(fields) => gen_tuple_struct(ty, fields, bound),
         syn::Fields::Unit => gen_unit(ty),
     }
 }
@@ -112,13 +314,91 @@ fn gen_unit(
 fn gen_named_struct(
     ty: &syn::Ident,
     fields: &syn::FieldsNamed,
+    bound: Bound,
 ) -> proc_macro2::TokenStream {
-    gen_fields_size(ty, &fields.named)
+    gen_fields_size(ty, &fields.named, bound)
 }
 
 fn gen_tuple_struct(
     ty: &syn::Ident,
     fields: &syn::FieldsUnnamed,
+    bound: Bound,
 ) -> proc_macro2::TokenStream {
-    gen_fields_size(ty, &fields.unnamed)
+    gen_fields_size(ty, &fields.unnamed, bound)
+}
+
+/// Checks whether a type is marked `#[hubpack(hash_without_names)]`, meaning
+/// its schema hash should only cover the encoding.
+fn hash_without_names(attrs: &[syn::Attribute]) -> syn::Result<bool> {
+    let mut without_names = false;
+    for attr in attrs {
+        if !attr.path.is_ident("hubpack") {
+            continue;
+        }
+        let list = match attr.parse_meta()? {
+            syn::Meta::List(list) => list,
+            other => {
+                return Err(syn::Error::new(other.span(), "expected #[hubpack(...)]"));
+            }
+        };
+        for nested in &list.nested {
+            match nested {
+                syn::NestedMeta::Meta(syn::Meta::Path(p))
+                    if p.is_ident("hash_without_names") =>
+                {
+                    without_names = true;
+                }
+                other => {
+                    return Err(syn::Error::new(
+                        other.span(),
+                        "unrecognized hubpack attribute",
+                    ));
+                }
+            }
+        }
+    }
+    Ok(without_names)
+}
+
+/// Finds the maximum length declared for a variable-length field using
+/// `#[serde(with = "hubpack::MaxLen::<N>")]`, if any, returning the path to
+/// `MaxLen::<N>`. Any path ending in `MaxLen` counts, so that it can be
+/// imported under its own name.
+fn field_max_len(f: &syn::Field) -> syn::Result<Option<syn::ExprPath>> {
+    let mut max_len = None;
+    for attr in &f.attrs {
+        if attr.path.is_ident("hubpack") {
+            // Maximum lengths are declared through `serde`, so that the
+            // serializer sees them too.
+            return Err(syn::Error::new(
+                attr.span(),
+                "declare a maximum length with #[serde(with = \"hubpack::MaxLen::<N>\")]",
+            ));
+        }
+        if !attr.path.is_ident("serde") {
+            continue;
+        }
+        // Leave anything we can't make sense of to serde.
+        let Ok(syn::Meta::List(list)) = attr.parse_meta() else {
+            continue;
+        };
+        for nested in &list.nested {
+            let syn::NestedMeta::Meta(syn::Meta::NameValue(nv)) = nested else {
+                continue;
+            };
+            let syn::Lit::Str(s) = &nv.lit else {
+                continue;
+            };
+            if !nv.path.is_ident("with") {
+                continue;
+            }
+            let Ok(path) = s.parse::<syn::ExprPath>() else {
+                continue;
+            };
+            if path.path.segments.last().is_some_and(|seg| seg.ident == "MaxLen") {
+                max_len = Some(path);
+            }
+        }
+    }
+    Ok(max_len)
 }
@@ -0,0 +1,403 @@
+//! Checking whether a change to a message type is wire-compatible.
+//!
+//! Message types change over time, and peers aren't always updated together.
+//! [`check_compat`] compares the [`Schema`]s of two versions of a type, and
+//! says whether messages written with one can be read with the other:
+//!
+//! ```
+//! use hubpack::HubpackSchema;
+//! use hubpack::compat::{check_compat, Compatibility};
+//!
+//! mod v1 {
+//!     #[derive(hubpack::HubpackSchema)]
+//!     pub enum Request { Ping, Read(u16) }
+//! }
+//! mod v2 {
+//!     #[derive(hubpack::HubpackSchema)]
+//!     pub enum Request { Ping, Read(u16), Write(u16, u8) }
+//! }
+//! mod v3 {
+//!     #[derive(hubpack::HubpackSchema)]
+//!     pub enum Request { Ping, Read(u32), Write(u16, u8) }
+//! }
+//!
+//! let report = check_compat(&v1::Request::SCHEMA, &v2::Request::SCHEMA);
+//! assert_eq!(report.compatibility, Compatibility::ForwardCompatible);
+//!
+//! let report = check_compat(&v2::Request::SCHEMA, &v3::Request::SCHEMA);
+//! assert!(report.is_breaking());
+//! assert_eq!(report.to_string(), "breaking: Read.0: type changed from u16 to u32");
+//! ```
+//!
+//! This is handy in unit tests: keep the schema of each released version of a
+//! message as a constant, and check new versions against it, so that CI
+//! catches accidental wire breaks.
+//!
+//! Names don't affect the encoding, so renaming a type, field, or variant is
+//! compatible. Moving a field or variant, though, is reported as breaking,
+//! even when the encoding happens to stay the same, since the bytes would
+//! change meaning. Values that take no bytes at all, like `()` and empty
+//! structs, are compatible with each other, whatever their types.
+
+use core::fmt;
+use crate::error::FieldPath;
+use crate::schema::{Fields, Schema, Variant};
+
+/// How compatible two versions of a type are, from best to worst.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Compatibility {
+    /// Both versions encode the same way, and can read each other's messages.
+    Compatible,
+    /// The new version can read messages written with the old one, but not
+    /// necessarily the other way around. For instance, an enum variant was
+    /// appended.
+    ForwardCompatible,
+    /// The new version can't read some messages written with the old one.
+    Breaking,
+}
+
+/// One difference between two schemas.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// A value is now encoded as a different type.
+    TypeChanged { old: Schema, new: Schema },
+    /// An array, tuple, or fixed-size string changed length.
+    LengthChanged { old: usize, new: usize },
+    /// Fields were added or removed.
+    FieldCountChanged { old: usize, new: usize },
+    /// A field is now at a different position.
+    FieldMoved { name: &'static str, old: usize, new: usize },
+    /// A variant was added.
+    VariantAdded { name: &'static str, index: u8 },
+    /// A variant was removed.
+    VariantRemoved { name: &'static str, index: u8 },
+    /// A variant now has a different index.
+    VariantMoved { name: &'static str, old: u8, new: u8 },
+    /// The maximum length of a sequence or string changed. `None` means
+    /// there's no maximum.
+    MaxLenChanged { old: Option<usize>, new: Option<usize> },
+}
+
+impl Change {
+    /// Returns how much this change affects compatibility.
+    pub fn compatibility(&self) -> Compatibility {
+        match self {
+            Change::VariantAdded { .. } => Compatibility::ForwardCompatible,
+            // Raising a maximum length is fine, as long as the length prefix
+            // stays the same size, which it does unless it crosses 255.
+            Change::MaxLenChanged { old: Some(old), new: Some(new) }
+                if new >= old && (*old <= 255) == (*new <= 255) =>
+            {
+                Compatibility::ForwardCompatible
+            }
+            _ => Compatibility::Breaking,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::TypeChanged { old, new } => {
+                write!(f, "type changed from {} to {}", type_name(old), type_name(new))
+            }
+            Change::LengthChanged { old, new } => {
+                write!(f, "length changed from {old} to {new}")
+            }
+            Change::FieldCountChanged { old, new } => {
+                write!(f, "number of fields changed from {old} to {new}")
+            }
+            Change::FieldMoved { name, old, new } => {
+                write!(f, "field {name} moved from position {old} to {new}")
+            }
+            Change::VariantAdded { name, index } => {
+                write!(f, "variant {name} added at index {index}")
+            }
+            Change::VariantRemoved { name, index } => {
+                write!(f, "variant {name} removed from index {index}")
+            }
+            Change::VariantMoved { name, old, new } => {
+                write!(f, "variant {name} moved from index {old} to {new}")
+            }
+            Change::MaxLenChanged { old, new } => {
+                f.write_str("maximum length changed from ")?;
+                match old {
+                    Some(n) => write!(f, "{n}")?,
+                    None => f.write_str("unlimited")?,
+                }
+                f.write_str(" to ")?;
+                match new {
+                    Some(n) => write!(f, "{n}"),
+                    None => f.write_str("unlimited"),
+                }
+            }
+        }
+    }
+}
+
+/// A [`Change`], and where in the type it happened.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Difference {
+    /// Field and variant names leading to the change, outermost first. This
+    /// is empty if the change is at the top level.
+    pub path: FieldPath,
+    /// What changed there.
+    pub change: Change,
+}
+
+/// The result of [`check_compat`].
+///
+/// This records the overall compatibility, and the first difference that
+/// caused it, without allocating.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    /// The worst compatibility of any difference found.
+    pub compatibility: Compatibility,
+    /// The first difference with that compatibility, if there was one.
+    pub difference: Option<Difference>,
+}
+
+impl Report {
+    /// Checks whether the two versions encode the same way.
+    pub fn is_compatible(&self) -> bool {
+        self.compatibility == Compatibility::Compatible
+    }
+
+    /// Checks whether the new version can't read messages from the old one.
+    pub fn is_breaking(&self) -> bool {
+        self.compatibility == Compatibility::Breaking
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self.compatibility {
+            Compatibility::Compatible => "compatible",
+            Compatibility::ForwardCompatible => "forward-compatible",
+            Compatibility::Breaking => "breaking",
+        })?;
+        if let Some(d) = &self.difference {
+            f.write_str(": ")?;
+            if d.path.depth() != 0 {
+                write!(f, "{}: ", d.path)?;
+            }
+            write!(f, "{}", d.change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares the schemas of an `old` and a `new` version of a type, and reports
+/// whether the `new` version can read messages written using the `old` one.
+pub fn check_compat(old: &Schema, new: &Schema) -> Report {
+    let mut c = Checker {
+        path: FieldPath::default(),
+        report: Report { compatibility: Compatibility::Compatible, difference: None },
+    };
+    c.schema(old, new);
+    c.report
+}
+
+/// Names used in paths for tuple elements and unnamed fields.
+const INDICES: [&str; 16] = [
+    "0", "1", "2", "3", "4", "5", "6", "7",
+    "8", "9", "10", "11", "12", "13", "14", "15",
+];
+
+fn index_name(i: usize) -> &'static str {
+    INDICES.get(i).copied().unwrap_or("_")
+}
+
+struct Checker {
+    path: FieldPath,
+    report: Report,
+}
+
+impl Checker {
+    /// Records `change`, if it's worse than anything found so far.
+    fn note(&mut self, change: Change) {
+        let compatibility = change.compatibility();
+        if compatibility > self.report.compatibility {
+            self.report = Report {
+                compatibility,
+                difference: Some(Difference { path: self.path, change }),
+            };
+        }
+    }
+
+    fn nested(&mut self, name: &'static str, old: &Schema, new: &Schema) {
+        self.path.push(name);
+        self.schema(old, new);
+        self.path.pop();
+    }
+
+    fn schema(&mut self, old: &Schema, new: &Schema) {
+        use Schema::*;
+
+        if is_empty(old) && is_empty(new) {
+            // Neither side reads or writes anything.
+            return;
+        }
+        match (old, new) {
+            (Option(old), Option(new)) => self.schema(old, new),
+            (Array { len: old_len, element: old }, Array { len: new_len, element: new }) => {
+                if old_len != new_len {
+                    self.note(Change::LengthChanged { old: *old_len, new: *new_len });
+                }
+                self.schema(old, new);
+            }
+            (Tuple(old), Tuple(new)) => {
+                if old.len() != new.len() {
+                    self.note(Change::LengthChanged { old: old.len(), new: new.len() });
+                }
+                for (i, (old, new)) in old.iter().zip(new.iter()).enumerate() {
+                    self.nested(index_name(i), old, new);
+                }
+            }
+            (Seq { max_len: old_max, element: old }, Seq { max_len: new_max, element: new }) => {
+                self.max_len(*old_max, *new_max);
+                self.schema(old, new);
+            }
+            (Str { max_len: old }, Str { max_len: new }) => self.max_len(*old, *new),
+            (FixedStr { len: old }, FixedStr { len: new }) => {
+                if old != new {
+                    self.note(Change::LengthChanged { old: *old, new: *new });
+                }
+            }
+            (Struct { fields: old, .. }, Struct { fields: new, .. }) => self.fields(old, new),
+            (Enum { variants: old, .. }, Enum { variants: new, .. }) => self.variants(old, new),
+            (old, new) => {
+                // Everything else is a primitive, which is only compatible
+                // with itself.
+                if core::mem::discriminant(old) != core::mem::discriminant(new) {
+                    self.note(Change::TypeChanged { old: *old, new: *new });
+                }
+            }
+        }
+    }
+
+    fn max_len(&mut self, old: Option<usize>, new: Option<usize>) {
+        if old != new {
+            self.note(Change::MaxLenChanged { old, new });
+        }
+    }
+
+    fn fields(&mut self, old: &Fields, new: &Fields) {
+        match (old, new) {
+            (Fields::Named(old), Fields::Named(new)) => {
+                if old.len() != new.len() {
+                    self.note(Change::FieldCountChanged { old: old.len(), new: new.len() });
+                }
+                // A field that's at a different position is a move; a field
+                // whose name is new is taken to be a rename.
+                for (i, field) in new.iter().enumerate() {
+                    if let Some(j) = old.iter().position(|f| f.name == field.name) {
+                        if i != j {
+                            self.note(Change::FieldMoved { name: field.name, old: j, new: i });
+                        }
+                    }
+                }
+                for (old, new) in old.iter().zip(new.iter()) {
+                    self.nested(new.name, &old.schema, &new.schema);
+                }
+            }
+            _ => {
+                // Switching between named and unnamed fields doesn't change
+                // the encoding, so compare the field types in order.
+                let (old_count, new_count) = (field_count(old), field_count(new));
+                if old_count != new_count {
+                    self.note(Change::FieldCountChanged { old: old_count, new: new_count });
+                }
+                for i in 0..old_count.min(new_count) {
+                    if let (Some(o), Some(n)) = (field_schema(old, i), field_schema(new, i)) {
+                        self.nested(index_name(i), o, n);
+                    }
+                }
+            }
+        }
+    }
+
+    fn variants(&mut self, old: &[Variant], new: &[Variant]) {
+        for o in old {
+            match new.iter().find(|n| n.index == o.index) {
+                Some(n) => {
+                    self.path.push(n.name);
+                    self.fields(&o.fields, &n.fields);
+                    self.path.pop();
+                }
+                None => self.note(Change::VariantRemoved { name: o.name, index: o.index }),
+            }
+            if let Some(n) = new.iter().find(|n| n.name == o.name && n.index != o.index) {
+                self.note(Change::VariantMoved { name: o.name, old: o.index, new: n.index });
+            }
+        }
+        for n in new {
+            if !old.iter().any(|o| o.index == n.index) {
+                self.note(Change::VariantAdded { name: n.name, index: n.index });
+            }
+        }
+    }
+}
+
+/// Checks whether every value of `schema` is encoded as zero bytes.
+fn is_empty(schema: &Schema) -> bool {
+    match schema {
+        Schema::Unit => true,
+        Schema::FixedStr { len } => *len == 0,
+        Schema::Array { len, element } => *len == 0 || is_empty(element),
+        Schema::Tuple(elements) => elements.iter().all(is_empty),
+        Schema::Struct { fields, .. } => match fields {
+            Fields::Unit => true,
+            Fields::Tuple(list) => list.iter().all(is_empty),
+            Fields::Named(list) => list.iter().all(|f| is_empty(&f.schema)),
+        },
+        _ => false,
+    }
+}
+
+/// Returns the number of fields in a list, whether they're named or not.
+fn field_count(fields: &Fields) -> usize {
+    match fields {
+        Fields::Unit => 0,
+        Fields::Tuple(list) => list.len(),
+        Fields::Named(list) => list.len(),
+    }
+}
+
+/// Returns the type of the field at position `i`, whether it's named or not.
+fn field_schema(fields: &Fields, i: usize) -> Option<&Schema> {
+    match fields {
+        Fields::Unit => None,
+        Fields::Tuple(list) => list.get(i),
+        Fields::Named(list) => list.get(i).map(|f| &f.schema),
+    }
+}
+
+/// Short name for a type, for messages.
+fn type_name(schema: &Schema) -> &'static str {
+    match schema {
+        Schema::Unit => "()",
+        Schema::Bool => "bool",
+        Schema::U8 => "u8",
+        Schema::U16 => "u16",
+        Schema::U32 => "u32",
+        Schema::U64 => "u64",
+        Schema::U128 => "u128",
+        Schema::I8 => "i8",
+        Schema::I16 => "i16",
+        Schema::I32 => "i32",
+        Schema::I64 => "i64",
+        Schema::I128 => "i128",
+        Schema::F32 => "f32",
+        Schema::F64 => "f64",
+        Schema::Char => "char",
+        Schema::Option(_) => "Option",
+        Schema::Array { .. } => "array",
+        Schema::Tuple(_) => "tuple",
+        Schema::Seq { .. } => "sequence",
+        Schema::Str { .. } => "string",
+        Schema::FixedStr { .. } => "fixed-size string",
+        Schema::Struct { name, .. } => name,
+        Schema::Enum { name, .. } => name,
+    }
+}
@@ -0,0 +1,42 @@
+//! Encoding configuration.
+//!
+//! By default, `hubpack` encodes multi-byte values in little-endian byte order.
+//! The types in this module let you pick a different configuration. Because
+//! the configuration is selected by type, rather than by a runtime value, it
+//! costs nothing in the generated code.
+
+/// Byte order for multi-byte integers and floats.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ByteOrder {
+    /// Least significant byte first.
+    Little,
+    /// Most significant byte first, also known as network byte order.
+    Big,
+}
+
+/// The `Config` trait is implemented by types that describe a variant of the
+/// `hubpack` encoding.
+///
+/// Configuration only affects how values are laid out, never how many bytes
+/// they take, so `SerializedSize` holds for every configuration.
+pub trait Config {
+    /// Byte order used for all integers (including length prefixes and
+    /// `char`s) and floats.
+    const BYTE_ORDER: ByteOrder;
+}
+
+/// The default configuration: little-endian.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LittleEndian;
+
+impl Config for LittleEndian {
+    const BYTE_ORDER: ByteOrder = ByteOrder::Little;
+}
+
+/// Big-endian configuration, for talking to network-byte-order peers.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BigEndian;
+
+impl Config for BigEndian {
+    const BYTE_ORDER: ByteOrder = ByteOrder::Big;
+}
@@ -1,8 +1,12 @@
 //! Deserializing `hubpack`-encoded values back into Rust.
 
+use core::marker::PhantomData;
 use serde::de::{self, Visitor, IntoDeserializer};
 use serde::Deserialize;
-use crate::error::{Error, Result};
+use crate::config::{ByteOrder, Config, LittleEndian};
+use crate::error::{Error, Result, Tracker};
+#[cfg(feature = "error-context")]
+use crate::error::DetailedError;
 
 /// Deserializes a `T` from the serialized representation at the start of
 /// `data`. Deserialization may succeed even if there's additional data tacked
@@ -20,17 +24,155 @@ use crate::error::{Error, Result};
 /// serialized values to be simply concatenated together and then deserialized
 /// correctly.
 pub fn deserialize<T: de::DeserializeOwned>(data: &[u8]) -> Result<(T, &[u8])> {
-    let mut d = Deserializer { data };
-    let val = T::deserialize(&mut d)?;
-    Ok((val, d.data))
+    deserialize_borrowed(data)
 }
 
-struct Deserializer<'de> {
+/// Deserializes a `T` that must take up all of `data`.
+///
+/// Unlike [`deserialize`], this treats any bytes left over after `T` as an
+/// error, `TrailingBytes`. This is useful for protocol endpoints that want to
+/// reject messages with junk on the end, or from peers using a newer schema.
+///
+/// `T` may borrow from `data`, like with [`deserialize_borrowed`].
+pub fn deserialize_exact<'de, T: Deserialize<'de>>(data: &'de [u8]) -> Result<T> {
+    let mut d = Decoder::new(data);
+    let val = d.decode()?;
+    d.finish_exact()?;
+    Ok(val)
+}
+
+/// Deserializes a `T` from the start of `data` like [`deserialize`], but using
+/// the encoding variant described by `config`.
+pub fn deserialize_with_config<T: de::DeserializeOwned, C: Config>(
+    data: &[u8],
+    config: C,
+) -> Result<(T, &[u8])> {
+    let mut d = Decoder::with_config(data, config);
+    let val = d.decode()?;
+    Ok((val, d.finish()))
+}
+
+/// Deserializes a `T` from the serialized representation at the start of
+/// `data`, allowing `T` to borrow from `data`.
+///
+/// This behaves exactly like [`deserialize`], except that `T` can contain
+/// references into `data`, such as `&'de [u8]` or `&'de str` fields. Those are
+/// decoded without copying, by pointing directly into the input buffer.
+pub fn deserialize_borrowed<'de, T: Deserialize<'de>>(
+    data: &'de [u8],
+) -> Result<(T, &'de [u8])> {
+    let mut d = Decoder::new(data);
+    let val = d.decode()?;
+    Ok((val, d.finish()))
+}
+
+/// Deserializes a `T` from the start of `data` like [`deserialize`], but on
+/// failure, describes where in `data` and where in `T` things went wrong.
+#[cfg(feature = "error-context")]
+#[allow(clippy::result_large_err)] // see DetailedError
+pub fn deserialize_with_context<T: de::DeserializeOwned>(
+    data: &[u8],
+) -> core::result::Result<(T, &[u8]), DetailedError> {
+    let mut d = Decoder::new(data);
+    let val = d.decode_with_context()?;
+    Ok((val, d.finish()))
+}
+
+/// A cursor that deserializes values from a buffer, one after another.
+///
+/// `deserialize` is the simplest way to decode a single value. `Decoder` is
+/// useful when a buffer holds several values back-to-back, or when you need to
+/// keep track of how much has been consumed.
+///
+/// `&mut Decoder` implements `serde::Deserializer`, so it can also be handed to
+/// other `serde` adapters.
+///
+/// The `C` parameter selects the encoding variant; see the `config` module.
+pub struct Decoder<'de, C = LittleEndian> {
     data: &'de [u8],
+    len: usize,
+    track: Tracker,
+    /// Maximum length declared by an enclosing `MaxLen`, waiting to be used
+    /// by the next length prefix.
+    max_len: Option<usize>,
+    _config: PhantomData<C>,
+}
+
+impl<'de> Decoder<'de> {
+    /// Creates a `Decoder` that will read from `data`, starting at its
+    /// beginning.
+    pub fn new(data: &'de [u8]) -> Self {
+        Self::with_config(data, LittleEndian)
+    }
 }
 
-impl<'de> Deserializer<'de> {
+impl<'de, C: Config> Decoder<'de, C> {
+    /// Creates a `Decoder` that will read from `data` using the encoding
+    /// variant described by `config`.
+    pub fn with_config(data: &'de [u8], _config: C) -> Self {
+        Self::for_config(data)
+    }
+
+    /// Creates a `Decoder` for the configuration `C`, for code that only has
+    /// the type.
+    pub(crate) fn for_config(data: &'de [u8]) -> Self {
+        Self { data, len: data.len(), track: Tracker::default(), max_len: None, _config: PhantomData }
+    }
+
+    /// Deserializes a `T` at the current position, and advances past it.
+    ///
+    /// If this fails, the position is left where it was before the call.
+    pub fn decode<T: Deserialize<'de>>(&mut self) -> Result<T> {
+        let start = self.data;
+        self.track.reset();
+        self.max_len = None;
+        let result = T::deserialize(&mut *self);
+        if result.is_err() {
+            self.data = start;
+        }
+        result
+    }
+
+    /// Deserializes a `T` like [`Decoder::decode`], but on failure, describes
+    /// where things went wrong.
+    #[cfg(feature = "error-context")]
+    #[allow(clippy::result_large_err)] // see DetailedError
+    pub fn decode_with_context<T: Deserialize<'de>>(
+        &mut self,
+    ) -> core::result::Result<T, DetailedError> {
+        self.decode().map_err(|error| DetailedError {
+            error,
+            context: self.track.context(error),
+        })
+    }
+
+    /// Returns the number of bytes consumed so far.
+    pub fn position(&self) -> usize {
+        self.len - self.data.len()
+    }
+
+    /// Returns the number of bytes that have not yet been consumed.
+    pub fn remaining(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Consumes the `Decoder`, returning the unused part of the data.
+    pub fn finish(self) -> &'de [u8] {
+        self.data
+    }
+
+    /// Consumes the `Decoder`, checking that all of the data was used.
+    ///
+    /// If not, this fails with `TrailingBytes`.
+    pub fn finish_exact(self) -> Result<()> {
+        match self.data.len() {
+            0 => Ok(()),
+            count => Err(Error::TrailingBytes { count }),
+        }
+    }
+
     fn take_u8(&mut self) -> Result<u8> {
+        self.track.access(self.position(), 1);
         let (first, rest) = self.data.split_first()
             .ok_or(Error::Truncated)?;
         self.data = rest;
@@ -38,6 +180,7 @@ impl<'de> Deserializer<'de> {
     }
 
     fn take_ary<const N: usize>(&mut self) -> Result<[u8; N]> {
+        self.track.access(self.position(), N);
         if N <= self.data.len() {
             let (chunk, rest) = self.data.split_at(N);
             self.data = rest;
@@ -48,24 +191,78 @@ impl<'de> Deserializer<'de> {
     }
 
     fn take_u16(&mut self) -> Result<u16> {
-        Ok(u16::from_le_bytes(self.take_ary()?))
+        let bytes = self.take_ary()?;
+        Ok(match C::BYTE_ORDER {
+            ByteOrder::Little => u16::from_le_bytes(bytes),
+            ByteOrder::Big => u16::from_be_bytes(bytes),
+        })
     }
 
     fn take_u32(&mut self) -> Result<u32> {
-        Ok(u32::from_le_bytes(self.take_ary()?))
+        let bytes = self.take_ary()?;
+        Ok(match C::BYTE_ORDER {
+            ByteOrder::Little => u32::from_le_bytes(bytes),
+            ByteOrder::Big => u32::from_be_bytes(bytes),
+        })
     }
 
     fn take_u64(&mut self) -> Result<u64> {
-        Ok(u64::from_le_bytes(self.take_ary()?))
+        let bytes = self.take_ary()?;
+        Ok(match C::BYTE_ORDER {
+            ByteOrder::Little => u64::from_le_bytes(bytes),
+            ByteOrder::Big => u64::from_be_bytes(bytes),
+        })
     }
 
     fn take_u128(&mut self) -> Result<u128> {
-        Ok(u128::from_le_bytes(self.take_ary()?))
+        let bytes = self.take_ary()?;
+        Ok(match C::BYTE_ORDER {
+            ByteOrder::Little => u128::from_le_bytes(bytes),
+            ByteOrder::Big => u128::from_be_bytes(bytes),
+        })
+    }
+
+    /// Reads the length prefix of a slice, string, or sequence, checking it
+    /// against its declared maximum length, `max`, which also picks the size
+    /// of the prefix. Without one, the value isn't supported.
+    pub(crate) fn take_len(&mut self, max: Option<usize>) -> Result<usize> {
+        let max = max.ok_or(Error::NotSupported)?;
+        let len = if max <= usize::from(u8::MAX) {
+            usize::from(self.take_u8()?)
+        } else if max <= usize::from(u16::MAX) {
+            usize::from(self.take_u16()?)
+        } else {
+            return Err(Error::NotSupported);
+        };
+        if len > max {
+            return Err(Error::Invalid);
+        }
+        Ok(len)
+    }
+
+    pub(crate) fn take_slice(&mut self, len: usize) -> Result<&'de [u8]> {
+        self.track.access(self.position(), len);
+        if len <= self.data.len() {
+            let (chunk, rest) = self.data.split_at(len);
+            self.data = rest;
+            Ok(chunk)
+        } else {
+            Err(Error::Truncated)
+        }
+    }
+
+    fn take_bytes(&mut self, max: Option<usize>) -> Result<&'de [u8]> {
+        let len = self.take_len(max)?;
+        self.take_slice(len)
     }
 
+    pub(crate) fn take_str(&mut self, max: Option<usize>) -> Result<&'de str> {
+        core::str::from_utf8(self.take_bytes(max)?)
+            .map_err(|_| Error::InvalidUtf8)
+    }
 }
 
-impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+impl<'de, C: Config> de::Deserializer<'de> for &mut Decoder<'de, C> {
     type Error = Error;
 
     fn is_human_readable(&self) -> bool {
@@ -189,21 +386,48 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        visitor.visit_seq(SeqAccess { inner: self, len })
+        visitor.visit_seq(SeqAccess { inner: self, len, fields: &[] })
     }
 
-    fn deserialize_tuple_struct<V>(self, _name: &'static str, len: usize, visitor: V) -> Result<V::Value>
+    fn deserialize_tuple_struct<V>(self, name: &'static str, len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        if name == crate::max_len::NAME {
+            // The length is the declared maximum of the value inside.
+            self.max_len = Some(len);
+            let value = self.deserialize_tuple(1, visitor);
+            self.max_len = None;
+            return value;
+        }
+        if name == crate::pointer_sized::UNSIGNED {
+            // The length is the number of bits the value has to fit in.
+            let v = self.take_u64()?;
+            if !crate::pointer_sized::fits_unsigned(v, len) {
+                return Err(Error::IntegerOverflow);
+            }
+            return visitor.visit_u64(v);
+        }
+        if name == crate::pointer_sized::SIGNED {
+            let v = self.take_u64()? as i64;
+            if !crate::pointer_sized::fits_signed(v, len) {
+                return Err(Error::IntegerOverflow);
+            }
+            return visitor.visit_i64(v);
+        }
+        if name == crate::fixed_str::NAME {
+            let bytes = self.take_slice(len)?;
+            crate::fixed_str::padded_str(bytes)?;
+            return visitor.visit_borrowed_bytes(bytes);
+        }
         self.deserialize_tuple(len, visitor)
     }
 
-    fn deserialize_struct<V>(self, _name: &'static str, fields: &[&'static str], visitor: V) -> Result<V::Value>
+    fn deserialize_struct<V>(self, _name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_tuple(fields.len(), visitor)
+        visitor.visit_seq(SeqAccess { inner: self, len: fields.len(), fields })
     }
 
     fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
@@ -220,11 +444,11 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         visitor.visit_newtype_struct(self)
     }
 
-    fn deserialize_enum<V>(self, _name: &'static str, _variants: &[&'static str], visitor: V) -> Result<V::Value>
+    fn deserialize_enum<V>(self, _name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_enum(self)
+        visitor.visit_enum(EnumAccess { inner: self, variants })
     }
 
     fn deserialize_identifier<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
@@ -235,46 +459,54 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         Err(Error::NotSupported)
     }
 
-    fn deserialize_bytes<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
-        Err(Error::NotSupported)
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let max = self.max_len.take();
+        visitor.visit_borrowed_bytes(self.take_bytes(max)?)
     }
 
-    fn deserialize_byte_buf<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
-        Err(Error::NotSupported)
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
     }
 
-    fn deserialize_str<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
-        Err(Error::NotSupported)
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let max = self.max_len.take();
+        visitor.visit_borrowed_str(self.take_str(max)?)
     }
 
-    fn deserialize_string<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
-        Err(Error::NotSupported)
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
     }
 
     fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
         Err(Error::NotSupported)
     }
 
-    fn deserialize_seq<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
-        Err(Error::NotSupported)
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let max = self.max_len.take();
+        let len = self.take_len(max)?;
+        visitor.visit_seq(SeqAccess { inner: self, len, fields: &[] })
     }
 
     fn deserialize_map<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
         Err(Error::NotSupported)
     }
 
-    fn deserialize_char<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
-        Err(Error::NotSupported)
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let v = self.take_u32()?;
+        visitor.visit_char(char::from_u32(v).ok_or(Error::InvalidChar)?)
     }
 
 }
 
-struct SeqAccess<'a, 'de> {
-    inner: &'a mut Deserializer<'de>,
+struct SeqAccess<'a, 'de, C> {
+    inner: &'a mut Decoder<'de, C>,
     len: usize,
+    /// Names of the elements, if they're struct fields. This is only used to
+    /// describe errors.
+    fields: &'static [&'static str],
 }
 
-impl<'a, 'de> de::SeqAccess<'de> for SeqAccess<'a, 'de> {
+impl<'de, C: Config> de::SeqAccess<'de> for SeqAccess<'_, 'de, C> {
     type Error = Error;
 
     fn next_element_seed<T: de::DeserializeSeed<'de>>(
@@ -283,7 +515,16 @@ impl<'a, 'de> de::SeqAccess<'de> for SeqAccess<'a, 'de> {
     ) -> Result<Option<T::Value>> {
         if let Some(new_len) = self.len.checked_sub(1) {
             self.len = new_len;
-            Ok(Some(seed.deserialize(&mut *self.inner)?))
+            let field = self.fields.len().checked_sub(new_len + 1)
+                .and_then(|i| self.fields.get(i));
+            if let Some(field) = field {
+                self.inner.track.push(field);
+            }
+            let value = seed.deserialize(&mut *self.inner)?;
+            if field.is_some() {
+                self.inner.track.pop();
+            }
+            Ok(Some(value))
         } else {
             Ok(None)
         }
@@ -294,33 +535,49 @@ impl<'a, 'de> de::SeqAccess<'de> for SeqAccess<'a, 'de> {
     }
 }
 
-impl<'b, 'de> de::EnumAccess<'de> for &'b mut Deserializer<'de> {
+struct EnumAccess<'a, 'de, C> {
+    inner: &'a mut Decoder<'de, C>,
+    /// Names of the enum's variants. This is only used to describe errors.
+    variants: &'static [&'static str],
+}
+
+impl<'de, C: Config> de::EnumAccess<'de> for EnumAccess<'_, 'de, C> {
     type Error = Error;
     type Variant = Self;
-    
+
     fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
-        let v = u8::deserialize(&mut *self)?;
-        Ok((seed.deserialize(u32::from(v).into_deserializer())?, self))
+        let v = u8::deserialize(&mut *self.inner)?;
+        // The only way for this to fail is for `v` not to name a variant.
+        let value = seed.deserialize(u32::from(v).into_deserializer())
+            .map_err(|_: Error| Error::Invalid)?;
+        self.inner.track.push(self.variants.get(usize::from(v)).unwrap_or(&"?"));
+        Ok((value, self))
     }
 }
 
-impl<'b, 'de> de::VariantAccess<'de> for &'b mut Deserializer<'de> {
+impl<'de, C: Config> de::VariantAccess<'de> for EnumAccess<'_, 'de, C> {
     type Error = Error;
 
     fn unit_variant(self) -> Result<()> {
+        self.inner.track.pop();
         Ok(())
     }
 
     fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
-        seed.deserialize(self)
+        let value = seed.deserialize(&mut *self.inner)?;
+        self.inner.track.pop();
+        Ok(value)
     }
 
     fn tuple_variant<V: de::Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
-        serde::Deserializer::deserialize_tuple(self, len, visitor)
+        let value = serde::Deserializer::deserialize_tuple(&mut *self.inner, len, visitor)?;
+        self.inner.track.pop();
+        Ok(value)
     }
 
-    fn struct_variant<V: de::Visitor<'de>>(self, fields: &[&'static str], visitor: V) -> Result<V::Value> {
-        serde::Deserializer::deserialize_tuple(self, fields.len(), visitor)
+    fn struct_variant<V: de::Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value> {
+        let value = serde::Deserializer::deserialize_struct(&mut *self.inner, "", fields, visitor)?;
+        self.inner.track.pop();
+        Ok(value)
     }
 }
-
@@ -0,0 +1,309 @@
+//! Decoding and encoding without the Rust types, using a [`Schema`].
+//!
+//! This is meant for tools, such as debuggers and ground stations, that need
+//! to handle messages from programs they weren't built with. Values are
+//! decoded into a [`Value`] tree, following the same rules as the typed
+//! decoder.
+//!
+//! ```
+//! use hubpack::HubpackSchema;
+//! use hubpack::dynamic::{decode_dynamic, Value};
+//!
+//! #[derive(serde::Serialize, HubpackSchema)]
+//! struct Reading {
+//!     sensor: u8,
+//!     value: Option<i16>,
+//! }
+//!
+//! let mut buf = [0; 4];
+//! let n = hubpack::serialize(&mut buf, &Reading { sensor: 3, value: Some(-2) }).unwrap();
+//! let (value, _rest) = decode_dynamic(&Reading::SCHEMA, &buf[..n]).unwrap();
+//! assert_eq!(value, Value::Record(vec![
+//!     ("sensor".into(), Value::U8(3)),
+//!     ("value".into(), Value::Option(Some(Box::new(Value::I16(-2))))),
+//! ]));
+//! ```
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use crate::config::{Config, LittleEndian};
+use crate::de::Decoder;
+use crate::error::{Error, Result};
+use crate::schema::{Fields, Schema};
+use crate::ser::{Encoder, Output, Serializer};
+
+/// A decoded value, whose type is only known at runtime.
+///
+/// Each kind of `Schema` decodes to a particular kind of `Value`:
+///
+/// - Primitive types decode to the variant of the same name.
+/// - `Option` decodes to `Option`.
+/// - `Array`, `Tuple`, and `Seq` decode to `List`.
+/// - `Str` and `FixedStr` decode to `Str`, without any padding.
+/// - A struct decodes to its fields (see below).
+/// - An enum decodes to `Variant`, holding the variant's fields.
+///
+/// Fields decode to `Unit` if there are none, `List` if they're unnamed, and
+/// `Record` if they're named.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Unit,
+    Bool(bool),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    U128(u128),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    I128(i128),
+    F32(f32),
+    F64(f64),
+    Char(char),
+    Option(Option<Box<Value>>),
+    List(Vec<Value>),
+    Str(String),
+    /// Named fields, in order.
+    Record(Vec<(String, Value)>),
+    /// An enum variant and its fields.
+    Variant {
+        index: u8,
+        name: String,
+        fields: Box<Value>,
+    },
+}
+
+/// Decodes a value described by `schema` from the start of `data`, returning
+/// it along with the rest of `data`.
+pub fn decode_dynamic<'de>(
+    schema: &Schema,
+    data: &'de [u8],
+) -> Result<(Value, &'de [u8])> {
+    decode_dynamic_with_config(schema, data, LittleEndian)
+}
+
+/// Decodes a value described by `schema` like [`decode_dynamic`], using the
+/// encoding variant described by `config`.
+pub fn decode_dynamic_with_config<'de, C: Config>(
+    schema: &Schema,
+    data: &'de [u8],
+    config: C,
+) -> Result<(Value, &'de [u8])> {
+    let mut d = Decoder::with_config(data, config);
+    let value = decode_value(&mut d, schema)?;
+    Ok((value, d.finish()))
+}
+
+/// Encodes `value`, which must match `schema`, into `buf`, returning the
+/// number of bytes used.
+///
+/// Fails with `Invalid` if `value` doesn't have the shape `schema` describes,
+/// including if a sequence or string is longer than its maximum length. Named
+/// fields are matched up by name, so they can appear in any order.
+pub fn encode_dynamic(buf: &mut [u8], schema: &Schema, value: &Value) -> Result<usize> {
+    encode_dynamic_with_config(buf, schema, value, LittleEndian)
+}
+
+/// Encodes `value` like [`encode_dynamic`], using the encoding variant
+/// described by `config`.
+pub fn encode_dynamic_with_config<C: Config>(
+    buf: &mut [u8],
+    schema: &Schema,
+    value: &Value,
+    config: C,
+) -> Result<usize> {
+    let mut e = Encoder::with_config(buf, config);
+    encode_value(&mut e, schema, value)?;
+    Ok(e.position())
+}
+
+/// Decodes a value described by `schema` at the current position of `d`.
+///
+/// Unlike `Decoder::decode`, this doesn't restore the position on failure.
+pub fn decode_value<C: Config>(d: &mut Decoder<'_, C>, schema: &Schema) -> Result<Value> {
+    Ok(match schema {
+        Schema::Unit => Value::Unit,
+        Schema::Bool => Value::Bool(d.decode()?),
+        Schema::U8 => Value::U8(d.decode()?),
+        Schema::U16 => Value::U16(d.decode()?),
+        Schema::U32 => Value::U32(d.decode()?),
+        Schema::U64 => Value::U64(d.decode()?),
+        Schema::U128 => Value::U128(d.decode()?),
+        Schema::I8 => Value::I8(d.decode()?),
+        Schema::I16 => Value::I16(d.decode()?),
+        Schema::I32 => Value::I32(d.decode()?),
+        Schema::I64 => Value::I64(d.decode()?),
+        Schema::I128 => Value::I128(d.decode()?),
+        Schema::F32 => Value::F32(d.decode()?),
+        Schema::F64 => Value::F64(d.decode()?),
+        Schema::Char => Value::Char(d.decode()?),
+        Schema::Option(inner) => Value::Option(if d.decode::<bool>()? {
+            Some(Box::new(decode_value(d, inner)?))
+        } else {
+            None
+        }),
+        Schema::Array { len, element } => Value::List(decode_list(d, *len, element)?),
+        Schema::Tuple(elements) => Value::List(
+            elements.iter().map(|s| decode_value(d, s)).collect::<Result<_>>()?
+        ),
+        // These fail with `Invalid` if the length is over the maximum.
+        Schema::Seq { max_len, element } => {
+            let len = d.take_len(*max_len)?;
+            Value::List(decode_list(d, len, element)?)
+        }
+        Schema::Str { max_len } => Value::Str(d.take_str(*max_len)?.to_string()),
+        Schema::FixedStr { len } => {
+            Value::Str(crate::fixed_str::padded_str(d.take_slice(*len)?)?.to_string())
+        }
+        Schema::Struct { fields, .. } => decode_fields(d, fields)?,
+        Schema::Enum { variants, .. } => {
+            let index = d.decode::<u8>()?;
+            let v = variants.iter().find(|v| v.index == index).ok_or(Error::Invalid)?;
+            Value::Variant {
+                index,
+                name: v.name.to_string(),
+                fields: Box::new(decode_fields(d, &v.fields)?),
+            }
+        }
+    })
+}
+
+fn decode_list<C: Config>(
+    d: &mut Decoder<'_, C>,
+    len: usize,
+    element: &Schema,
+) -> Result<Vec<Value>> {
+    // Don't trust `len` for the allocation; a corrupt prefix shouldn't be able
+    // to make us reserve much more than the data could hold.
+    let mut items = Vec::with_capacity(len.min(d.remaining()));
+    for _ in 0..len {
+        items.push(decode_value(d, element)?);
+    }
+    Ok(items)
+}
+
+fn decode_fields<C: Config>(d: &mut Decoder<'_, C>, fields: &Fields) -> Result<Value> {
+    Ok(match fields {
+        Fields::Unit => Value::Unit,
+        Fields::Tuple(list) => Value::List(
+            list.iter().map(|s| decode_value(d, s)).collect::<Result<_>>()?
+        ),
+        Fields::Named(list) => Value::Record(
+            list.iter()
+                .map(|f| Ok((f.name.to_string(), decode_value(d, &f.schema)?)))
+                .collect::<Result<_>>()?
+        ),
+    })
+}
+
+/// Encodes `value`, which must match `schema`, at the current position of
+/// `s`.
+///
+/// This works with any `Serializer`, so you can also use it with a
+/// `SizeCounter` to measure `value`. Unlike `Serializer::encode`, this
+/// doesn't restore the position on failure.
+pub fn encode_value<O: Output, C: Config>(
+    s: &mut Serializer<O, C>,
+    schema: &Schema,
+    value: &Value,
+) -> Result<()> {
+    match (schema, value) {
+        (Schema::Unit, Value::Unit) => Ok(()),
+        (Schema::Bool, Value::Bool(v)) => s.encode(v),
+        (Schema::U8, Value::U8(v)) => s.encode(v),
+        (Schema::U16, Value::U16(v)) => s.encode(v),
+        (Schema::U32, Value::U32(v)) => s.encode(v),
+        (Schema::U64, Value::U64(v)) => s.encode(v),
+        (Schema::U128, Value::U128(v)) => s.encode(v),
+        (Schema::I8, Value::I8(v)) => s.encode(v),
+        (Schema::I16, Value::I16(v)) => s.encode(v),
+        (Schema::I32, Value::I32(v)) => s.encode(v),
+        (Schema::I64, Value::I64(v)) => s.encode(v),
+        (Schema::I128, Value::I128(v)) => s.encode(v),
+        (Schema::F32, Value::F32(v)) => s.encode(v),
+        (Schema::F64, Value::F64(v)) => s.encode(v),
+        (Schema::Char, Value::Char(v)) => s.encode(v),
+        (Schema::Option(_), Value::Option(None)) => s.encode(&false),
+        (Schema::Option(inner), Value::Option(Some(v))) => {
+            s.encode(&true)?;
+            encode_value(s, inner, v)
+        }
+        (Schema::Array { len, element }, Value::List(items)) if items.len() == *len => {
+            items.iter().try_for_each(|v| encode_value(s, element, v))
+        }
+        (Schema::Tuple(elements), Value::List(items)) if items.len() == elements.len() => {
+            encode_all(s, elements, items)
+        }
+        (Schema::Seq { max_len, element }, Value::List(items)) => {
+            check_len(items.len(), *max_len)?;
+            s.write_len(items.len(), *max_len)?;
+            items.iter().try_for_each(|v| encode_value(s, element, v))
+        }
+        (Schema::Str { max_len }, Value::Str(v)) => {
+            check_len(v.len(), *max_len)?;
+            s.write_len(v.len(), *max_len)?;
+            s.write_slice(v.as_bytes())
+        }
+        (Schema::FixedStr { len }, Value::Str(v)) => {
+            check_len(v.len(), Some(*len))?;
+            if v.as_bytes().contains(&0) {
+                return Err(Error::Invalid);
+            }
+            for &b in v.as_bytes() {
+                s.encode(&b)?;
+            }
+            (v.len()..*len).try_for_each(|_| s.encode(&0u8))
+        }
+        (Schema::Struct { fields, .. }, v) => encode_fields(s, fields, v),
+        (Schema::Enum { variants, .. }, Value::Variant { index, fields, .. }) => {
+            let v = variants.iter().find(|v| v.index == *index).ok_or(Error::Invalid)?;
+            s.encode(index)?;
+            encode_fields(s, &v.fields, fields)
+        }
+        _ => Err(Error::Invalid),
+    }
+}
+
+/// Checks that a sequence or string of `len` elements is within its maximum
+/// length, or a fixed-size string within its size, reporting `Invalid` like
+/// any other mismatch with the schema, where the encoder would report
+/// `TooLong`.
+fn check_len(len: usize, max_len: Option<usize>) -> Result<()> {
+    match max_len {
+        Some(max) if len > max => Err(Error::Invalid),
+        _ => Ok(()),
+    }
+}
+
+fn encode_all<O: Output, C: Config>(
+    s: &mut Serializer<O, C>,
+    schemas: &[Schema],
+    values: &[Value],
+) -> Result<()> {
+    schemas.iter().zip(values).try_for_each(|(schema, v)| encode_value(s, schema, v))
+}
+
+fn encode_fields<O: Output, C: Config>(
+    s: &mut Serializer<O, C>,
+    fields: &Fields,
+    value: &Value,
+) -> Result<()> {
+    match (fields, value) {
+        (Fields::Unit, Value::Unit) => Ok(()),
+        (Fields::Tuple(list), Value::List(items)) if items.len() == list.len() => {
+            encode_all(s, list, items)
+        }
+        (Fields::Named(list), Value::Record(items)) if items.len() == list.len() => {
+            list.iter().try_for_each(|f| {
+                let (_, v) = items.iter()
+                    .find(|(name, _)| name == f.name)
+                    .ok_or(Error::Invalid)?;
+                encode_value(s, &f.schema, v)
+            })
+        }
+        _ => Err(Error::Invalid),
+    }
+}
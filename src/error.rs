@@ -6,46 +6,79 @@ pub enum Error {
     /// The `Serialize` or `Deserialize` implementation for some type produced
     /// `serde`'s `Custom` error type. This is never produced by `hubpack`
     /// itself, but is simply passed through from existing types.
+    #[cfg(not(feature = "custom-message"))]
     Custom,
+    /// The `Serialize` or `Deserialize` implementation for some type produced
+    /// `serde`'s `Custom` error type, with the start of its message; see
+    /// [`Error::custom_message`].
+    #[cfg(feature = "custom-message")]
+    Custom(CustomMessage),
     /// Serializing a value failed because there were not enough bytes
     /// available in the destination buffer.
     Overrun,
     /// Serializing a value failed because it is an enum type with more than 256
     /// variants, which we don't support.
     TooManyVariants,
-    /// Serializing a value failed because it is a type we don't support, such
-    /// as a sequence, map, or `char`.
+    /// Serializing or deserializing a value failed because it is a type we
+    /// don't support, such as a map, or a slice, string, or sequence without a
+    /// declared maximum length.
     NotSupported,
+    /// Serializing a value failed because it contained a slice, string, or
+    /// sequence with more elements than its declared maximum length.
+    TooLong,
     /// Deserializing a value failed because its serialized representation ended
     /// unexpectedly.
     Truncated,
     /// Deserializing a value failed because an encoded value was out of range
-    /// for its type, such as a `bool` with a value of `39`.
+    /// for its type, such as a `bool` with a value of `39`, an enum variant
+    /// index past the last variant, or a length over the declared maximum.
     Invalid,
+    /// Deserializing a `char` failed because the encoded value was not a
+    /// Unicode scalar value: either a surrogate, or above `0x10FFFF`.
+    InvalidChar,
+    /// Deserializing a value failed because an encoded integer does not fit in
+    /// the type being decoded: a `usize` or `isize` (which are always encoded
+    /// as 64 bits) declared with `PointerSized`, being decoded on a platform
+    /// with a smaller pointer width.
+    IntegerOverflow,
+    /// Deserializing a string failed because its contents were not valid
+    /// UTF-8.
+    InvalidUtf8,
+    /// Deserializing a value succeeded, but was expected to use all of the
+    /// input, and didn't. `count` is the number of bytes left over.
+    TrailingBytes { count: usize },
 }
 
 impl core::fmt::Display for Error {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         match self {
+            #[cfg(not(feature = "custom-message"))]
             Self::Custom => f.write_str("Custom"),
+            #[cfg(feature = "custom-message")]
+            Self::Custom(message) => write!(f, "Custom: {}", message.as_str()),
             Self::Overrun => f.write_str("serialization buffer too small"),
             Self::TooManyVariants => f.write_str("too many enum variants (format only supports 256)"),
             Self::NotSupported => f.write_str("type not supported"),
+            Self::TooLong => f.write_str("variable-length value too long"),
             Self::Truncated => f.write_str("truncated"),
             Self::Invalid => f.write_str("invalid/corrupt"),
+            Self::InvalidChar => f.write_str("invalid char"),
+            Self::IntegerOverflow => f.write_str("integer too large for target type"),
+            Self::InvalidUtf8 => f.write_str("invalid UTF-8"),
+            Self::TrailingBytes { count } => write!(f, "{} trailing bytes", count),
         }
     }
 }
 
-impl serde::ser::Error for Error {
-    fn custom<T: core::fmt::Display>(_msg: T) -> Self {
-        Self::Custom
-    }
-}
-
-impl serde::de::Error for Error {
-    fn custom<T: core::fmt::Display>(_msg: T) -> Self {
-        Self::Custom
+#[cfg(feature = "custom-message")]
+impl Error {
+    /// Returns the message of a `Custom` error, cut short if it's longer than
+    /// [`CUSTOM_MESSAGE_LEN`] bytes.
+    pub fn custom_message(&self) -> Option<&str> {
+        match self {
+            Self::Custom(message) => Some(message.as_str()),
+            _ => None,
+        }
     }
 }
 
@@ -56,3 +89,272 @@ impl serde::ser::StdError for Error {}
 
 pub type Result<T> = core::result::Result<T, Error>;
 
+impl Error {
+    fn custom(_msg: impl core::fmt::Display) -> Self {
+        #[cfg(not(feature = "custom-message"))]
+        {
+            Self::Custom
+        }
+        #[cfg(feature = "custom-message")]
+        {
+            Self::Custom(CustomMessage::new(_msg))
+        }
+    }
+}
+
+impl serde::ser::Error for Error {
+    fn custom<T: core::fmt::Display>(msg: T) -> Self {
+        Self::custom(msg)
+    }
+}
+
+impl serde::de::Error for Error {
+    fn custom<T: core::fmt::Display>(msg: T) -> Self {
+        Self::custom(msg)
+    }
+
+    fn invalid_value(
+        unexp: serde::de::Unexpected,
+        exp: &dyn serde::de::Expected,
+    ) -> Self {
+        match unexp {
+            // An integer that its type rejects, like zero for a `NonZeroU32`,
+            // is corrupt data, like a `bool` of 2. (`usize` and `isize` that
+            // don't fit are caught before this, as `IntegerOverflow`, if
+            // they're declared with `PointerSized`.)
+            serde::de::Unexpected::Unsigned(_)
+                | serde::de::Unexpected::Signed(_) => Error::Invalid,
+            _ => Self::custom(format_args!(
+                "invalid value: {}, expected {}", unexp, exp,
+            )),
+        }
+    }
+
+    fn invalid_length(_len: usize, _exp: &dyn serde::de::Expected) -> Self {
+        // This happens when an encoded length prefix is larger than the type
+        // being decoded can hold, e.g. 5 elements for a `heapless::Vec<T, 4>`
+        // declared with a larger `MaxLen`.
+        Error::Invalid
+    }
+}
+
+/// Maximum number of bytes of a custom error message that are recorded.
+#[cfg(feature = "custom-message")]
+pub const CUSTOM_MESSAGE_LEN: usize = 64;
+
+/// The message of a `Custom` error, of which this stores the first
+/// [`CUSTOM_MESSAGE_LEN`] bytes inline, without allocating.
+#[cfg(feature = "custom-message")]
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct CustomMessage {
+    buf: [u8; CUSTOM_MESSAGE_LEN],
+    len: usize,
+}
+
+// Arrays this long don't implement `Default`.
+#[cfg(feature = "custom-message")]
+impl Default for CustomMessage {
+    fn default() -> Self {
+        Self { buf: [0; CUSTOM_MESSAGE_LEN], len: 0 }
+    }
+}
+
+#[cfg(feature = "custom-message")]
+impl core::fmt::Debug for CustomMessage {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(feature = "custom-message")]
+impl CustomMessage {
+    /// Records the start of `msg`.
+    pub(crate) fn new(msg: impl core::fmt::Display) -> Self {
+        use core::fmt::Write;
+        let mut m = Self::default();
+        // Our `write_str` never fails; it just stops recording when full.
+        let _ = write!(m, "{}", msg);
+        m
+    }
+
+    /// Returns the recorded message.
+    pub fn as_str(&self) -> &str {
+        match core::str::from_utf8(&self.buf[..self.len]) {
+            Ok(s) => s,
+            // `write_str` only ever cuts at char boundaries.
+            Err(_) => unreachable!(),
+        }
+    }
+}
+
+#[cfg(feature = "custom-message")]
+impl core::fmt::Write for CustomMessage {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let space = CUSTOM_MESSAGE_LEN - self.len;
+        let mut n = s.len().min(space);
+        while !s.is_char_boundary(n) {
+            n -= 1;
+        }
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+
+/// Maximum number of names recorded in a [`FieldPath`].
+pub const FIELD_PATH_DEPTH: usize = 8;
+
+/// The chain of struct field and enum variant names leading to a value,
+/// outermost first: the value being processed when an error happened, or the
+/// place where two schemas differ.
+///
+/// Only the outermost [`FIELD_PATH_DEPTH`] names are kept; deeper names are
+/// counted but dropped.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FieldPath {
+    names: [&'static str; FIELD_PATH_DEPTH],
+    depth: usize,
+}
+
+impl FieldPath {
+    /// Returns the recorded names, outermost first.
+    pub fn names(&self) -> &[&'static str] {
+        &self.names[..self.depth.min(FIELD_PATH_DEPTH)]
+    }
+
+    /// Returns the full depth of the path, which may be greater than the number
+    /// of names recorded.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Checks whether names were dropped because the path was too deep.
+    pub fn is_truncated(&self) -> bool {
+        self.depth > FIELD_PATH_DEPTH
+    }
+
+    pub(crate) fn push(&mut self, name: &'static str) {
+        if let Some(slot) = self.names.get_mut(self.depth) {
+            *slot = name;
+        }
+        self.depth += 1;
+    }
+
+    pub(crate) fn pop(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+}
+
+impl core::fmt::Display for FieldPath {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        for (i, name) in self.names().iter().enumerate() {
+            if i != 0 {
+                f.write_str(".")?;
+            }
+            f.write_str(name)?;
+        }
+        if self.is_truncated() {
+            f.write_str("...")?;
+        }
+        Ok(())
+    }
+}
+
+/// Information about where in a buffer, and where in a value, an error
+/// happened.
+#[cfg(feature = "error-context")]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// Byte offset of the last read or write `hubpack` attempted before the
+    /// error.
+    pub offset: usize,
+    /// For `Overrun` and `Truncated`, the number of bytes that the failed read
+    /// or write needed at `offset`.
+    pub needed: Option<usize>,
+    /// Fields being processed when the error happened.
+    pub path: FieldPath,
+}
+
+/// An [`Error`] along with its [`ErrorContext`].
+///
+/// This is produced by functions like `serialize_with_context`, when the
+/// `error-context` feature is enabled. It's considerably larger than `Error`,
+/// since it holds the field path inline rather than allocating.
+#[cfg(feature = "error-context")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DetailedError {
+    /// What went wrong.
+    pub error: Error,
+    /// Where in the data and the type it went wrong.
+    pub context: ErrorContext,
+}
+
+#[cfg(feature = "error-context")]
+impl core::fmt::Display for DetailedError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{}", self.error)?;
+        write!(f, " at offset {}", self.context.offset)?;
+        if let Some(needed) = self.context.needed {
+            write!(f, " (needed {} bytes)", needed)?;
+        }
+        if self.context.path.depth() != 0 {
+            write!(f, " in {}", self.context.path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "error-context")]
+impl serde::ser::StdError for DetailedError {}
+
+/// Keeps track of the information needed for an `ErrorContext` during
+/// serialization or deserialization. Without the `error-context` feature,
+/// this is empty and does nothing.
+#[derive(Default)]
+pub(crate) struct Tracker {
+    #[cfg(feature = "error-context")]
+    context: ErrorContext,
+}
+
+impl Tracker {
+    /// Notes that a read or write of `needed` bytes is starting at `offset`.
+    #[inline(always)]
+    pub(crate) fn access(&mut self, _offset: usize, _needed: usize) {
+        #[cfg(feature = "error-context")]
+        {
+            self.context.offset = _offset;
+            self.context.needed = Some(_needed);
+        }
+    }
+
+    /// Notes that we're descending into a field or variant called `name`.
+    #[inline(always)]
+    pub(crate) fn push(&mut self, _name: &'static str) {
+        #[cfg(feature = "error-context")]
+        self.context.path.push(_name);
+    }
+
+    /// Notes that we've successfully finished the innermost field or variant.
+    #[inline(always)]
+    pub(crate) fn pop(&mut self) {
+        #[cfg(feature = "error-context")]
+        self.context.path.pop();
+    }
+
+    /// Forgets everything, in preparation for a new top-level value.
+    #[inline(always)]
+    pub(crate) fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Describes the context of `error`, which just happened.
+    #[cfg(feature = "error-context")]
+    pub(crate) fn context(&self, error: Error) -> ErrorContext {
+        let mut context = self.context;
+        if !matches!(error, Error::Overrun | Error::Truncated) {
+            context.needed = None;
+        }
+        context
+    }
+}
@@ -0,0 +1,209 @@
+//! Fixed-size, NUL-padded strings.
+
+use core::fmt;
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeTuple, Serializer};
+use crate::error::Error;
+use crate::size::SerializedSize;
+
+/// The name under which `FixedStr` asks the `hubpack` deserializer for its
+/// bytes, which it checks with [`padded_str`] and passes along whole.
+pub(crate) const NAME: &str = "hubpack::FixedStr";
+
+/// Returns the string at the start of `bytes`, which ends at the first NUL, or
+/// fills `bytes` if there isn't one.
+///
+/// Fails with `InvalidUtf8` if the string isn't valid UTF-8.
+pub(crate) fn padded_str(bytes: &[u8]) -> Result<&str, Error> {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    core::str::from_utf8(&bytes[..len]).map_err(|_| Error::InvalidUtf8)
+}
+
+/// A string that always occupies exactly `N` bytes, padded at the end with
+/// NULs.
+///
+/// This is intended for interoperating with C structures containing `char`
+/// arrays. Unlike `heapless::String`, it has no length prefix: it's encoded as
+/// its `N` bytes, so its encoded size never varies.
+///
+/// A string of exactly `N` bytes has no terminating NUL. When decoding, the
+/// string ends at the first NUL, and any bytes after it are ignored, as a C
+/// reader would.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FixedStr<const N: usize> {
+    bytes: [u8; N],
+}
+
+impl<const N: usize> FixedStr<N> {
+    /// Creates an empty string.
+    pub const fn new() -> Self {
+        Self { bytes: [0; N] }
+    }
+
+    /// Returns the contents of the string, without padding.
+    pub fn as_str(&self) -> &str {
+        match core::str::from_utf8(&self.bytes[..self.len()]) {
+            Ok(s) => s,
+            // Every constructor checks this.
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Returns the length of the string in bytes, without padding.
+    pub fn len(&self) -> usize {
+        self.bytes.iter().position(|&b| b == 0).unwrap_or(N)
+    }
+
+    /// Checks if the string is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns all `N` bytes of the string, including padding, exactly as they
+    /// are encoded.
+    pub fn as_padded_bytes(&self) -> &[u8; N] {
+        &self.bytes
+    }
+
+    /// Creates a string from `N` bytes in C style: the string ends at the first
+    /// NUL, or fills the array if there isn't one.
+    ///
+    /// Fails with `InvalidUtf8` if the string isn't valid UTF-8.
+    pub fn from_padded_bytes(mut bytes: [u8; N]) -> Result<Self, Error> {
+        let len = padded_str(&bytes)?.len();
+        bytes[len..].fill(0);
+        Ok(Self { bytes })
+    }
+}
+
+impl<const N: usize> Default for FixedStr<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> TryFrom<&str> for FixedStr<N> {
+    type Error = Error;
+
+    /// Copies `s` into a new `FixedStr`.
+    ///
+    /// This fails with `TooLong` if `s` is longer than `N` bytes, and with
+    /// `Invalid` if `s` contains a NUL, since the NUL would cut the string
+    /// short when it's decoded.
+    fn try_from(s: &str) -> Result<Self, Error> {
+        if s.len() > N {
+            return Err(Error::TooLong);
+        }
+        if s.as_bytes().contains(&0) {
+            return Err(Error::Invalid);
+        }
+        let mut bytes = [0; N];
+        bytes[..s.len()].copy_from_slice(s.as_bytes());
+        Ok(Self { bytes })
+    }
+}
+
+impl<const N: usize> core::str::FromStr for FixedStr<N> {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Self::try_from(s)
+    }
+}
+
+impl<const N: usize> core::ops::Deref for FixedStr<N> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize> AsRef<str> for FixedStr<N> {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize> PartialEq<str> for FixedStr<N> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<const N: usize> PartialEq<&str> for FixedStr<N> {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl<const N: usize> fmt::Debug for FixedStr<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl<const N: usize> fmt::Display for FixedStr<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<const N: usize> SerializedSize for FixedStr<N> {
+    const MAX_SIZE: usize = N;
+    const MIN_SIZE: usize = N;
+}
+
+impl<const N: usize> crate::packed::Packable for FixedStr<N> {
+    type Buffer = [u8; N];
+}
+
+impl<const N: usize> Serialize for FixedStr<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // serde only implements `Serialize` for arrays up to 32 elements, so
+        // we write the bytes out as a tuple ourselves.
+        let mut t = serializer.serialize_tuple(N)?;
+        for b in &self.bytes {
+            t.serialize_element(b)?;
+        }
+        t.end()
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for FixedStr<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FixedStrVisitor<const N: usize>;
+
+        impl<'de, const N: usize> Visitor<'de> for FixedStrVisitor<N> {
+            type Value = FixedStr<N>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a NUL-padded string of {} bytes", N)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut bytes = [0; N];
+                for (i, b) in bytes.iter_mut().enumerate() {
+                    *b = seq.next_element()?
+                        .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+                }
+                FixedStr::from_padded_bytes(bytes).map_err(|_| {
+                    de::Error::invalid_value(de::Unexpected::Bytes(&bytes), &self)
+                })
+            }
+
+            // The `hubpack` deserializer hands over all `N` bytes at once,
+            // having already checked them, so that it can report
+            // `InvalidUtf8` itself.
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                let bytes = <[u8; N]>::try_from(v)
+                    .map_err(|_| E::invalid_length(v.len(), &self))?;
+                FixedStr::from_padded_bytes(bytes).map_err(|_| {
+                    E::invalid_value(de::Unexpected::Bytes(v), &self)
+                })
+            }
+        }
+
+        deserializer.deserialize_tuple_struct(NAME, N, FixedStrVisitor)
+    }
+}
@@ -0,0 +1,300 @@
+//! Annotated dumps of encoded messages.
+//!
+//! [`Annotated`] pairs an encoded buffer with the [`Schema`] of the value in
+//! it, and formats it like a protocol analyzer would: one line per component,
+//! with its byte range, its raw bytes, and its decoded value. It doesn't
+//! allocate, so it can be used directly in `no_std` logging.
+//!
+//! ```
+//! use hubpack::HubpackSchema;
+//! use hubpack::fmt::Annotated;
+//!
+//! #[derive(serde::Serialize, HubpackSchema)]
+//! struct Reading {
+//!     sensor: u8,
+//!     value: Option<i16>,
+//! }
+//!
+//! let mut buf = [0; 4];
+//! let n = hubpack::serialize(&mut buf, &Reading { sensor: 3, value: Some(-2) }).unwrap();
+//! let dump = format!("{}", Annotated::new(&Reading::SCHEMA, &buf[..n]));
+//! assert_eq!(dump, "\
+//! 0000..0000                              Reading
+//! 0000..0001  03                            sensor: 3
+//! 0001..0002  01                            value: Some
+//! 0002..0004  fe ff                           -2
+//! ");
+//! ```
+//!
+//! Each line's range covers the bytes shown on it: the component's own
+//! encoding, like an `Option`'s tag or a sequence's length, but not the
+//! components inside it, which get lines of their own. So a struct, which has
+//! no encoding of its own, gets an empty range at its start. Each line is
+//! written as its component starts, so if the buffer doesn't hold a valid
+//! value, the dump ends with a line describing the problem, after everything
+//! decoded before it, with any bytes left from where decoding failed.
+
+use core::fmt::{self, Display, Formatter};
+use core::marker::PhantomData;
+use serde::Deserialize;
+use crate::config::{Config, LittleEndian};
+use crate::de::Decoder;
+use crate::error::Error;
+use crate::schema::{Fields, Schema};
+
+/// Most raw bytes to show on one line; longer runs are cut short.
+const MAX_HEX_BYTES: usize = 8;
+
+/// Formats an encoded value, annotated using its schema. See the module
+/// documentation for an example.
+pub struct Annotated<'a, C = LittleEndian> {
+    schema: &'a Schema,
+    data: &'a [u8],
+    _config: PhantomData<C>,
+}
+
+impl<'a> Annotated<'a> {
+    /// Creates an `Annotated` for a value described by `schema`, at the start
+    /// of `data`.
+    pub fn new(schema: &'a Schema, data: &'a [u8]) -> Self {
+        Self::with_config(schema, data, LittleEndian)
+    }
+}
+
+impl<'a, C: Config> Annotated<'a, C> {
+    /// Creates an `Annotated` for data encoded using the variant described by
+    /// `config`.
+    pub fn with_config(schema: &'a Schema, data: &'a [u8], _config: C) -> Self {
+        Self { schema, data, _config: PhantomData }
+    }
+}
+
+impl<C: Config> Display for Annotated<'_, C> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let len = self.data.len();
+        match self.walk(f, self.schema, 0, 0, Label::None) {
+            Ok(end) if end < len => {
+                self.line(f, end, len - end, 0, Label::None, format_args!(
+                    "({} bytes left over)", len - end,
+                ))
+            }
+            Ok(_) => Ok(()),
+            Err(Stop::Fmt(e)) => Err(e),
+            Err(Stop::Decode { offset, error }) => {
+                self.line(f, offset, len - offset, 0, Label::None, format_args!("error: {error}"))
+            }
+        }
+    }
+}
+
+/// Why a walk ended early.
+enum Stop {
+    Fmt(fmt::Error),
+    Decode { offset: usize, error: Error },
+}
+
+impl From<fmt::Error> for Stop {
+    fn from(e: fmt::Error) -> Self {
+        Stop::Fmt(e)
+    }
+}
+
+impl<'a, C: Config> Annotated<'a, C> {
+    /// Decodes a `T` at `offset`, returning it with the offset just past it.
+    fn read<T: Deserialize<'a>>(&self, offset: usize) -> Result<(T, usize), Stop> {
+        self.read_with(offset, |d| d.decode())
+    }
+
+    /// Decodes something at `offset` using `decode`, returning it with the
+    /// offset just past it.
+    fn read_with<T>(
+        &self,
+        offset: usize,
+        decode: impl FnOnce(&mut Decoder<'a, C>) -> crate::Result<T>,
+    ) -> Result<(T, usize), Stop> {
+        let mut d = Decoder::<C>::for_config(&self.data[offset..]);
+        match decode(&mut d) {
+            Ok(v) => Ok((v, offset + d.position())),
+            Err(error) => Err(Stop::Decode { offset, error }),
+        }
+    }
+
+    /// Writes one line for a component starting at `start`, whose own
+    /// encoding (not counting any children) is `own` bytes long.
+    fn line(
+        &self,
+        f: &mut Formatter,
+        start: usize,
+        own: usize,
+        depth: usize,
+        label: Label,
+        text: fmt::Arguments,
+    ) -> fmt::Result {
+        write!(f, "{start:04x}..{:04x}  ", start + own)?;
+        let bytes = &self.data[start..start + own];
+        for b in bytes.iter().take(MAX_HEX_BYTES) {
+            write!(f, "{b:02x} ")?;
+        }
+        // Pad the hex column out to a fixed width.
+        let shown = bytes.len().min(MAX_HEX_BYTES);
+        let mut pad = (MAX_HEX_BYTES - shown) * 3;
+        if bytes.len() > MAX_HEX_BYTES {
+            f.write_str("… ")?;
+        } else {
+            pad += 2;
+        }
+        writeln!(f, "{:pad$}{:indent$}{label}{text}", "", "", indent = 2 + depth * 2)
+    }
+
+    /// Walks the value described by `schema` at `offset`, writing lines
+    /// describing it, and returns the offset just past it.
+    fn walk(
+        &self,
+        f: &mut Formatter,
+        schema: &Schema,
+        offset: usize,
+        depth: usize,
+        label: Label,
+    ) -> Result<usize, Stop> {
+        macro_rules! primitive {
+            ($t:ty, $fmt:literal) => {{
+                let (v, end) = self.read::<$t>(offset)?;
+                self.line(f, offset, end - offset, depth, label, format_args!($fmt, v))?;
+                Ok(end)
+            }};
+        }
+
+        match schema {
+            Schema::Unit => primitive!((), "{:?}"),
+            Schema::Bool => primitive!(bool, "{}"),
+            Schema::U8 => primitive!(u8, "{}"),
+            Schema::U16 => primitive!(u16, "{}"),
+            Schema::U32 => primitive!(u32, "{}"),
+            Schema::U64 => primitive!(u64, "{}"),
+            Schema::U128 => primitive!(u128, "{}"),
+            Schema::I8 => primitive!(i8, "{}"),
+            Schema::I16 => primitive!(i16, "{}"),
+            Schema::I32 => primitive!(i32, "{}"),
+            Schema::I64 => primitive!(i64, "{}"),
+            Schema::I128 => primitive!(i128, "{}"),
+            Schema::F32 => primitive!(f32, "{}"),
+            Schema::F64 => primitive!(f64, "{}"),
+            Schema::Char => primitive!(char, "{:?}"),
+            Schema::Str { max_len } => {
+                let (v, end) = self.read_with(offset, |d| d.take_str(*max_len))?;
+                self.line(f, offset, end - offset, depth, label, format_args!("{v:?}"))?;
+                Ok(end)
+            }
+            Schema::FixedStr { len } => {
+                let (text, end) = self.read_with(offset, |d| {
+                    crate::fixed_str::padded_str(d.take_slice(*len)?)
+                })?;
+                self.line(f, offset, *len, depth, label, format_args!("{text:?}"))?;
+                Ok(end)
+            }
+            Schema::Option(inner) => {
+                let (some, start) = self.read::<bool>(offset)?;
+                if some {
+                    self.line(f, offset, 1, depth, label, format_args!("Some"))?;
+                    self.walk(f, inner, start, depth + 1, Label::None)
+                } else {
+                    self.line(f, offset, 1, depth, label, format_args!("None"))?;
+                    Ok(start)
+                }
+            }
+            Schema::Array { len, element } => {
+                self.line(f, offset, 0, depth, label, format_args!("[{len} items]"))?;
+                self.walk_repeat(f, element, *len, offset, depth + 1)
+            }
+            Schema::Seq { max_len, element } => {
+                let (len, start) = self.read_with(offset, |d| d.take_len(*max_len))?;
+                self.line(f, offset, start - offset, depth, label, format_args!("[{len} items]"))?;
+                self.walk_repeat(f, element, len, start, depth + 1)
+            }
+            Schema::Tuple(elements) => {
+                self.line(f, offset, 0, depth, label, format_args!("(tuple)"))?;
+                self.walk_list(f, elements, offset, depth + 1)
+            }
+            Schema::Struct { name, fields } => {
+                self.line(f, offset, 0, depth, label, format_args!("{name}"))?;
+                self.walk_fields(f, fields, offset, depth + 1)
+            }
+            Schema::Enum { name, variants } => {
+                let (index, start) = self.read::<u8>(offset)?;
+                let v = variants.iter()
+                    .find(|v| v.index == index)
+                    .ok_or(Stop::Decode { offset, error: Error::Invalid })?;
+                self.line(f, offset, 1, depth, label, format_args!("{name}::{}", v.name))?;
+                self.walk_fields(f, &v.fields, start, depth + 1)
+            }
+        }
+    }
+
+    /// Walks `count` elements described by `element`, labeled by index.
+    fn walk_repeat(
+        &self,
+        f: &mut Formatter,
+        element: &Schema,
+        count: usize,
+        mut offset: usize,
+        depth: usize,
+    ) -> Result<usize, Stop> {
+        for i in 0..count {
+            offset = self.walk(f, element, offset, depth, Label::Index(i))?;
+        }
+        Ok(offset)
+    }
+
+    /// Walks elements described by `list`, labeled by index.
+    fn walk_list(
+        &self,
+        f: &mut Formatter,
+        list: &[Schema],
+        mut offset: usize,
+        depth: usize,
+    ) -> Result<usize, Stop> {
+        for (i, s) in list.iter().enumerate() {
+            offset = self.walk(f, s, offset, depth, Label::Index(i))?;
+        }
+        Ok(offset)
+    }
+
+    /// Walks the fields of a struct or variant.
+    fn walk_fields(
+        &self,
+        f: &mut Formatter,
+        fields: &Fields,
+        mut offset: usize,
+        depth: usize,
+    ) -> Result<usize, Stop> {
+        match fields {
+            Fields::Unit => Ok(offset),
+            Fields::Tuple(list) => self.walk_list(f, list, offset, depth),
+            Fields::Named(list) => {
+                for field in list.iter() {
+                    offset = self.walk(f, &field.schema, offset, depth, Label::Name(field.name))?;
+                }
+                Ok(offset)
+            }
+        }
+    }
+}
+
+/// What a line's component is called within its parent, written before its
+/// value as `3: ` or `name: `.
+#[derive(Copy, Clone)]
+enum Label<'a> {
+    None,
+    Index(usize),
+    Name(&'a str),
+}
+
+impl Display for Label<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Label::None => Ok(()),
+            Label::Index(i) => write!(f, "{i}: "),
+            Label::Name(name) => write!(f, "{name}: "),
+        }
+    }
+}
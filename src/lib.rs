@@ -15,7 +15,8 @@
 //!   Any type that implements `SerializedSize` can report the maximum number of
 //!   bytes necessary to encode it using `hubpack`. This means you can allocate a
 //!   fixed-size buffer without worry. (You can `#[derive(SerializedSize)]` for your
-//!   own types.)
+//!   own types.) It also reports the minimum size, and whether every value
+//!   has the same size.
 //!
 //! - The encode/decode implementations generate fairly small, efficient code.
 //!
@@ -24,8 +25,9 @@
 //!
 //! You might not want to use `hubpack` because of the following limitations:
 //!
-//! - `hubpack` is designed for fixed-size small data structures, and cannot encode
-//!   things like `Vec`, `str`, and maps.
+//! - `hubpack` is designed for fixed-size small data structures. It can encode
+//!   slices and strings with a length prefix, but they only get a
+//!   `SerializedSize` if you declare a maximum length. It cannot encode maps.
 //!
 //! - `hubpack` does not support `enum` types with more than 256 variants.
 //!
@@ -37,20 +39,43 @@
 
 #![forbid(unsafe_code)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 pub mod ser;
 pub mod de;
 pub mod error;
+pub mod config;
+pub mod fixed_str;
+pub mod max_len;
+pub mod packed;
+pub mod pointer_sized;
+pub mod schema;
+pub mod fmt;
+pub mod compat;
+#[cfg(feature = "alloc")]
+pub mod dynamic;
 
 pub mod size;
 
-pub use de::deserialize;
+pub use de::{deserialize, Decoder};
 pub use error::{Error, Result};
-pub use ser::serialize;
+pub use fixed_str::FixedStr;
+pub use max_len::MaxLen;
+pub use packed::Packed;
+pub use pointer_sized::PointerSized;
+pub use schema::HubpackSchema;
+pub use ser::{serialize, serialized_size, Encoder};
+#[cfg(feature = "alloc")]
+pub use ser::to_vec;
 pub use size::SerializedSize;
 
 /// Derive macro for the `SerializedSize` trait.
 pub use hubpack_derive::SerializedSize;
 
+/// Derive macro for the `HubpackSchema` trait.
+pub use hubpack_derive::HubpackSchema;
+
 // Internal re-export to make derive macros work inside the crate.
 extern crate self as hubpack;
 
@@ -59,6 +84,7 @@ mod tests {
     use super::*;
 
     use serde::{Serialize, Deserialize};
+    use packed::Packable;
 
     macro_rules! round_trip {
         ($testname:ident: $t:ty = $init:expr) => {
@@ -73,6 +99,8 @@ mod tests {
 
                 assert_eq!(output, input);
                 assert_eq!(buffer.len() - rest.len(), len);
+                assert_eq!(serialized_size(&input).unwrap(), len);
+                assert!(len >= <$t as crate::SerializedSize>::MIN_SIZE);
                 assert!(len <= BUFSZ,
                     "Serialized length ({}) should be less than SerializedSize predicts ({})", len, BUFSZ);
             }
@@ -99,12 +127,16 @@ mod tests {
     round_trip!(rt_true: bool = true);
     round_trip!(rt_false: bool = false);
 
+    round_trip!(rt_char_ascii: char = 'x');
+    round_trip!(rt_char_max: char = char::MAX);
+    round_trip!(rt_char_emoji: char = '\u{1F980}');
+
     round_trip!(rt_option_u8_none: Option<u8> = None);
     round_trip!(rt_option_u8_some: Option<u8> = Some(0xAA));
 
     round_trip!(rt_tuple: (u8, u16, bool) = (55, 0xCAFE, false));
 
-    #[derive(Debug, Serialize, Deserialize, PartialEq, SerializedSize)]
+    #[derive(Debug, Serialize, Deserialize, PartialEq, SerializedSize, HubpackSchema)]
     struct UnitStruct;
 
     round_trip!(rt_unit_struct: UnitStruct = UnitStruct);
@@ -114,12 +146,12 @@ mod tests {
 
     round_trip!(rt_empty_struct: EmptyStruct = EmptyStruct {});
 
-    #[derive(Debug, Serialize, Deserialize, PartialEq, SerializedSize)]
+    #[derive(Debug, Serialize, Deserialize, PartialEq, SerializedSize, HubpackSchema)]
     struct TupleStruct(u8, u32);
 
     round_trip!(rt_tuple_struct: TupleStruct = TupleStruct(12, 345678));
 
-    #[derive(Debug, Serialize, Deserialize, PartialEq, SerializedSize)]
+    #[derive(Debug, Serialize, Deserialize, PartialEq, SerializedSize, HubpackSchema)]
     struct Struct {
         a: Option<u16>,
         b: i16,
@@ -127,7 +159,7 @@ mod tests {
 
     round_trip!(rt_struct: Struct = Struct { a: Some(0xF00D), b: -30 });
 
-    #[derive(Debug, Serialize, Deserialize, PartialEq, SerializedSize)]
+    #[derive(Debug, Serialize, Deserialize, PartialEq, SerializedSize, HubpackSchema, Packable)]
     enum Enum {
         Unit,
         Tuple(u8, u16),
@@ -141,10 +173,1112 @@ mod tests {
     round_trip!(rt_enum_tuple: Enum = Enum::Tuple(12, 3456));
     round_trip!(rt_enum_struct: Enum = Enum::Struct { a: Some(0xF00D), b: -12 });
 
+    #[test]
+    fn size_ranges() {
+        assert_eq!(<(u8, char)>::MIN_SIZE, 5);
+        const { assert!(<(u8, char)>::IS_FIXED_SIZE) };
+        assert_eq!(<Option<u32>>::MIN_SIZE, 1);
+        const { assert!(!<Option<u32>>::IS_FIXED_SIZE) };
+        assert_eq!(<core::result::Result<u8, u64>>::MIN_SIZE, 2);
+        assert_eq!(<[Option<u8>; 3]>::MIN_SIZE, 3);
+
+        assert_eq!(Struct::MIN_SIZE, 1 + 2);
+        assert_eq!(Struct::MAX_SIZE, 3 + 2);
+        const { assert!(TupleStruct::IS_FIXED_SIZE) };
+        const { assert!(UnitStruct::IS_FIXED_SIZE) };
+        // The smallest variant is `Unit`, which is just the index.
+        assert_eq!(Enum::MIN_SIZE, 1);
+        const { assert!(!Enum::IS_FIXED_SIZE) };
+        assert_eq!(<Borrowed<'static>>::MIN_SIZE, 1 + 1 + 1);
+
+        #[derive(SerializedSize)]
+        #[allow(dead_code)]
+        enum Same {
+            A(u32),
+            B(i16, i16),
+        }
+        assert_eq!(Same::MIN_SIZE, 5);
+        const { assert!(Same::IS_FIXED_SIZE) };
+    }
+
     #[test]
     fn whither_usize() {
         let mut buf = [0; 8];
         let n = ser::serialize(&mut buf, &0usize).unwrap();
         assert_eq!(n, 8);
     }
+
+    round_trip!(rt_usize: usize = 0xDEAD_BEEF);
+    round_trip!(rt_isize: isize = -0x4EAD_BEEF);
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq, SerializedSize)]
+    struct Seek {
+        #[serde(with = "hubpack::PointerSized")]
+        offset: usize,
+        #[serde(with = "hubpack::PointerSized")]
+        delta: isize,
+    }
+
+    round_trip!(rt_pointer_sized: Seek = Seek { offset: 0xDEAD_BEEF, delta: -0x4EAD_BEEF });
+
+    #[test]
+    fn pointer_sized_encoding() {
+        assert_eq!(Seek::MAX_SIZE, 16);
+
+        // The same as plain `usize` and `isize`.
+        let mut buf = [0; 16];
+        let n = serialize(&mut buf, &Seek { offset: 1, delta: -2 }).unwrap();
+        assert_eq!(&buf[..n], &[1, 0, 0, 0, 0, 0, 0, 0, 0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn pointer_sized_overflow() {
+        use serde::de::{Deserializer, IgnoredAny};
+
+        // What the decoder sees from `PointerSized` on a 32-bit target.
+        let narrow = |name, v: u64| {
+            let bytes = v.to_le_bytes();
+            let mut d = Decoder::new(&bytes);
+            (&mut d).deserialize_tuple_struct(name, 32, IgnoredAny).map(|_| ())
+        };
+        assert_eq!(narrow(pointer_sized::UNSIGNED, u32::MAX.into()), Ok(()));
+        assert_eq!(narrow(pointer_sized::UNSIGNED, 1 << 32), Err(Error::IntegerOverflow));
+        assert_eq!(narrow(pointer_sized::SIGNED, i32::MIN as u64), Ok(()));
+        assert_eq!(narrow(pointer_sized::SIGNED, i32::MAX as u64), Ok(()));
+        assert_eq!(
+            narrow(pointer_sized::SIGNED, (i64::from(i32::MIN) - 1) as u64),
+            Err(Error::IntegerOverflow),
+        );
+        assert_eq!(
+            narrow(pointer_sized::SIGNED, (i64::from(i32::MAX) + 1) as u64),
+            Err(Error::IntegerOverflow),
+        );
+    }
+
+    #[cfg(target_pointer_width = "32")]
+    #[test]
+    fn usize_overflow() {
+        let mut bytes = [0; 16];
+        bytes[..8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(deserialize::<Seek>(&bytes), Err(Error::IntegerOverflow));
+        // Without `PointerSized`, serde rejects it like any other bad value.
+        assert_eq!(deserialize::<usize>(&bytes), Err(Error::Invalid));
+    }
+
+    #[test]
+    fn rejected_integers() {
+        use core::num::NonZeroU32;
+
+        assert_eq!(deserialize::<NonZeroU32>(&[7, 0, 0, 0]), Ok((NonZeroU32::new(7).unwrap(), &[][..])));
+        // Zero is out of range for the type, not too large for it.
+        assert_eq!(deserialize::<NonZeroU32>(&[0, 0, 0, 0]), Err(Error::Invalid));
+    }
+
+    #[test]
+    fn rejected_bytes() {
+        // Bytes that a type rejects aren't necessarily meant to be UTF-8.
+        struct Magic;
+
+        impl<'de> serde::Deserialize<'de> for Magic {
+            fn deserialize<D: serde::Deserializer<'de>>(d: D) -> core::result::Result<Self, D::Error> {
+                struct V;
+
+                impl<'de> serde::de::Visitor<'de> for V {
+                    type Value = Magic;
+
+                    fn expecting(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                        f.write_str("the magic bytes")
+                    }
+
+                    fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> core::result::Result<Magic, E> {
+                        if v == b"HP" {
+                            Ok(Magic)
+                        } else {
+                            Err(E::invalid_value(serde::de::Unexpected::Bytes(v), &self))
+                        }
+                    }
+                }
+
+                d.deserialize_bytes(V)
+            }
+        }
+
+        #[derive(Deserialize)]
+        struct Header(#[serde(with = "crate::MaxLen::<2>")] Magic);
+
+        assert!(deserialize::<Header>(&[2, b'H', b'P']).is_ok());
+        assert_custom(deserialize::<Header>(&[2, 0xC3, 0x28]).map(|_| ()));
+    }
+
+    #[test]
+    fn bad_variant_index() {
+        assert_eq!(deserialize::<Enum>(&[3]), Err(Error::Invalid));
+    }
+
+    #[test]
+    fn encoder_decoder_sequence() {
+        let mut buf = [0; 7];
+        let mut e = Encoder::new(&mut buf);
+        e.encode(&0x1234u16).unwrap();
+        e.encode(&Some(0xAAu8)).unwrap();
+        assert_eq!(e.position(), 4);
+        assert_eq!(e.remaining(), 3);
+        // A failed encode leaves the position untouched.
+        assert_eq!(e.encode(&0u64), Err(Error::Overrun));
+        assert_eq!(e.position(), 4);
+        e.encode(&true).unwrap();
+        assert_eq!(e.finish(), &[0x34, 0x12, 1, 0xAA, 1]);
+
+        let mut d = Decoder::new(&buf);
+        assert_eq!(d.decode::<u16>(), Ok(0x1234));
+        assert_eq!(d.decode::<Option<u8>>(), Ok(Some(0xAA)));
+        assert_eq!(d.position(), 4);
+        // A failed decode leaves the position untouched.
+        assert_eq!(d.decode::<u32>(), Err(Error::Truncated));
+        assert_eq!(d.position(), 4);
+        assert_eq!(d.decode::<bool>(), Ok(true));
+        assert_eq!(d.position(), 5);
+        assert_eq!(d.remaining(), 2);
+        assert_eq!(d.finish(), &[0, 0]);
+    }
+
+    #[test]
+    fn char_encoding() {
+        let mut buf = [0; 4];
+        serialize(&mut buf, &'\u{1F980}').unwrap();
+        assert_eq!(buf, [0x80, 0xF9, 0x01, 0x00]);
+
+        // Surrogates are not valid chars.
+        assert_eq!(
+            deserialize::<char>(&0xD800u32.to_le_bytes()),
+            Err(Error::InvalidChar),
+        );
+        // Neither is anything past the end of Unicode.
+        assert_eq!(
+            deserialize::<char>(&0x11_0000u32.to_le_bytes()),
+            Err(Error::InvalidChar),
+        );
+    }
+
+    #[test]
+    fn big_endian() {
+        use config::BigEndian;
+
+        let input = (0x1234u16, 0xDEADBEEFu32, 'A', Some(-2i64), 1.0f32);
+        let mut buf = [0; 32];
+        let n = ser::serialize_with_config(&mut buf, &input, BigEndian).unwrap();
+        assert_eq!(&buf[..n], &[
+            0x12, 0x34,
+            0xDE, 0xAD, 0xBE, 0xEF,
+            0, 0, 0, b'A',
+            1, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFE,
+            0x3F, 0x80, 0x00, 0x00,
+        ]);
+        assert_eq!(n, <(u16, u32, char, Option<i64>, f32)>::MAX_SIZE);
+
+        let (output, _) = de::deserialize_with_config::<(u16, u32, char, Option<i64>, f32), _>(
+            &buf, BigEndian,
+        ).unwrap();
+        assert_eq!(output, input);
+
+        let mut d = Decoder::with_config(&[0, 2, b'h', b'i'], BigEndian);
+        assert_eq!(MaxLen::<300>::deserialize(&mut d), Ok("hi"));
+    }
+
+    #[cfg(feature = "heapless")]
+    mod heapless_tests {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq, SerializedSize)]
+        struct Message {
+            id: u8,
+            #[serde(with = "hubpack::MaxLen::<8>")]
+            readings: heapless::Vec<Option<i32>, 8>,
+        }
+
+        round_trip!(rt_heapless_vec_empty: Message = Message {
+            id: 0,
+            readings: heapless::Vec::new(),
+        });
+        round_trip!(rt_heapless_vec: Message = Message {
+            id: 3,
+            readings: heapless::Vec::from_slice(&[Some(-1), None, Some(7)]).unwrap(),
+        });
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq, SerializedSize, HubpackSchema)]
+        struct Words(#[serde(with = "hubpack::MaxLen::<4>")] heapless::Vec<u16, 4>);
+
+        #[test]
+        fn heapless_vec_encoding() {
+            assert_eq!(Words::MAX_SIZE, 1 + 4 * 2);
+
+            let v = Words(heapless::Vec::from_slice(&[0x1234, 0x5678]).unwrap());
+            let mut buf = [0; 16];
+            let n = serialize(&mut buf, &v).unwrap();
+            assert_eq!(&buf[..n], &[2, 0x34, 0x12, 0x78, 0x56]);
+
+            // More elements than the declared maximum.
+            assert_eq!(
+                deserialize::<Words>(&[5, 1, 0, 2, 0, 3, 0, 4, 0, 5, 0]),
+                Err(Error::Invalid),
+            );
+            // Fewer elements than the length claims.
+            assert_eq!(
+                deserialize::<Words>(&[3, 1, 0, 2, 0]),
+                Err(Error::Truncated),
+            );
+            // Without a declared maximum, sequences aren't supported.
+            assert_eq!(serialize(&mut buf, &v.0), Err(Error::NotSupported));
+            assert_eq!(
+                deserialize::<heapless::Vec<u16, 4>>(&[0, 0]),
+                Err(Error::NotSupported),
+            );
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq, HubpackSchema)]
+        struct BareWords {
+            id: u8,
+            words: heapless::Vec<u16, 4>,
+        }
+
+        #[test]
+        fn heapless_vec_needs_max_len() {
+            use schema::{Fields, Schema};
+
+            // The capacity doesn't count as a declared maximum, for the
+            // encoder or the schema.
+            let v = BareWords { id: 1, words: heapless::Vec::from_slice(&[2]).unwrap() };
+            let mut buf = [0; 16];
+            assert_eq!(serialize(&mut buf, &v), Err(Error::NotSupported));
+            assert_eq!(deserialize::<BareWords>(&[1, 1, 2, 0]), Err(Error::NotSupported));
+            #[cfg(feature = "alloc")]
+            assert_eq!(
+                dynamic::decode_dynamic(&BareWords::SCHEMA, &[1, 1, 2, 0]),
+                Err(Error::NotSupported),
+            );
+            let Schema::Struct { fields: Fields::Named([_, words]), .. } = BareWords::SCHEMA else {
+                panic!("not a struct");
+            };
+            assert_eq!(words.schema, Schema::Seq { max_len: None, element: &Schema::U16 });
+            let Schema::Struct { fields: Fields::Tuple([words]), .. } = Words::SCHEMA else {
+                panic!("not a tuple struct");
+            };
+            assert_eq!(*words, Schema::Seq { max_len: Some(4), element: &Schema::U16 });
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq, SerializedSize, HubpackSchema)]
+        struct Text(#[serde(with = "hubpack::MaxLen::<8>")] heapless::String<8>);
+
+        round_trip!(rt_heapless_string: Text = Text("héllo".try_into().unwrap()));
+
+        #[test]
+        fn heapless_string_encoding() {
+            assert_eq!(Text::MAX_SIZE, 1 + 8);
+
+            let s = Text("hi".try_into().unwrap());
+            let mut buf = [0; 16];
+            let n = serialize(&mut buf, &s).unwrap();
+            assert_eq!(&buf[..n], &[2, b'h', b'i']);
+
+            assert_eq!(
+                deserialize::<Text>(&[2, 0xC3, 0x28]),
+                Err(Error::InvalidUtf8),
+            );
+            assert_eq!(
+                deserialize::<Text>(&[9, b'a', b'b', b'c', b'd', b'e', b'f', b'g', b'h', b'i']),
+                Err(Error::Invalid),
+            );
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq, HubpackSchema)]
+        struct BareText {
+            id: u8,
+            text: heapless::String<8>,
+        }
+
+        #[test]
+        fn heapless_string_needs_max_len() {
+            use schema::{Fields, Schema};
+
+            // As for `heapless::Vec`, the capacity isn't a declared maximum.
+            let s = BareText { id: 1, text: "hi".try_into().unwrap() };
+            let mut buf = [0; 16];
+            assert_eq!(serialize(&mut buf, &s), Err(Error::NotSupported));
+            assert_eq!(deserialize::<BareText>(&[1, 2, b'h', b'i']), Err(Error::NotSupported));
+            #[cfg(feature = "alloc")]
+            assert_eq!(
+                dynamic::decode_dynamic(&BareText::SCHEMA, &[1, 2, b'h', b'i']),
+                Err(Error::NotSupported),
+            );
+            let Schema::Struct { fields: Fields::Named([_, text]), .. } = BareText::SCHEMA else {
+                panic!("not a struct");
+            };
+            assert_eq!(text.schema, Schema::Str { max_len: None });
+            let Schema::Struct { fields: Fields::Tuple([text]), .. } = Text::SCHEMA else {
+                panic!("not a tuple struct");
+            };
+            assert_eq!(*text, Schema::Str { max_len: Some(8) });
+        }
+    }
+
+    round_trip!(rt_fixed_str: FixedStr<8> = "hello".parse().unwrap());
+    round_trip!(rt_fixed_str_full: FixedStr<5> = "hello".parse().unwrap());
+    round_trip!(rt_fixed_str_empty: FixedStr<4> = FixedStr::new());
+
+    #[test]
+    fn fixed_str_encoding() {
+        let s: FixedStr<6> = "abc".parse().unwrap();
+        assert_eq!(s, "abc");
+        assert_eq!(s.len(), 3);
+        let mut buf = [0xFF; 6];
+        assert_eq!(serialize(&mut buf, &s), Ok(6));
+        assert_eq!(buf, *b"abc\0\0\0");
+
+        // Garbage after the NUL is ignored, and cleared.
+        let (s, _) = deserialize::<FixedStr<6>>(b"ab\0xyz").unwrap();
+        assert_eq!(s, "ab");
+        assert_eq!(s.as_padded_bytes(), b"ab\0\0\0\0");
+
+        assert_eq!(
+            deserialize::<FixedStr<2>>(&[0xC3, 0x28]),
+            Err(Error::InvalidUtf8),
+        );
+        assert_eq!(
+            deserialize::<FixedStr<4>>(b"abc"),
+            Err(Error::Truncated),
+        );
+
+        assert_eq!("hello".parse::<FixedStr<4>>(), Err(Error::TooLong));
+        assert_eq!("a\0b".parse::<FixedStr<4>>(), Err(Error::Invalid));
+    }
+
+    #[cfg(feature = "alloc")]
+    mod alloc_tests {
+        use super::*;
+        use alloc::borrow::Cow;
+        use alloc::boxed::Box;
+        use alloc::string::String;
+        use alloc::vec;
+        use alloc::vec::Vec;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq, SerializedSize)]
+        struct Owned {
+            id: Box<u16>,
+            #[serde(with = "hubpack::MaxLen::<4>")]
+            samples: Vec<i32>,
+            #[serde(with = "hubpack::MaxLen::<16>")]
+            name: String,
+            #[serde(with = "hubpack::MaxLen::<3>")]
+            tags: Box<[u8]>,
+        }
+
+        #[test]
+        fn rt_owned() {
+            assert_eq!(Owned::MAX_SIZE, 2 + (1 + 4 * 4) + (1 + 16) + (1 + 3));
+
+            let input = Owned {
+                id: Box::new(0xBEEF),
+                samples: vec![-1, 2],
+                name: "widget".into(),
+                tags: vec![1, 2, 3].into_boxed_slice(),
+            };
+            let bytes = to_vec(&input).unwrap();
+            assert_eq!(bytes.len(), 2 + 9 + 7 + 4);
+            let (output, rest) = deserialize::<Owned>(&bytes).unwrap();
+            assert_eq!(output, input);
+            assert!(rest.is_empty());
+        }
+
+        #[test]
+        fn owned_max_len() {
+            let long = Owned {
+                id: Box::new(1),
+                samples: vec![1, 2, 3, 4, 5],
+                name: "widget".into(),
+                tags: Box::new([]),
+            };
+            assert_eq!(to_vec(&long), Err(Error::TooLong));
+            let long = Owned {
+                samples: vec![],
+                name: "a name longer than sixteen bytes".into(),
+                ..long
+            };
+            assert_eq!(to_vec(&long), Err(Error::TooLong));
+
+            // Five samples, over the maximum of four.
+            let mut bytes = vec![1, 0, 5];
+            bytes.extend([0; 5 * 4]);
+            bytes.extend([0, 0]);
+            assert_eq!(deserialize::<Owned>(&bytes), Err(Error::Invalid));
+            // A name of seventeen bytes.
+            let mut bytes = vec![1, 0, 0, 17];
+            bytes.extend([b'a'; 17]);
+            bytes.push(0);
+            assert_eq!(deserialize::<Owned>(&bytes), Err(Error::Invalid));
+        }
+
+        #[test]
+        fn smart_pointer_sizes() {
+            assert_eq!(<Box<u32>>::MAX_SIZE, 4);
+            assert_eq!(<alloc::rc::Rc<u32>>::MAX_SIZE, 4);
+            assert_eq!(<alloc::sync::Arc<u32>>::MAX_SIZE, 4);
+            assert_eq!(<Cow<'static, u32>>::MAX_SIZE, 4);
+        }
+
+        #[test]
+        fn dynamic_round_trip() {
+            use crate::dynamic::{decode_dynamic, encode_dynamic, Value};
+            use crate::schema::HubpackSchema;
+            use alloc::string::ToString;
+
+            fn check<T: Serialize + HubpackSchema>(input: &T, expected: Value) {
+                let mut buf = [0; 64];
+                let n = serialize(&mut buf, input).unwrap();
+                let (value, rest) = decode_dynamic(&T::SCHEMA, &buf[..n + 1]).unwrap();
+                assert_eq!(value, expected);
+                assert_eq!(rest.len(), 1);
+
+                let mut out = [0; 64];
+                let m = encode_dynamic(&mut out, &T::SCHEMA, &value).unwrap();
+                assert_eq!(&out[..m], &buf[..n]);
+            }
+
+            check(&Enum::Tuple(1, 2), Value::Variant {
+                index: 1,
+                name: "Tuple".to_string(),
+                fields: Box::new(Value::List(alloc::vec![Value::U8(1), Value::U16(2)])),
+            });
+            check(&Borrowed { a: 1, bytes: &[2], text: "é" }, Value::Record(alloc::vec![
+                ("a".to_string(), Value::U8(1)),
+                ("bytes".to_string(), Value::List(alloc::vec![Value::U8(2)])),
+                ("text".to_string(), Value::Str("é".to_string())),
+            ]));
+            check(
+                &(None::<u8>, FixedStr::<4>::try_from("hi").unwrap(), 'x', -1.5f64),
+                Value::List(alloc::vec![
+                    Value::Option(None),
+                    Value::Str("hi".to_string()),
+                    Value::Char('x'),
+                    Value::F64(-1.5),
+                ]),
+            );
+        }
+
+        #[test]
+        fn dynamic_errors() {
+            use crate::dynamic::{decode_dynamic, encode_dynamic, Value};
+            use crate::schema::HubpackSchema;
+            use alloc::string::ToString;
+
+            // The same checks as the typed decoder.
+            assert_eq!(decode_dynamic(&bool::SCHEMA, &[2]), Err(Error::Invalid));
+            assert_eq!(decode_dynamic(&Enum::SCHEMA, &[3]), Err(Error::Invalid));
+            assert_eq!(decode_dynamic(&u32::SCHEMA, &[1, 2]), Err(Error::Truncated));
+            let text = <&str>::SCHEMA.with_max_len(4);
+            assert_eq!(decode_dynamic(&text, &[1, 0xFF]), Err(Error::InvalidUtf8));
+            assert_eq!(decode_dynamic(&<&str>::SCHEMA, &[0, 0]), Err(Error::NotSupported));
+            // Lengths over the maximum, in both directions.
+            assert_eq!(decode_dynamic(&text, &[5, b'a', b'b', b'c', b'd', b'e']), Err(Error::Invalid));
+            let bytes = <&[u8]>::SCHEMA.with_max_len(2);
+            assert_eq!(decode_dynamic(&bytes, &[3, 1, 2, 3]), Err(Error::Invalid));
+            let mut out = [0; 16];
+            let long = Value::Str("abcde".to_string());
+            assert_eq!(encode_dynamic(&mut out, &text, &long), Err(Error::Invalid));
+            let long = Value::List(alloc::vec![Value::U8(1); 3]);
+            assert_eq!(encode_dynamic(&mut out, &bytes, &long), Err(Error::Invalid));
+            let fixed = FixedStr::<4>::SCHEMA;
+            let long = Value::Str("abcde".to_string());
+            assert_eq!(encode_dynamic(&mut out, &fixed, &long), Err(Error::Invalid));
+
+            // Values that don't match the schema.
+            let mut buf = [0; 16];
+            assert_eq!(encode_dynamic(&mut buf, &u16::SCHEMA, &Value::U8(1)), Err(Error::Invalid));
+            let missing = Value::Record(alloc::vec![
+                ("a".to_string(), Value::Option(None)),
+                ("c".to_string(), Value::I16(0)),
+            ]);
+            assert_eq!(encode_dynamic(&mut buf, &Struct::SCHEMA, &missing), Err(Error::Invalid));
+            // Named fields can come in any order.
+            let swapped = Value::Record(alloc::vec![
+                ("b".to_string(), Value::I16(-1)),
+                ("a".to_string(), Value::Option(None)),
+            ]);
+            assert_eq!(encode_dynamic(&mut buf, &Struct::SCHEMA, &swapped), Ok(3));
+            assert_eq!(&buf[..3], &[0, 0xFF, 0xFF]);
+        }
+
+        #[test]
+        fn owned_schemas() {
+            use crate::schema::{HubpackSchema, Schema};
+
+            assert_eq!(<Box<u32>>::SCHEMA, Schema::U32);
+            assert_eq!(<Box<[u8]>>::SCHEMA, <&[u8]>::SCHEMA);
+            assert_eq!(<Cow<'static, str>>::SCHEMA, Schema::Str { max_len: None });
+            assert_eq!(
+                <Vec<i8>>::SCHEMA,
+                Schema::Seq { max_len: None, element: &Schema::I8 },
+            );
+        }
+    }
+
+    #[cfg(feature = "error-context")]
+    mod context_tests {
+        use super::*;
+        use crate::error::DetailedError;
+
+        extern crate std;
+        use std::format;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Outer {
+            header: u16,
+            body: Enum,
+        }
+
+        #[test]
+        fn overrun_context() {
+            let value = Outer {
+                header: 1,
+                body: Enum::Struct { a: Some(2), b: 3 },
+            };
+            let mut buf = [0; 6];
+            let err = ser::serialize_with_context(&mut buf, &value).unwrap_err();
+            assert_eq!(err.error, Error::Overrun);
+            assert_eq!(err.context.offset, 6);
+            assert_eq!(err.context.needed, Some(2));
+            assert_eq!(err.context.path.names(), &["body", "Struct", "b"]);
+            assert_eq!(
+                format!("{}", err),
+                "serialization buffer too small at offset 6 (needed 2 bytes) in body.Struct.b",
+            );
+
+            let mut buf = [0; 8];
+            assert_eq!(ser::serialize_with_context(&mut buf, &value), Ok(8));
+        }
+
+        #[test]
+        fn decode_context() {
+            // Truncated in the middle of a field.
+            let err: DetailedError = de::deserialize_with_context::<Outer>(&[1, 0, 2, 1])
+                .unwrap_err();
+            assert_eq!(err.error, Error::Truncated);
+            assert_eq!(err.context.offset, 4);
+            assert_eq!(err.context.needed, Some(2));
+            assert_eq!(err.context.path.names(), &["body", "Struct", "a"]);
+
+            // Corrupt option tag.
+            let err = de::deserialize_with_context::<Outer>(&[1, 0, 2, 9, 0, 0, 0, 0])
+                .unwrap_err();
+            assert_eq!(err.error, Error::Invalid);
+            assert_eq!(err.context.offset, 3);
+            assert_eq!(err.context.needed, None);
+
+            // Bad variant index; we never got into the variant.
+            let err = de::deserialize_with_context::<Outer>(&[1, 0, 7])
+                .unwrap_err();
+            assert_eq!(err.error, Error::Invalid);
+            assert_eq!(err.context.offset, 2);
+            assert_eq!(err.context.path.names(), &["body"]);
+        }
+
+        #[test]
+        fn deep_paths_are_truncated() {
+            #[derive(Serialize)]
+            struct Nest<T> { n: T }
+
+            let value = Nest { n: Nest { n: Nest { n: Nest { n: Nest {
+                n: Nest { n: Nest { n: Nest { n: Nest { n: 0u32 } } } },
+            } } } } };
+            let mut buf = [0; 2];
+            let err = ser::serialize_with_context(&mut buf, &value).unwrap_err();
+            assert_eq!(err.context.path.depth(), 9);
+            assert!(err.context.path.is_truncated());
+            assert_eq!(format!("{}", err.context.path), "n.n.n.n.n.n.n.n...");
+        }
+    }
+
+    #[test]
+    fn exact() {
+        assert_eq!(de::deserialize_exact::<u16>(&[1, 2]), Ok(0x0201));
+        assert_eq!(
+            de::deserialize_exact::<u16>(&[1, 2, 3, 4, 5]),
+            Err(Error::TrailingBytes { count: 3 }),
+        );
+        assert_eq!(de::deserialize_exact::<u16>(&[1]), Err(Error::Truncated));
+        assert_eq!(de::deserialize_exact::<Borrowed>(&[1, 0, 2, b'o', b'k']).unwrap().text, "ok");
+
+        let mut d = Decoder::new(&[1, 2, 3]);
+        assert_eq!(d.decode::<u8>(), Ok(1));
+        assert_eq!(d.decode::<u8>(), Ok(2));
+        assert_eq!(d.finish_exact(), Err(Error::TrailingBytes { count: 1 }));
+    }
+
+    #[test]
+    fn sizes_without_buffers() {
+        let hello = Borrowed { a: 0, bytes: &[], text: "hello" };
+        assert_eq!(serialized_size(&hello), Ok(8));
+        assert_eq!(serialized_size(&(1u8, Some(2u32), [0u16; 3])), Ok(12));
+        assert_eq!(serialized_size(&None::<u64>), Ok(1));
+
+        // The same failures as `serialize`, other than running out of room.
+        let big = Borrowed { a: 0, bytes: &[0; 5], text: "" };
+        assert_eq!(serialized_size(&big), Err(Error::TooLong));
+
+        let mut c = ser::SizeCounter::new();
+        c.encode(&0u32).unwrap();
+        assert!(c.encode(&big).is_err());
+        c.encode(&'x').unwrap();
+        assert_eq!(c.position(), 8);
+    }
+
+    /// A type whose `Deserialize` impl does its own validation.
+    #[derive(Debug, PartialEq)]
+    struct SensorId(u8);
+
+    impl<'de> Deserialize<'de> for SensorId {
+        fn deserialize<D: serde::Deserializer<'de>>(d: D) -> core::result::Result<Self, D::Error> {
+            let id = u8::deserialize(d)?;
+            if id < 16 {
+                Ok(SensorId(id))
+            } else {
+                Err(serde::de::Error::custom(format_args!("sensor id {} out of range", id)))
+            }
+        }
+    }
+
+    /// Checks that `r` failed with `Error::Custom`, which holds a message with
+    /// the `custom-message` feature.
+    fn assert_custom<T: core::fmt::Debug>(r: Result<T>) {
+        #[cfg(not(feature = "custom-message"))]
+        assert!(matches!(r, Err(Error::Custom)), "{r:?}");
+        #[cfg(feature = "custom-message")]
+        assert!(matches!(r, Err(Error::Custom(_))), "{r:?}");
+    }
+
+    #[test]
+    fn custom_errors() {
+        assert_custom(deserialize::<SensorId>(&[20]));
+
+        // `Error` is also usable as serde's error type directly.
+        assert_custom::<()>(Err(<Error as serde::ser::Error>::custom("nope")));
+        assert_custom::<()>(Err(<Error as serde::de::Error>::custom("nope")));
+    }
+
+    #[cfg(feature = "custom-message")]
+    #[test]
+    fn custom_messages() {
+        extern crate std;
+
+        let err = deserialize::<SensorId>(&[20]).unwrap_err();
+        assert_eq!(err.custom_message(), Some("sensor id 20 out of range"));
+        assert_eq!(std::format!("{}", err), "Custom: sensor id 20 out of range");
+        assert_eq!(Error::Truncated.custom_message(), None);
+
+        #[cfg(feature = "error-context")]
+        {
+            let err = de::deserialize_with_context::<SensorId>(&[20]).unwrap_err();
+            assert_eq!(err.error.custom_message(), Some("sensor id 20 out of range"));
+            assert_eq!(
+                std::format!("{}", err),
+                "Custom: sensor id 20 out of range at offset 0",
+            );
+        }
+    }
+
+    #[cfg(feature = "custom-message")]
+    #[test]
+    fn long_custom_messages_are_cut_short() {
+        use crate::error::{CustomMessage, CUSTOM_MESSAGE_LEN};
+
+        extern crate std;
+
+        // Each 'é' is two bytes, so the message can't be cut exactly at the
+        // limit without splitting one.
+        let m = CustomMessage::new(format_args!("x{}", "é".repeat(40)));
+        let s = m.as_str();
+        assert_eq!(s.len(), CUSTOM_MESSAGE_LEN - 1);
+        assert!(s.starts_with("xéé"));
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq, SerializedSize, HubpackSchema, Packable)]
+    struct Borrowed<'a> {
+        a: u8,
+        #[serde(with = "hubpack::MaxLen::<4>")]
+        bytes: &'a [u8],
+        #[serde(with = "hubpack::MaxLen::<{ 2 * 4 }>")]
+        text: &'a str,
+    }
+
+    #[test]
+    fn rt_borrowed() {
+        assert_eq!(<Borrowed<'static>>::MAX_SIZE, 1 + 1 + 4 + 1 + 8);
+
+        let input = Borrowed { a: 7, bytes: &[1, 2, 3], text: "hi" };
+        let mut buf = [0; <Borrowed<'static>>::MAX_SIZE];
+        let n = serialize(&mut buf, &input).unwrap();
+        assert_eq!(&buf[..n], &[7, 3, 1, 2, 3, 2, b'h', b'i']);
+
+        let (output, rest) = de::deserialize_borrowed::<Borrowed>(&buf).unwrap();
+        assert_eq!(output, input);
+        assert_eq!(rest.len(), buf.len() - n);
+        // The decoded slices should point into the buffer, not be copies.
+        assert!(buf.as_ptr_range().contains(&output.bytes.as_ptr()));
+        assert!(buf.as_ptr_range().contains(&output.text.as_ptr()));
+    }
+
+    #[test]
+    fn max_len_enforced() {
+        let mut buf = [0; 32];
+        // Longer than declared, in either direction.
+        let long = Borrowed { a: 7, bytes: &[1, 2, 3, 4, 5], text: "hi" };
+        assert_eq!(serialize(&mut buf, &long), Err(Error::TooLong));
+        let long = Borrowed { a: 7, bytes: &[], text: "ninebytes" };
+        assert_eq!(serialize(&mut buf, &long), Err(Error::TooLong));
+        assert_eq!(
+            de::deserialize_borrowed::<Borrowed>(&[7, 5, 1, 2, 3, 4, 5, 0]),
+            Err(Error::Invalid),
+        );
+        assert_eq!(
+            de::deserialize_borrowed::<Borrowed>(&[7, 0, 9, b'n', b'i', b'n', b'e', b'b', b'y', b't', b'e', b's']),
+            Err(Error::Invalid),
+        );
+        // Exactly the maximum is fine.
+        let full = Borrowed { a: 7, bytes: &[1, 2, 3, 4], text: "eightbyt" };
+        let n = serialize(&mut buf, &full).unwrap();
+        assert_eq!(n, <Borrowed<'static>>::MAX_SIZE);
+        assert_eq!(de::deserialize_borrowed::<Borrowed>(&buf[..n]), Ok((full, &[][..])));
+
+        // Without a declared maximum, there's no way to bound the size, so
+        // slices and strings aren't supported at all.
+        assert_eq!(serialize(&mut buf, &"hello"), Err(Error::NotSupported));
+        assert_eq!(de::deserialize_borrowed::<&str>(&[0, 0]), Err(Error::NotSupported));
+        assert_eq!(de::deserialize_borrowed::<&[u8]>(&[0, 0]), Err(Error::NotSupported));
+    }
+
+    #[test]
+    fn schemas() {
+        use schema::{Field, Fields, Schema, Variant};
+
+        assert_eq!(UnitStruct::SCHEMA, Schema::Struct { name: "UnitStruct", fields: Fields::Unit });
+        assert_eq!(
+            TupleStruct::SCHEMA,
+            Schema::Struct { name: "TupleStruct", fields: Fields::Tuple(&[Schema::U8, Schema::U32]) },
+        );
+        const STRUCT_FIELDS: Fields = Fields::Named(&[
+            Field { name: "a", schema: Schema::Option(&Schema::U16) },
+            Field { name: "b", schema: Schema::I16 },
+        ]);
+        assert_eq!(Struct::SCHEMA, Schema::Struct { name: "Struct", fields: STRUCT_FIELDS });
+        assert_eq!(Enum::SCHEMA, Schema::Enum {
+            name: "Enum",
+            variants: &[
+                Variant { name: "Unit", index: 0, fields: Fields::Unit },
+                Variant { name: "Tuple", index: 1, fields: Fields::Tuple(&[Schema::U8, Schema::U16]) },
+                Variant { name: "Struct", index: 2, fields: STRUCT_FIELDS },
+            ],
+        });
+        assert_eq!(<Borrowed<'static>>::SCHEMA, Schema::Struct {
+            name: "Borrowed",
+            fields: Fields::Named(&[
+                Field { name: "a", schema: Schema::U8 },
+                Field { name: "bytes", schema: Schema::Seq { max_len: Some(4), element: &Schema::U8 } },
+                Field { name: "text", schema: Schema::Str { max_len: Some(8) } },
+            ]),
+        });
+
+        assert_eq!(<[usize; 2]>::SCHEMA, Schema::Array { len: 2, element: &Schema::U64 });
+        assert_eq!(<(char, FixedStr<3>)>::SCHEMA, Schema::Tuple(&[Schema::Char, Schema::FixedStr { len: 3 }]));
+        let Schema::Enum { name: "Result", variants } = <core::result::Result<u8, ()>>::SCHEMA else {
+            panic!();
+        };
+        assert_eq!(variants[1].fields, Fields::Tuple(&[Schema::Unit]));
+    }
+
+    #[test]
+    fn schema_hashes() {
+        // These are part of the protocol, and mustn't change by accident.
+        assert_eq!(u8::SCHEMA_HASH, 0xaf63_bf4c_8601_bb45);
+        assert_eq!(Struct::SCHEMA_HASH, 0xf9c8_5406_6524_886e);
+
+        #[derive(HubpackSchema)]
+        #[allow(dead_code)]
+        struct Renamed {
+            x: Option<u16>,
+            y: i16,
+        }
+        #[derive(HubpackSchema)]
+        #[hubpack(hash_without_names)]
+        #[allow(dead_code)]
+        struct Anonymous(Option<u16>, i16);
+        #[derive(HubpackSchema)]
+        #[allow(dead_code)]
+        struct Swapped {
+            b: i16,
+            a: Option<u16>,
+        }
+        #[derive(HubpackSchema)]
+        #[allow(dead_code)]
+        enum Extended {
+            Unit,
+            Tuple(u8, u16),
+            Struct { a: Option<u16>, b: i16 },
+            Another,
+        }
+
+        assert_ne!(Renamed::SCHEMA_HASH, Struct::SCHEMA_HASH);
+        assert_eq!(Renamed::SCHEMA.hash_without_names(), Struct::SCHEMA.hash_without_names());
+        assert_eq!(Anonymous::SCHEMA_HASH, Struct::SCHEMA.hash_without_names());
+        assert_ne!(Swapped::SCHEMA.hash_without_names(), Struct::SCHEMA.hash_without_names());
+        assert_ne!(Extended::SCHEMA_HASH, Enum::SCHEMA_HASH);
+        assert_ne!(<[u8; 2]>::SCHEMA_HASH, <(u8, u8)>::SCHEMA_HASH);
+        assert_ne!(<&[u8]>::SCHEMA_HASH, <&str>::SCHEMA_HASH);
+    }
+
+    #[test]
+    fn packed() {
+        // Generic code can make buffers without knowing the size.
+        fn pack<T: Packable + Serialize>(v: &T) -> Packed<T> {
+            Packed::encode(v).unwrap()
+        }
+
+        let p = pack(&Enum::Tuple(1, 0x0302));
+        assert_eq!(p.as_bytes(), &[1, 1, 2, 3]);
+        assert_eq!(p.decode(), Ok(Enum::Tuple(1, 0x0302)));
+
+        let p = pack(&Enum::Unit);
+        assert_eq!(p.len(), 1);
+
+        let p = pack(&Borrowed { a: 7, bytes: &[1, 2], text: "hi" });
+        assert_eq!(p.as_bytes(), &[7, 2, 1, 2, 2, b'h', b'i']);
+        assert_eq!(p.decode().unwrap().text, "hi");
+
+        let p = pack(&FixedStr::<4>::try_from("ab").unwrap());
+        assert_eq!(p.as_bytes(), b"ab\0\0");
+        assert!(pack(&()).is_empty());
+
+        let p: Packed<Option<u32>> = pack(&Some(0x0403_0201));
+        assert_eq!(p.as_bytes(), &[1, 1, 2, 3, 4]);
+        assert_eq!(p.decode(), Ok(Some(0x0403_0201)));
+        assert_eq!(pack(&None::<u32>).as_bytes(), &[0]);
+
+        let p = pack(&[Enum::Unit, Enum::Tuple(1, 2)]);
+        assert_eq!(p.as_bytes(), &[0, 1, 1, 2, 0]);
+        assert_eq!(p.decode(), Ok([Enum::Unit, Enum::Tuple(1, 2)]));
+        assert_eq!(pack(&[1u16, 2, 3]).len(), 6);
+    }
+
+    #[test]
+    fn annotated() {
+        use crate::fmt::Annotated;
+
+        extern crate std;
+        use std::format;
+
+        let data = [2, 1, 0x34, 0x12, 0xFE, 0xFF, 9];
+        assert_eq!(format!("{}", Annotated::new(&Enum::SCHEMA, &data)), "\
+0000..0001  02                          Enum::Struct
+0001..0002  01                            a: Some
+0002..0004  34 12                           4660
+0004..0006  fe ff                         b: -2
+0006..0007  09                          (1 bytes left over)
+");
+        let data = [0x0A, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+        let bytes = <&[u8]>::SCHEMA.with_max_len(300);
+        assert_eq!(format!("{}", Annotated::new(&bytes, &data)), "\
+0000..0002  0a 00                       [10 items]
+0002..0003  00                            0: 0
+0003..0004  01                            1: 1
+0004..0005  02                            2: 2
+0005..0006  03                            3: 3
+0006..0007  04                            4: 4
+0007..0008  05                            5: 5
+0008..0009  06                            6: 6
+0009..000a  07                            7: 7
+000a..000b  08                            8: 8
+000b..000c  09                            9: 9
+");
+        // Decoding stops at the first problem, after showing everything
+        // before it.
+        let data = [1, 1, 2];
+        assert_eq!(format!("{}", Annotated::new(&Enum::SCHEMA, &data)), "\
+0000..0001  01                          Enum::Tuple
+0001..0002  01                            0: 1
+0002..0003  02                          error: truncated
+");
+        #[derive(HubpackSchema)]
+        #[allow(dead_code)]
+        struct Outer {
+            x: u32,
+            y: u16,
+            inner: Inner,
+        }
+        #[derive(HubpackSchema)]
+        #[allow(dead_code)]
+        struct Inner {
+            a: u8,
+            b: bool,
+        }
+        let data = [1, 0, 0, 0, 2, 0, 3, 4];
+        assert_eq!(format!("{}", Annotated::new(&Outer::SCHEMA, &data)), "\
+0000..0000                              Outer
+0000..0004  01 00 00 00                   x: 1
+0004..0006  02 00                         y: 2
+0006..0006                                inner: Inner
+0006..0007  03                              a: 3
+0007..0008  04                          error: invalid/corrupt
+");
+        let data = [2, 0];
+        assert_eq!(format!("{}", Annotated::new(&Option::<u8>::SCHEMA, &data)), "\
+0000..0002  02 00                       error: invalid/corrupt
+");
+        // Long field names are written out in full.
+        #[derive(HubpackSchema)]
+        #[allow(dead_code)]
+        struct Long {
+            a_field_name_much_longer_than_most: u8,
+        }
+        assert_eq!(format!("{}", Annotated::new(&Long::SCHEMA, &[7])), "\
+0000..0000                              Long
+0000..0001  07                            a_field_name_much_longer_than_most: 7
+");
+        let data = [1, 0x12, 0x34];
+        let dump = Annotated::with_config(&Option::<u16>::SCHEMA, &data, crate::config::BigEndian);
+        assert!(format!("{dump}").ends_with("4660\n"));
+    }
+
+    #[test]
+    fn compat() {
+        use crate::compat::{check_compat, Change, Compatibility};
+        use crate::schema::{Field, Fields, Schema, Variant};
+
+        extern crate std;
+        use std::string::ToString;
+
+        const fn message(fields: &'static [Field]) -> Schema {
+            Schema::Struct { name: "Message", fields: Fields::Named(fields) }
+        }
+        const V1: Schema = message(&[
+            Field { name: "id", schema: Schema::U16 },
+            Field { name: "body", schema: Enum::SCHEMA },
+        ]);
+
+        assert!(check_compat(&V1, &V1).is_compatible());
+        assert!(check_compat(&Struct::SCHEMA, &Struct::SCHEMA).is_compatible());
+
+        // Renaming fields is fine.
+        let renamed = message(&[
+            Field { name: "ident", schema: Schema::U16 },
+            Field { name: "body", schema: Enum::SCHEMA },
+        ]);
+        assert!(check_compat(&V1, &renamed).is_compatible());
+
+        // Widening a field is not.
+        let widened = message(&[
+            Field { name: "id", schema: Schema::U32 },
+            Field { name: "body", schema: Enum::SCHEMA },
+        ]);
+        let report = check_compat(&V1, &widened);
+        assert!(report.is_breaking());
+        let d = report.difference.unwrap();
+        assert_eq!(d.path.names(), ["id"]);
+        assert_eq!(d.change, Change::TypeChanged { old: Schema::U16, new: Schema::U32 });
+
+        // Nor is reordering fields, even of the same type.
+        let reordered = message(&[
+            Field { name: "body", schema: Enum::SCHEMA },
+            Field { name: "id", schema: Schema::U16 },
+        ]);
+        let report = check_compat(&V1, &reordered);
+        assert!(report.is_breaking());
+        assert_eq!(
+            report.difference.unwrap().change,
+            Change::FieldMoved { name: "body", old: 1, new: 0 },
+        );
+
+        // Appending a variant is forward-compatible, but removing or
+        // renumbering one is breaking.
+        const VARIANTS: &[Variant] = match Enum::SCHEMA {
+            Schema::Enum { variants, .. } => variants,
+            _ => unreachable!(),
+        };
+        const fn unit(name: &'static str, index: u8) -> Variant {
+            Variant { name, index, fields: Fields::Unit }
+        }
+        const APPENDED: Schema = Schema::Enum {
+            name: "Enum",
+            variants: &[VARIANTS[0], VARIANTS[1], VARIANTS[2], unit("Extra", 3)],
+        };
+        const INSERTED: Schema = Schema::Enum {
+            name: "Enum",
+            variants: &[VARIANTS[0], unit("Extra", 1), VARIANTS[1], VARIANTS[2]],
+        };
+        let report = check_compat(&Enum::SCHEMA, &APPENDED);
+        assert_eq!(report.compatibility, Compatibility::ForwardCompatible);
+        assert_eq!(report.to_string(), "forward-compatible: variant Extra added at index 3");
+        assert!(check_compat(&APPENDED, &Enum::SCHEMA).is_breaking());
+        assert!(check_compat(&Enum::SCHEMA, &INSERTED).is_breaking());
+
+        // Changes inside variants are found too.
+        const CHANGED: Schema = Schema::Enum {
+            name: "Enum",
+            variants: &[VARIANTS[0], VARIANTS[1], Variant {
+                fields: Fields::Named(&[
+                    Field { name: "a", schema: Schema::Option(&Schema::U16) },
+                    Field { name: "b", schema: Schema::I32 },
+                ]),
+                ..VARIANTS[2]
+            }],
+        };
+        let report = check_compat(&Enum::SCHEMA, &CHANGED);
+        assert_eq!(report.to_string(), "breaking: Struct.b: type changed from i16 to i32");
+
+        // Raising a maximum length is forward-compatible, unless the length
+        // prefix gets bigger.
+        let short = Schema::Str { max_len: Some(8) };
+        let long = Schema::Str { max_len: Some(16) };
+        assert_eq!(check_compat(&short, &long).compatibility, Compatibility::ForwardCompatible);
+        assert!(check_compat(&long, &short).is_breaking());
+        let longer = Schema::Str { max_len: Some(256) };
+        assert!(check_compat(&long, &longer).is_breaking());
+        assert_eq!(
+            check_compat(&<[u8; 4]>::SCHEMA, &<[u8; 5]>::SCHEMA).difference.unwrap().change,
+            Change::LengthChanged { old: 4, new: 5 },
+        );
+
+        // Types that encode as nothing are interchangeable, but not with
+        // anything else.
+        #[derive(HubpackSchema)]
+        struct Empty {}
+        assert!(check_compat(&<()>::SCHEMA, &UnitStruct::SCHEMA).is_compatible());
+        assert!(check_compat(&Empty::SCHEMA, &<()>::SCHEMA).is_compatible());
+        assert!(check_compat(&<[u8; 0]>::SCHEMA, &<((), ())>::SCHEMA).is_compatible());
+        assert!(check_compat(&<()>::SCHEMA, &u8::SCHEMA).is_breaking());
+        assert!(check_compat(&UnitStruct::SCHEMA, &TupleStruct::SCHEMA).is_breaking());
+    }
+
+    #[test]
+    fn borrowed_errors() {
+        // Length prefix runs past the end of the data.
+        assert_eq!(
+            de::deserialize_borrowed::<Borrowed>(&[0, 3, 1, 2]),
+            Err(Error::Truncated),
+        );
+        // Not UTF-8.
+        assert_eq!(
+            de::deserialize_borrowed::<Borrowed>(&[0, 0, 1, 0xFF]),
+            Err(Error::InvalidUtf8),
+        );
+        // The length prefix is as small as the declared maximum allows.
+        #[derive(Serialize, SerializedSize)]
+        struct Short<'a>(#[serde(with = "hubpack::MaxLen::<255>")] &'a [u8]);
+        #[derive(Serialize, SerializedSize)]
+        struct Long<'a>(#[serde(with = "hubpack::MaxLen::<256>")] &'a [u8]);
+        assert_eq!(<Short<'static>>::MAX_SIZE, 1 + 255);
+        assert_eq!(<Long<'static>>::MAX_SIZE, 2 + 256);
+        let mut buf = [0; 4];
+        assert_eq!(serialize(&mut buf, &Short(&[9])), Ok(2));
+        assert_eq!(buf[..2], [1, 9]);
+        assert_eq!(serialize(&mut buf, &Long(&[9])), Ok(3));
+        assert_eq!(buf[..3], [1, 0, 9]);
+    }
 }
@@ -0,0 +1,108 @@
+//! Declaring the maximum length of slices, strings, and sequences.
+//!
+//! `hubpack` only encodes variable-length values, like `&str`, `&[u8]`, `Vec`,
+//! and the `heapless` collections, when their maximum length is declared,
+//! since otherwise their encoded size couldn't be predicted. The declaration
+//! is a `serde` attribute on the field, naming [`MaxLen`]:
+//!
+//! ```
+//! use hubpack::SerializedSize;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize, SerializedSize)]
+//! struct Message<'a> {
+//!     #[serde(with = "hubpack::MaxLen::<32>")]
+//!     name: &'a str,
+//! }
+//!
+//! assert_eq!(Message::MAX_SIZE, 1 + 32);
+//!
+//! let mut buf = [0; Message::MAX_SIZE];
+//! let long = Message { name: "far, far too long for this message, really" };
+//! assert_eq!(hubpack::serialize(&mut buf, &long), Err(hubpack::Error::TooLong));
+//! ```
+//!
+//! The value is encoded as a length prefix, followed by its elements (or, for
+//! strings, its UTF-8 bytes). The prefix is a `u8` if `N` is at most 255, and
+//! a `u16` otherwise; `N` can't be more than 65535. Encoding a value longer
+//! than the maximum fails with `TooLong`, and decoding a length prefix over the
+//! maximum fails with `Invalid`. The derives for `SerializedSize` and `HubpackSchema` read the
+//! maximum from the same attribute.
+//!
+//! Without a declared maximum, these values fail to encode or decode with
+//! `NotSupported`. The maximum covers only the field's own length prefix, so
+//! the elements of a sequence can't themselves be sequences or strings,
+//! unless they're structs declaring their own maximums.
+
+use core::fmt;
+use core::marker::PhantomData;
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeTupleStruct, Serializer};
+use crate::size::len_prefix_size;
+
+/// The name under which `MaxLen` passes a value to the `hubpack` serializer
+/// and deserializer, which recognize it and take the length of the tuple
+/// struct as the maximum.
+pub(crate) const NAME: &str = "hubpack::MaxLen";
+
+/// Declares that a field holds at most `N` elements (or bytes, for strings),
+/// for use with `#[serde(with = "hubpack::MaxLen::<N>")]`. See the module
+/// documentation.
+///
+/// With other `serde` formats, this is encoded as a tuple struct holding the
+/// value, which compact binary formats treat as just the value, and
+/// human-readable formats get the value directly.
+pub struct MaxLen<const N: usize>;
+
+impl<const N: usize> MaxLen<N> {
+    /// The declared maximum length.
+    pub const MAX_LEN: usize = N;
+
+    /// Number of bytes used by the length prefix. Using this with an `N` over
+    /// 65535 fails to compile.
+    pub const PREFIX_SIZE: usize = len_prefix_size(N);
+
+    /// Serializes `value`, which must be a slice, string, or sequence of at
+    /// most `N` elements.
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize + ?Sized,
+        S: Serializer,
+    {
+        // Check `N` at compile time.
+        let _ = Self::PREFIX_SIZE;
+        if serializer.is_human_readable() {
+            return value.serialize(serializer);
+        }
+        let mut s = serializer.serialize_tuple_struct(NAME, N)?;
+        s.serialize_field(value)?;
+        s.end()
+    }
+
+    /// Deserializes a slice, string, or sequence of at most `N` elements.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let _ = Self::PREFIX_SIZE;
+        if deserializer.is_human_readable() {
+            return T::deserialize(deserializer);
+        }
+        deserializer.deserialize_tuple_struct(NAME, N, MaxLenVisitor(PhantomData))
+    }
+}
+
+struct MaxLenVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for MaxLenVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a value with a maximum length")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<T, A::Error> {
+        seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))
+    }
+}
@@ -0,0 +1,197 @@
+//! Owned, right-sized buffers holding encoded values.
+//!
+//! Encoding a value usually looks like this:
+//!
+//! ```
+//! # use hubpack::SerializedSize;
+//! let mut buf = [0u8; u32::MAX_SIZE];
+//! let n = hubpack::serialize(&mut buf, &0xDEAD_BEEFu32).unwrap();
+//! let bytes = &buf[..n];
+//! # assert_eq!(bytes.len(), 4);
+//! ```
+//!
+//! That works for concrete types, but generic code can't name the type
+//! `[u8; T::MAX_SIZE]` on stable Rust. [`Packed<T>`] does the same job for any
+//! `T: Packable`, by having each type name its own buffer type.
+
+use core::fmt;
+use core::marker::PhantomData;
+use serde::{Deserialize, Serialize};
+use crate::error::Result;
+use crate::size::SerializedSize;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Storage for an encoded value. This is implemented for byte arrays, and
+/// arrays of them, and can't be implemented elsewhere.
+pub trait Buffer: sealed::Sealed + Clone {
+    /// Returns a buffer full of zeros.
+    fn zeroed() -> Self;
+
+    /// Returns the whole buffer as bytes.
+    fn bytes(&self) -> &[u8];
+
+    /// Returns the whole buffer as mutable bytes.
+    fn bytes_mut(&mut self) -> &mut [u8];
+}
+
+impl<const N: usize> sealed::Sealed for [u8; N] {}
+
+impl<const N: usize> Buffer for [u8; N] {
+    fn zeroed() -> Self {
+        [0; N]
+    }
+
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+impl<const M: usize, const N: usize> sealed::Sealed for [[u8; M]; N] {}
+
+impl<const M: usize, const N: usize> Buffer for [[u8; M]; N] {
+    fn zeroed() -> Self {
+        [[0; M]; N]
+    }
+
+    fn bytes(&self) -> &[u8] {
+        self.as_flattened()
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        self.as_flattened_mut()
+    }
+}
+
+/// The `Packable` trait is implemented by types that can name a buffer big
+/// enough to hold any of their encoded values.
+///
+/// This is implemented for the primitive types, `Option`s of them,
+/// [`FixedStr`](crate::FixedStr), and arrays of any `Packable` type whose
+/// buffer is a byte array. `#[derive(Packable)]` implements it for types that
+/// also derive `SerializedSize`, as long as they don't have type or const
+/// parameters:
+///
+/// ```
+/// use hubpack::SerializedSize;
+/// use hubpack::packed::{Packable, Packed};
+///
+/// #[derive(serde::Serialize, SerializedSize, Packable)]
+/// struct Reading {
+///     channel: u8,
+///     value: Option<i32>,
+/// }
+///
+/// let p = Packed::encode(&Reading { channel: 2, value: None }).unwrap();
+/// assert_eq!(p.as_bytes(), &[2, 0]);
+/// ```
+///
+/// Generic `Option<T>`, tuples, and `Result` aren't `Packable`, because their
+/// buffer would have to be a byte array of a size computed from their
+/// parameters' sizes, which stable Rust can't name in a type. Neither are the
+/// `heapless` collections, which can only be encoded as fields declaring a
+/// [`MaxLen`](crate::MaxLen). Wrap these in a struct deriving `Packable`
+/// instead, or implement `Packable` yourself for the type you need, as long
+/// as `Buffer` is at least `MAX_SIZE` bytes long:
+///
+/// ```
+/// use hubpack::SerializedSize;
+/// use hubpack::packed::Packable;
+///
+/// #[derive(serde::Serialize, SerializedSize)]
+/// struct Sample<T> {
+///     value: T,
+/// }
+///
+/// impl Packable for Sample<u16> {
+///     type Buffer = [u8; <Sample<u16>>::MAX_SIZE];
+/// }
+/// ```
+pub trait Packable: SerializedSize {
+    /// A byte array of at least `MAX_SIZE` bytes.
+    type Buffer: Buffer;
+}
+
+/// Derive macro for the `Packable` trait.
+pub use hubpack_derive::Packable;
+
+impl<T, const M: usize, const N: usize> Packable for [T; N]
+    where T: Packable<Buffer = [u8; M]>,
+{
+    type Buffer = [[u8; M]; N];
+}
+
+/// An encoded `T`, in a buffer that's always big enough for it.
+pub struct Packed<T: Packable> {
+    buf: T::Buffer,
+    len: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Packable> Packed<T> {
+    /// Encodes `value`.
+    ///
+    /// Since the buffer is big enough for any `T`, this can't fail with
+    /// `Overrun`, but can fail for the other reasons `serialize` does.
+    pub fn encode(value: &T) -> Result<Self>
+        where T: Serialize,
+    {
+        let mut buf = T::Buffer::zeroed();
+        let len = crate::serialize(buf.bytes_mut(), value)?;
+        Ok(Self { buf, len, _marker: PhantomData })
+    }
+
+    /// Decodes the value back out of the buffer.
+    pub fn decode<'de>(&'de self) -> Result<T>
+        where T: Deserialize<'de>,
+    {
+        crate::de::deserialize_exact(self.as_bytes())
+    }
+
+    /// Returns the encoded bytes, without any unused part of the buffer.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf.bytes()[..self.len]
+    }
+
+    /// Returns the length of the encoded value in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Checks if the encoded value took no bytes at all, as `()` does.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T: Packable> AsRef<[u8]> for Packed<T> {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl<T: Packable> Clone for Packed<T> {
+    fn clone(&self) -> Self {
+        Self { buf: self.buf.clone(), len: self.len, _marker: PhantomData }
+    }
+}
+
+impl<T: Packable> fmt::Debug for Packed<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Packed").field(&self.as_bytes()).finish()
+    }
+}
+
+impl<T: Packable> PartialEq for Packed<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl<T: Packable> Eq for Packed<T> {}
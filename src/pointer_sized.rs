@@ -0,0 +1,160 @@
+//! Decoding `usize` and `isize` on targets narrower than 64 bits.
+//!
+//! `usize` and `isize` are always encoded as 64 bits, so that programs agree
+//! on the encoding whatever their pointer width. On a 32-bit target, though, a
+//! value encoded by a 64-bit program might not fit. Declaring the field with
+//! [`PointerSized`] makes decoding such a value fail with `IntegerOverflow`:
+//!
+//! ```
+//! use hubpack::SerializedSize;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize, SerializedSize)]
+//! struct Seek {
+//!     #[serde(with = "hubpack::PointerSized")]
+//!     offset: usize,
+//! }
+//!
+//! assert_eq!(Seek::MAX_SIZE, 8);
+//!
+//! let (seek, _) = hubpack::deserialize::<Seek>(&[1, 0, 0, 0, 0, 0, 0, 0]).unwrap();
+//! assert_eq!(seek.offset, 1);
+//! ```
+//!
+//! The encoding is the same either way. Without the declaration, `serde`
+//! rejects the value itself, which is reported as `Invalid`, like any other
+//! value out of range for its type.
+
+use core::fmt;
+use core::marker::PhantomData;
+use serde::de::{self, Deserializer, SeqAccess, Unexpected, Visitor};
+use serde::ser::{SerializeTupleStruct, Serializer};
+
+/// The names under which `PointerSized` passes unsigned and signed values to
+/// the `hubpack` deserializer, which recognizes them and takes the length of
+/// the tuple struct as the number of bits the value has to fit in.
+pub(crate) const UNSIGNED: &str = "hubpack::PointerSized::unsigned";
+pub(crate) const SIGNED: &str = "hubpack::PointerSized::signed";
+
+/// Declares that a `usize` or `isize` field should fail to decode with
+/// `IntegerOverflow` if it doesn't fit on this target, for use with
+/// `#[serde(with = "hubpack::PointerSized")]`. See the module documentation.
+///
+/// With other `serde` formats, this is encoded as a tuple struct holding the
+/// value as a `u64` or `i64`, and human-readable formats get the value
+/// directly.
+pub struct PointerSized;
+
+impl PointerSized {
+    /// Serializes `value` as 64 bits.
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: PointerSizedInt,
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            return value.serialize(serializer);
+        }
+        let mut s = serializer.serialize_tuple_struct(T::NAME, T::BITS)?;
+        value.serialize_wide(&mut s)?;
+        s.end()
+    }
+
+    /// Deserializes a `usize` or `isize` from 64 bits.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: PointerSizedInt,
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            return T::deserialize(deserializer);
+        }
+        deserializer.deserialize_tuple_struct(T::NAME, T::BITS, PointerSizedVisitor(PhantomData))
+    }
+}
+
+/// The types [`PointerSized`] can be used with: `usize` and `isize`.
+pub trait PointerSizedInt: private::Int {}
+
+impl PointerSizedInt for usize {}
+impl PointerSizedInt for isize {}
+
+mod private {
+    use serde::ser::SerializeTupleStruct;
+
+    pub trait Int: Sized + serde::Serialize + for<'de> serde::Deserialize<'de> {
+        /// Which of `UNSIGNED` or `SIGNED` to pass the value under.
+        const NAME: &'static str;
+        /// Number of bits in the type on this target.
+        const BITS: usize;
+
+        fn serialize_wide<S: SerializeTupleStruct>(&self, s: &mut S) -> Result<(), S::Error>;
+        fn from_u64(v: u64) -> Option<Self>;
+        fn from_i64(v: i64) -> Option<Self>;
+    }
+
+    macro_rules! int_impl {
+        ($t:ty, $name:path, $wide:ty) => {
+            impl Int for $t {
+                const NAME: &'static str = $name;
+                const BITS: usize = <$t>::BITS as usize;
+
+                fn serialize_wide<S: SerializeTupleStruct>(&self, s: &mut S) -> Result<(), S::Error> {
+                    s.serialize_field(&(*self as $wide))
+                }
+
+                fn from_u64(v: u64) -> Option<Self> {
+                    v.try_into().ok()
+                }
+
+                fn from_i64(v: i64) -> Option<Self> {
+                    v.try_into().ok()
+                }
+            }
+        };
+    }
+
+    int_impl!(usize, super::UNSIGNED, u64);
+    int_impl!(isize, super::SIGNED, i64);
+}
+
+/// Checks whether `v` fits in an unsigned integer of `bits` bits.
+pub(crate) fn fits_unsigned(v: u64, bits: usize) -> bool {
+    bits >= 64 || v >> bits == 0
+}
+
+/// Checks whether `v` fits in a signed integer of `bits` bits.
+pub(crate) fn fits_signed(v: i64, bits: usize) -> bool {
+    bits >= 64 || {
+        // Everything from the sign bit up has to be the same.
+        let high = v >> (bits - 1);
+        high == 0 || high == -1
+    }
+}
+
+struct PointerSizedVisitor<T>(PhantomData<T>);
+
+impl<'de, T: PointerSizedInt> Visitor<'de> for PointerSizedVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a 64-bit integer that fits in a usize or isize")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<T, E> {
+        T::from_u64(v).ok_or_else(|| E::invalid_value(Unexpected::Unsigned(v), &self))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<T, E> {
+        T::from_i64(v).ok_or_else(|| E::invalid_value(Unexpected::Signed(v), &self))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<T, A::Error> {
+        let missing = || de::Error::invalid_length(0, &"a 64-bit integer");
+        if T::NAME == SIGNED {
+            self.visit_i64(seq.next_element()?.ok_or_else(missing)?)
+        } else {
+            self.visit_u64(seq.next_element()?.ok_or_else(missing)?)
+        }
+    }
+}
@@ -0,0 +1,429 @@
+//! Descriptions of the encoded layout of types.
+//!
+//! `SerializedSize` tells you how big an encoded value can be. The
+//! `HubpackSchema` trait goes further, and describes what each byte means:
+//! which fields come in which order, how wide each integer is, which enum
+//! variant has which index, and so on. This is enough to decode a message
+//! without having the Rust type at hand, which makes it useful for tools and
+//! for other languages.
+//!
+//! Like `SerializedSize`, `HubpackSchema` can be derived:
+//!
+//! ```
+//! use hubpack::HubpackSchema;
+//! use hubpack::schema::{Schema, Fields, Field};
+//!
+//! #[derive(HubpackSchema)]
+//! struct Reading {
+//!     sensor: u8,
+//!     value: Option<i32>,
+//! }
+//!
+//! assert_eq!(Reading::SCHEMA, Schema::Struct {
+//!     name: "Reading",
+//!     fields: Fields::Named(&[
+//!         Field { name: "sensor", schema: Schema::U8 },
+//!         Field { name: "value", schema: Schema::Option(&Schema::I32) },
+//!     ]),
+//! });
+//! ```
+//!
+//! The derive only looks at the Rust definition, and doesn't understand
+//! `serde` attributes that change the encoding, such as `skip` or `with`.
+
+/// The `HubpackSchema` trait is implemented by types that can describe their
+/// own encoding.
+///
+/// It's implemented for the same standard types as `SerializedSize`, and can
+/// be derived for your own types.
+pub trait HubpackSchema {
+    /// Description of how values of `Self` are encoded.
+    const SCHEMA: Schema;
+
+    /// A fingerprint of `SCHEMA`, for checking that two programs agree on
+    /// the encoding of `Self`; see [`Schema::hash`].
+    ///
+    /// When derived, this includes the names of the type, its fields, and its
+    /// variants, unless the type is marked `#[hubpack(hash_without_names)]`.
+    const SCHEMA_HASH: u64 = Self::SCHEMA.hash();
+}
+
+/// The encoded layout of a type.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Schema {
+    /// No bytes at all, as for `()`.
+    Unit,
+    /// A byte that's either 0 or 1.
+    Bool,
+    /// A single byte.
+    U8,
+    /// A `u16`, in the configured byte order.
+    U16,
+    /// A `u32`, in the configured byte order.
+    U32,
+    /// A `u64`, in the configured byte order.
+    U64,
+    /// A `u128`, in the configured byte order.
+    U128,
+    /// A single byte, as two's complement.
+    I8,
+    /// An `i16`, in the configured byte order.
+    I16,
+    /// An `i32`, in the configured byte order.
+    I32,
+    /// An `i64`, in the configured byte order.
+    I64,
+    /// An `i128`, in the configured byte order.
+    I128,
+    /// An IEEE 754 `f32`, in the configured byte order.
+    F32,
+    /// An IEEE 754 `f64`, in the configured byte order.
+    F64,
+    /// A Unicode scalar value, encoded like a `u32`.
+    Char,
+    /// A `bool` tag, followed by the value if the tag is 1.
+    Option(&'static Schema),
+    /// Exactly `len` elements, one after another, with no length prefix.
+    Array { len: usize, element: &'static Schema },
+    /// Each element in turn, with no length prefix.
+    Tuple(&'static [Schema]),
+    /// A length prefix followed by that many elements. `max_len` is the
+    /// declared maximum, if any.
+    Seq { max_len: Option<usize>, element: &'static Schema },
+    /// A length prefix followed by that many bytes of UTF-8. `max_len` is the
+    /// declared maximum, if any.
+    Str { max_len: Option<usize> },
+    /// Exactly `len` bytes of UTF-8, padded at the end with NULs; see
+    /// `FixedStr`.
+    FixedStr { len: usize },
+    /// The fields of a struct, one after another.
+    Struct { name: &'static str, fields: Fields },
+    /// A `u8` variant index, followed by the fields of that variant.
+    Enum { name: &'static str, variants: &'static [Variant] },
+}
+
+/// The fields of a struct or enum variant.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Fields {
+    /// No fields, as in `struct Unit;`.
+    Unit,
+    /// Unnamed fields, as in `struct Pair(u8, u8);`.
+    Tuple(&'static [Schema]),
+    /// Named fields, as in `struct Point { x: i32, y: i32 }`.
+    Named(&'static [Field]),
+}
+
+/// A named field.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Field {
+    /// The field's name, as written in Rust.
+    pub name: &'static str,
+    /// How the field is encoded.
+    pub schema: Schema,
+}
+
+/// A variant of an enum.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Variant {
+    /// The variant's name, as written in Rust.
+    pub name: &'static str,
+    /// The byte that identifies this variant on the wire.
+    pub index: u8,
+    /// The fields that follow the index.
+    pub fields: Fields,
+}
+
+impl Schema {
+    /// Computes a 64-bit fingerprint of this schema, including the names of
+    /// structs, enums, fields, and variants.
+    ///
+    /// The fingerprint changes if fields are reordered, if any type changes,
+    /// or if variants are added, removed, or reordered. It's computed with
+    /// 64-bit FNV-1a over a fixed description of the schema, so it doesn't
+    /// depend on the compiler or platform.
+    ///
+    /// This isn't a cryptographic hash, and so isn't useful against peers
+    /// that are trying to fool you.
+    pub const fn hash(&self) -> u64 {
+        hash_schema(Fnv::new(), self, true).0
+    }
+
+    /// Computes a fingerprint like [`Schema::hash`], but ignoring all names,
+    /// so that only the encoding itself matters.
+    pub const fn hash_without_names(&self) -> u64 {
+        hash_schema(Fnv::new(), self, false).0
+    }
+
+    /// Returns `self` with a declared maximum length, if it's a sequence or
+    /// string. This is how the derive handles `MaxLen`.
+    pub const fn with_max_len(self, max_len: usize) -> Self {
+        match self {
+            Schema::Seq { element, .. } => Schema::Seq { max_len: Some(max_len), element },
+            Schema::Str { .. } => Schema::Str { max_len: Some(max_len) },
+            other => other,
+        }
+    }
+}
+
+/// 64-bit FNV-1a, written so that it can be used in `const fn`s.
+#[derive(Copy, Clone)]
+struct Fnv(u64);
+
+impl Fnv {
+    const fn new() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    const fn byte(self, b: u8) -> Self {
+        Fnv((self.0 ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3))
+    }
+
+    const fn u64(mut self, v: u64) -> Self {
+        let bytes = v.to_le_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            self = self.byte(bytes[i]);
+            i += 1;
+        }
+        self
+    }
+
+    const fn str(mut self, s: &str) -> Self {
+        let bytes = s.as_bytes();
+        self = self.u64(bytes.len() as u64);
+        let mut i = 0;
+        while i < bytes.len() {
+            self = self.byte(bytes[i]);
+            i += 1;
+        }
+        self
+    }
+
+    const fn name(self, s: &str, names: bool) -> Self {
+        if names { self.str(s) } else { self }
+    }
+}
+
+// Each kind of schema starts with a distinct tag byte, and every list starts
+// with its length, so that different schemas can't produce the same sequence
+// of bytes.
+const fn hash_schema(h: Fnv, s: &Schema, names: bool) -> Fnv {
+    match s {
+        Schema::Unit => h.byte(0),
+        Schema::Bool => h.byte(1),
+        Schema::U8 => h.byte(2),
+        Schema::U16 => h.byte(3),
+        Schema::U32 => h.byte(4),
+        Schema::U64 => h.byte(5),
+        Schema::U128 => h.byte(6),
+        Schema::I8 => h.byte(7),
+        Schema::I16 => h.byte(8),
+        Schema::I32 => h.byte(9),
+        Schema::I64 => h.byte(10),
+        Schema::I128 => h.byte(11),
+        Schema::F32 => h.byte(12),
+        Schema::F64 => h.byte(13),
+        Schema::Char => h.byte(14),
+        Schema::Option(inner) => hash_schema(h.byte(15), inner, names),
+        Schema::Array { len, element } => {
+            hash_schema(h.byte(16).u64(*len as u64), element, names)
+        }
+        Schema::Tuple(elements) => hash_list(h.byte(17), elements, names),
+        Schema::Seq { max_len, element } => {
+            hash_schema(hash_max_len(h.byte(18), *max_len), element, names)
+        }
+        Schema::Str { max_len } => hash_max_len(h.byte(19), *max_len),
+        Schema::FixedStr { len } => h.byte(20).u64(*len as u64),
+        Schema::Struct { name, fields } => {
+            hash_fields(h.byte(21).name(name, names), fields, names)
+        }
+        Schema::Enum { name, variants } => {
+            let mut h = h.byte(22).name(name, names).u64(variants.len() as u64);
+            let mut i = 0;
+            while i < variants.len() {
+                let v = &variants[i];
+                h = hash_fields(h.byte(v.index).name(v.name, names), &v.fields, names);
+                i += 1;
+            }
+            h
+        }
+    }
+}
+
+const fn hash_max_len(h: Fnv, max_len: Option<usize>) -> Fnv {
+    match max_len {
+        None => h.byte(0),
+        Some(n) => h.byte(1).u64(n as u64),
+    }
+}
+
+const fn hash_list(mut h: Fnv, list: &[Schema], names: bool) -> Fnv {
+    h = h.u64(list.len() as u64);
+    let mut i = 0;
+    while i < list.len() {
+        h = hash_schema(h, &list[i], names);
+        i += 1;
+    }
+    h
+}
+
+const fn hash_fields(h: Fnv, fields: &Fields, names: bool) -> Fnv {
+    match fields {
+        Fields::Unit => h.byte(0),
+        Fields::Tuple(list) => hash_list(h.byte(1), list, names),
+        Fields::Named(list) => {
+            // Without their names, named fields are just like unnamed ones.
+            let mut h = h.byte(if names { 2 } else { 1 }).u64(list.len() as u64);
+            let mut i = 0;
+            while i < list.len() {
+                h = hash_schema(h.name(list[i].name, names), &list[i].schema, names);
+                i += 1;
+            }
+            h
+        }
+    }
+}
+
+macro_rules! schema_impls {
+    ($( $t:ty = $s:expr; )*) => {
+        $(
+            impl HubpackSchema for $t {
+                const SCHEMA: Schema = $s;
+            }
+        )*
+    };
+}
+
+schema_impls! {
+    () = Schema::Unit;
+
+    u8 = Schema::U8;
+    u16 = Schema::U16;
+    u32 = Schema::U32;
+    u64 = Schema::U64;
+    u128 = Schema::U128;
+
+    i8 = Schema::I8;
+    i16 = Schema::I16;
+    i32 = Schema::I32;
+    i64 = Schema::I64;
+    i128 = Schema::I128;
+
+    // Always 64 bits on the wire.
+    usize = Schema::U64;
+    isize = Schema::I64;
+
+    f32 = Schema::F32;
+    f64 = Schema::F64;
+
+    bool = Schema::Bool;
+    char = Schema::Char;
+
+    str = Schema::Str { max_len: None };
+}
+
+impl<T: HubpackSchema> HubpackSchema for [T] {
+    const SCHEMA: Schema = Schema::Seq { max_len: None, element: &T::SCHEMA };
+}
+
+impl<T: HubpackSchema + ?Sized> HubpackSchema for &T {
+    const SCHEMA: Schema = T::SCHEMA;
+}
+
+impl<T: HubpackSchema> HubpackSchema for Option<T> {
+    const SCHEMA: Schema = Schema::Option(&T::SCHEMA);
+}
+
+impl<T: HubpackSchema, E: HubpackSchema> HubpackSchema for Result<T, E> {
+    const SCHEMA: Schema = Schema::Enum {
+        name: "Result",
+        variants: &[
+            Variant { name: "Ok", index: 0, fields: Fields::Tuple(&[T::SCHEMA]) },
+            Variant { name: "Err", index: 1, fields: Fields::Tuple(&[E::SCHEMA]) },
+        ],
+    };
+}
+
+impl<T: HubpackSchema, const N: usize> HubpackSchema for [T; N] {
+    const SCHEMA: Schema = Schema::Array { len: N, element: &T::SCHEMA };
+}
+
+impl<const N: usize> HubpackSchema for crate::FixedStr<N> {
+    const SCHEMA: Schema = Schema::FixedStr { len: N };
+}
+
+#[cfg(feature = "heapless")]
+impl<T: HubpackSchema, const N: usize> HubpackSchema for heapless::Vec<T, N> {
+    // Like any other sequence, the maximum comes from `MaxLen`.
+    const SCHEMA: Schema = <[T]>::SCHEMA;
+}
+
+#[cfg(feature = "heapless")]
+impl<const N: usize> HubpackSchema for heapless::String<N> {
+    const SCHEMA: Schema = str::SCHEMA;
+}
+
+// These share the schema of what they point to, matching their sizes in
+// `size.rs`.
+#[cfg(feature = "alloc")]
+mod alloc_impls {
+    use super::{HubpackSchema, Schema};
+    use alloc::borrow::{Cow, ToOwned};
+    use alloc::boxed::Box;
+    use alloc::rc::Rc;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    #[cfg(target_has_atomic = "ptr")]
+    use alloc::sync::Arc;
+
+    macro_rules! pointer_impls {
+        ($($p:ident),*) => {
+            $(
+                impl<T: HubpackSchema + ?Sized> HubpackSchema for $p<T> {
+                    const SCHEMA: Schema = T::SCHEMA;
+                }
+            )*
+        };
+    }
+
+    pointer_impls!(Box, Rc);
+
+    #[cfg(target_has_atomic = "ptr")]
+    pointer_impls!(Arc);
+
+    impl<T: HubpackSchema + ToOwned + ?Sized> HubpackSchema for Cow<'_, T> {
+        const SCHEMA: Schema = T::SCHEMA;
+    }
+
+    impl<T: HubpackSchema> HubpackSchema for Vec<T> {
+        const SCHEMA: Schema = <[T]>::SCHEMA;
+    }
+
+    impl HubpackSchema for String {
+        const SCHEMA: Schema = Schema::Str { max_len: None };
+    }
+}
+
+impl<A: HubpackSchema> HubpackSchema for (A,) {
+    const SCHEMA: Schema = Schema::Tuple(&[A::SCHEMA]);
+}
+
+macro_rules! tuple_impl {
+    ($($t:ident),+) => {
+        impl<$($t: HubpackSchema),+> HubpackSchema for ($($t),+) {
+            const SCHEMA: Schema = Schema::Tuple(&[$($t::SCHEMA),+]);
+        }
+    };
+}
+
+tuple_impl!(A, B);
+tuple_impl!(A, B, C);
+tuple_impl!(A, B, C, D);
+tuple_impl!(A, B, C, D, E);
+tuple_impl!(A, B, C, D, E, F);
+tuple_impl!(A, B, C, D, E, F, G);
+tuple_impl!(A, B, C, D, E, F, G, H);
+tuple_impl!(A, B, C, D, E, F, G, H, I);
+tuple_impl!(A, B, C, D, E, F, G, H, I, J);
+tuple_impl!(A, B, C, D, E, F, G, H, I, J, K);
+tuple_impl!(A, B, C, D, E, F, G, H, I, J, K, L);
@@ -1,43 +1,145 @@
 //! Serialization of Rust values into `hubpack` format.
 
+use core::marker::PhantomData;
 use serde::{ser, Serialize};
-use crate::error::{Error, Result};
+use crate::config::{ByteOrder, Config, LittleEndian};
+use crate::error::{Error, Result, Tracker};
+#[cfg(feature = "error-context")]
+use crate::error::DetailedError;
 
 /// Serializes `value`, which must implement `serde::Serialize`, into `buf`.
 /// On success, returns the number of bytes used.
 ///
-/// Failures in `hubpack` itself fall into two groups.
+/// Failures in `hubpack` itself fall into three groups.
 ///
 /// - Dynamic failures: `Overrun`. This means that `buf` was not large enough to
 ///   contain the serialized representation of `value`, but a larger `buf` might
 ///   have succeeded.
 /// - Static failures: `TooManyVariants` and `NotSupported`. These mean that the
 ///   type of `value` is simply incompatible with `hubpack` and won't work.
+/// - Length failures: `TooLong`. This means that a slice, string, or sequence
+///   in `value` had more elements than its declared maximum length allows.
 ///
 /// The catch-all error `Custom` may be produced by the `Serialize`
 /// implementation of `value` or anything contained within `value`, but is never
 /// produced by `hubpack` directly.
 pub fn serialize(buf: &mut [u8], value: &impl Serialize) -> Result<usize> {
-    let mut s = Serializer { buf, pos: 0 };
-    value.serialize(&mut s)?;
-    Ok(s.pos)
+    serialize_with_config(buf, value, LittleEndian)
 }
 
-struct Serializer<'a> {
+/// Serializes `value` into `buf` like [`serialize`], but using the encoding
+/// variant described by `config`.
+pub fn serialize_with_config<C: Config>(
+    buf: &mut [u8],
+    value: &impl Serialize,
+    config: C,
+) -> Result<usize> {
+    let mut e = Encoder::with_config(buf, config);
+    e.encode(value)?;
+    Ok(e.position())
+}
+
+/// Serializes `value` into `buf` like [`serialize`], but on failure, describes
+/// where in `buf` and where in `value` things went wrong.
+#[cfg(feature = "error-context")]
+#[allow(clippy::result_large_err)] // see DetailedError
+pub fn serialize_with_context(
+    buf: &mut [u8],
+    value: &impl Serialize,
+) -> core::result::Result<usize, DetailedError> {
+    let mut e = Encoder::new(buf);
+    e.encode_with_context(value)?;
+    Ok(e.position())
+}
+
+/// Serializes `value` into a newly allocated `Vec`, which is exactly as long
+/// as the encoded representation.
+///
+/// This measures `value` using [`serialized_size`] first, so that it can
+/// allocate exactly once.
+#[cfg(feature = "alloc")]
+pub fn to_vec(value: &impl Serialize) -> Result<alloc::vec::Vec<u8>> {
+    let mut buf = alloc::vec![0; serialized_size(value)?];
+    serialize(&mut buf, value)?;
+    Ok(buf)
+}
+
+/// A cursor that serializes values into a buffer, one after another.
+///
+/// `serialize` is the simplest way to encode a single value. `Encoder` is
+/// useful when you want to write several values back-to-back into the same
+/// buffer, or keep track of how much space remains.
+///
+/// `&mut Encoder` implements `serde::Serializer`, so it can also be handed to
+/// other `serde` adapters.
+///
+/// The `C` parameter selects the encoding variant; see the `config` module.
+pub type Encoder<'a, C = LittleEndian> = Serializer<SliceOutput<'a>, C>;
+
+/// A serializer that doesn't write anything, and only counts how many bytes
+/// it would have written.
+///
+/// See [`serialized_size`].
+pub type SizeCounter = Serializer<CountOutput>;
+
+/// Returns the exact number of bytes that `value` will take when serialized,
+/// without actually serializing it.
+///
+/// This applies the same rules as [`serialize`], so it can fail for the same
+/// reasons, except for `Overrun`.
+pub fn serialized_size(value: &impl Serialize) -> Result<usize> {
+    let mut c = SizeCounter::new();
+    c.encode(value)?;
+    Ok(c.position())
+}
+
+/// The `hubpack` serializer, which writes into some kind of [`Output`].
+///
+/// You'll generally use this through one of its aliases, [`Encoder`] or
+/// [`SizeCounter`].
+pub struct Serializer<O, C = LittleEndian> {
+    out: O,
+    track: Tracker,
+    /// Maximum length declared by an enclosing `MaxLen`, waiting to be used
+    /// by the next length prefix.
+    max_len: Option<usize>,
+    _config: PhantomData<C>,
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A destination for the bytes produced by a [`Serializer`].
+///
+/// This trait is sealed; it's implemented by [`SliceOutput`] and
+/// [`CountOutput`].
+pub trait Output: sealed::Sealed {
+    /// Returns the number of bytes written so far.
+    fn position(&self) -> usize;
+
+    /// Moves back to `pos`, which is no greater than the current position.
+    fn rewind(&mut self, pos: usize);
+
+    /// Writes a single byte.
+    fn write_u8(&mut self, v: u8) -> Result<()>;
+
+    /// Writes an array of bytes.
+    fn write_ary<const N: usize>(&mut self, v: [u8; N]) -> Result<()>;
+
+    /// Writes a slice of bytes.
+    fn write_slice(&mut self, v: &[u8]) -> Result<()>;
+}
+
+/// An [`Output`] that writes into a borrowed buffer.
+pub struct SliceOutput<'a> {
     buf: &'a mut [u8],
     pos: usize,
 }
 
-impl<'a> Serializer<'a> {
-    fn write_u8(&mut self, v: u8) -> Result<()> {
-        *self.buf.get_mut(self.pos).ok_or(Error::Overrun)? = v;
-        // We can use non-overflowing add here because the dereference using pos
-        // just succeeded, meaning it is < buf.len, and buf.len can't be larger
-        // than usize::MAX.
-        self.pos = self.pos.wrapping_add(1);
-        Ok(())
-    }
+impl sealed::Sealed for SliceOutput<'_> {}
 
+impl SliceOutput<'_> {
     fn get_ary_mut<const N: usize>(&mut self) -> Result<&mut [u8; N]> {
         let chunk = self.buf.get_mut(self.pos..self.pos + N)
             .ok_or(Error::Overrun)?;
@@ -50,35 +152,229 @@ impl<'a> Serializer<'a> {
         self.pos = self.pos.wrapping_add(N);
         Ok(chunk.try_into().unwrap())
     }
+}
 
-    fn write_u16(&mut self, v: u16) -> Result<()> {
-        *self.get_ary_mut()? = v.to_le_bytes();
+impl Output for SliceOutput<'_> {
+    fn position(&self) -> usize {
+        self.pos
+    }
+
+    fn rewind(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
+    fn write_u8(&mut self, v: u8) -> Result<()> {
+        *self.buf.get_mut(self.pos).ok_or(Error::Overrun)? = v;
+        // We can use non-overflowing add here because the dereference using pos
+        // just succeeded, meaning it is < buf.len, and buf.len can't be larger
+        // than usize::MAX.
+        self.pos = self.pos.wrapping_add(1);
         Ok(())
     }
 
-    fn write_u32(&mut self, v: u32) -> Result<()> {
-        *self.get_ary_mut()? = v.to_le_bytes();
+    fn write_ary<const N: usize>(&mut self, v: [u8; N]) -> Result<()> {
+        *self.get_ary_mut()? = v;
         Ok(())
     }
 
-    fn write_u64(&mut self, v: u64) -> Result<()> {
-        *self.get_ary_mut()? = v.to_le_bytes();
+    fn write_slice(&mut self, v: &[u8]) -> Result<()> {
+        let chunk = self.buf.get_mut(self.pos..self.pos + v.len())
+            .ok_or(Error::Overrun)?;
+        chunk.copy_from_slice(v);
+        self.pos += v.len();
         Ok(())
     }
+}
 
-    fn write_u128(&mut self, v: u128) -> Result<()> {
-        *self.get_ary_mut()? = v.to_le_bytes();
+/// An [`Output`] that discards bytes, and only counts them.
+#[derive(Default)]
+pub struct CountOutput {
+    pos: usize,
+}
+
+impl sealed::Sealed for CountOutput {}
+
+impl CountOutput {
+    fn advance(&mut self, n: usize) -> Result<()> {
+        // A value would have to be larger than memory to overflow this, but
+        // it's cheap to check.
+        self.pos = self.pos.checked_add(n).ok_or(Error::Overrun)?;
         Ok(())
     }
+}
+
+impl Output for CountOutput {
+    fn position(&self) -> usize {
+        self.pos
+    }
+
+    fn rewind(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
+    fn write_u8(&mut self, _v: u8) -> Result<()> {
+        self.advance(1)
+    }
+
+    fn write_ary<const N: usize>(&mut self, _v: [u8; N]) -> Result<()> {
+        self.advance(N)
+    }
+
+    fn write_slice(&mut self, v: &[u8]) -> Result<()> {
+        self.advance(v.len())
+    }
+}
+
+impl<'a> Encoder<'a> {
+    /// Creates an `Encoder` that will write into `buf`, starting at its
+    /// beginning.
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self::with_config(buf, LittleEndian)
+    }
+}
+
+impl<'a, C: Config> Encoder<'a, C> {
+    /// Creates an `Encoder` that will write into `buf` using the encoding
+    /// variant described by `config`.
+    pub fn with_config(buf: &'a mut [u8], _config: C) -> Self {
+        Self::from_output(SliceOutput { buf, pos: 0 })
+    }
+
+    /// Returns the number of bytes left in the buffer.
+    pub fn remaining(&self) -> usize {
+        self.out.buf.len() - self.out.pos
+    }
+
+    /// Consumes the `Encoder`, returning the part of the buffer that has been
+    /// written.
+    pub fn finish(self) -> &'a mut [u8] {
+        &mut self.out.buf[..self.out.pos]
+    }
+}
+
+impl SizeCounter {
+    /// Creates a `SizeCounter` starting from zero.
+    pub fn new() -> Self {
+        Self::from_output(CountOutput::default())
+    }
+}
+
+impl Default for SizeCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<O: Output, C: Config> Serializer<O, C> {
+    fn from_output(out: O) -> Self {
+        Self { out, track: Tracker::default(), max_len: None, _config: PhantomData }
+    }
+
+    /// Serializes `value` at the current position, and advances past it.
+    ///
+    /// If this fails, the position is left where it was before the call, so
+    /// any bytes written for a partial `value` will be overwritten by the next
+    /// successful `encode`.
+    pub fn encode(&mut self, value: &impl Serialize) -> Result<()> {
+        let start = self.out.position();
+        self.track.reset();
+        self.max_len = None;
+        let result = value.serialize(&mut *self);
+        if result.is_err() {
+            self.out.rewind(start);
+        }
+        result
+    }
+
+    /// Serializes `value` like [`Serializer::encode`], but on failure,
+    /// describes where things went wrong.
+    #[cfg(feature = "error-context")]
+    #[allow(clippy::result_large_err)] // see DetailedError
+    pub fn encode_with_context(
+        &mut self,
+        value: &impl Serialize,
+    ) -> core::result::Result<(), DetailedError> {
+        self.encode(value).map_err(|error| DetailedError {
+            error,
+            context: self.track.context(error),
+        })
+    }
+
+    /// Returns the number of bytes written so far.
+    pub fn position(&self) -> usize {
+        self.out.position()
+    }
+
+    fn write_u8(&mut self, v: u8) -> Result<()> {
+        self.track.access(self.out.position(), 1);
+        self.out.write_u8(v)
+    }
+
+    fn write_ary<const N: usize>(&mut self, v: [u8; N]) -> Result<()> {
+        self.track.access(self.out.position(), N);
+        self.out.write_ary(v)
+    }
+
+    fn write_u16(&mut self, v: u16) -> Result<()> {
+        self.write_ary(match C::BYTE_ORDER {
+            ByteOrder::Little => v.to_le_bytes(),
+            ByteOrder::Big => v.to_be_bytes(),
+        })
+    }
+
+    fn write_u32(&mut self, v: u32) -> Result<()> {
+        self.write_ary(match C::BYTE_ORDER {
+            ByteOrder::Little => v.to_le_bytes(),
+            ByteOrder::Big => v.to_be_bytes(),
+        })
+    }
+
+    fn write_u64(&mut self, v: u64) -> Result<()> {
+        self.write_ary(match C::BYTE_ORDER {
+            ByteOrder::Little => v.to_le_bytes(),
+            ByteOrder::Big => v.to_be_bytes(),
+        })
+    }
+
+    fn write_u128(&mut self, v: u128) -> Result<()> {
+        self.write_ary(match C::BYTE_ORDER {
+            ByteOrder::Little => v.to_le_bytes(),
+            ByteOrder::Big => v.to_be_bytes(),
+        })
+    }
 
     fn write_variant(&mut self, v: u32) -> Result<()> {
+        self.track.access(self.out.position(), 1);
         self.write_u8(
             v.try_into().map_err(|_| Error::TooManyVariants)?
         )
     }
+
+    /// Writes the length prefix of a slice, string, or sequence, checking it
+    /// against its declared maximum length, `max`, which also picks the size
+    /// of the prefix. Without one, the value isn't supported.
+    pub(crate) fn write_len(&mut self, len: usize, max: Option<usize>) -> Result<()> {
+        let max = max.ok_or(Error::NotSupported)?;
+        if len > max {
+            return Err(Error::TooLong);
+        }
+        // `len` fits in the prefix, because `max` does.
+        if max <= usize::from(u8::MAX) {
+            self.write_u8(len as u8)
+        } else if max <= usize::from(u16::MAX) {
+            self.write_u16(len as u16)
+        } else {
+            Err(Error::NotSupported)
+        }
+    }
+
+    pub(crate) fn write_slice(&mut self, v: &[u8]) -> Result<()> {
+        self.track.access(self.out.position(), v.len());
+        self.out.write_slice(v)
+    }
 }
 
-impl<'a, 'b> ser::Serializer for &'a mut Serializer<'b> {
+impl<O: Output, C: Config> ser::Serializer for &mut Serializer<O, C> {
     type Ok = ();
     type Error = Error;
 
@@ -88,7 +384,7 @@ impl<'a, 'b> ser::Serializer for &'a mut Serializer<'b> {
     type SerializeStruct = Self;
     type SerializeStructVariant = Self;
 
-    type SerializeSeq = ser::Impossible<(), Error>;
+    type SerializeSeq = Self;
     type SerializeMap = ser::Impossible<(), Error>;
 
     fn is_human_readable(&self) -> bool {
@@ -152,25 +448,11 @@ impl<'a, 'b> ser::Serializer for &'a mut Serializer<'b> {
     }
 
     fn serialize_char(self, v: char) -> Result<()> {
-        if false {
-            // As of the current Unicode version, the maximum UTF-8 encoded length
-            // of any char is 4 bytes, which is also sizeof(char). So, that's handy.
-            //
-            // To ensure that any char value can encode, we require 4 bytes.
-            // However, since we don't always consume all 4, we can't use the array
-            // access routine.
-            let dest = self.buf.get_mut(self.pos..self.pos + 4)
-                .ok_or(Error::Overrun)?;
-            let encoded = v.encode_utf8(dest);
-            // Only advance by the required number of bytes.
-            self.pos += encoded.len();
-            Ok(())
-        } else {
-            Err(Error::NotSupported)
-        }
+        // Chars are encoded as their Unicode scalar value, rather than UTF-8,
+        // so that every char takes exactly 4 bytes.
+        self.write_u32(u32::from(v))
     }
 
-
     fn serialize_none(self) -> Result<()> {
         self.serialize_bool(false)
     }
@@ -199,21 +481,25 @@ impl<'a, 'b> ser::Serializer for &'a mut Serializer<'b> {
         self,
         _name: &'static str,
         variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         value: &T,
     ) -> Result<()> {
         self.write_variant(variant_index)?;
-        value.serialize(self)
+        self.track.push(variant);
+        value.serialize(&mut *self)?;
+        self.track.pop();
+        Ok(())
     }
 
     fn serialize_tuple_variant(
         self,
         _name: &'static str,
         variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
         self.write_variant(variant_index)?;
+        self.track.push(variant);
         Ok(self)
     }
 
@@ -221,10 +507,11 @@ impl<'a, 'b> ser::Serializer for &'a mut Serializer<'b> {
         self,
         _name: &'static str,
         variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
         self.write_variant(variant_index)?;
+        self.track.push(variant);
         Ok(self)
     }
 
@@ -239,9 +526,12 @@ impl<'a, 'b> ser::Serializer for &'a mut Serializer<'b> {
 
     fn serialize_tuple_struct(
         self,
-        _name: &'static str,
-        _len: usize,
+        name: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
+        if name == crate::max_len::NAME {
+            self.max_len = Some(len);
+        }
         Ok(self)
     }
 
@@ -255,9 +545,12 @@ impl<'a, 'b> ser::Serializer for &'a mut Serializer<'b> {
 
     fn serialize_seq(
         self,
-        _len: Option<usize>,
+        len: Option<usize>,
     ) -> Result<Self::SerializeSeq> {
-        Err(Error::NotSupported)
+        // We need to know the length up front to write the prefix.
+        let max = self.max_len.take();
+        self.write_len(len.ok_or(Error::NotSupported)?, max)?;
+        Ok(self)
     }
 
     fn serialize_map(
@@ -269,9 +562,9 @@ impl<'a, 'b> ser::Serializer for &'a mut Serializer<'b> {
 
     fn serialize_str(
         self,
-        _v: &str,
+        v: &str,
     ) -> Result<()> {
-        Err(Error::NotSupported)
+        self.serialize_bytes(v.as_bytes())
     }
 
     fn collect_str<T: ?Sized + core::fmt::Display>(
@@ -283,13 +576,15 @@ impl<'a, 'b> ser::Serializer for &'a mut Serializer<'b> {
 
     fn serialize_bytes(
         self,
-        _v: &[u8],
+        v: &[u8],
     ) -> Result<()> {
-        Err(Error::NotSupported)
+        let max = self.max_len.take();
+        self.write_len(v.len(), max)?;
+        self.write_slice(v)
     }
 }
 
-impl<'a, 'b> ser::SerializeTuple for &'a mut Serializer<'b> {
+impl<O: Output, C: Config> ser::SerializeSeq for &mut Serializer<O, C> {
     type Ok = ();
     type Error = Error;
 
@@ -305,7 +600,23 @@ impl<'a, 'b> ser::SerializeTuple for &'a mut Serializer<'b> {
     }
 }
 
-impl<'a, 'b> ser::SerializeTupleVariant for &'a mut Serializer<'b> {
+impl<O: Output, C: Config> ser::SerializeTuple for &mut Serializer<O, C> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(
+        &mut self,
+        element: &T,
+    ) -> Result<()> {
+        element.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<O: Output, C: Config> ser::SerializeTupleVariant for &mut Serializer<O, C> {
     type Ok = ();
     type Error = Error;
 
@@ -317,28 +628,33 @@ impl<'a, 'b> ser::SerializeTupleVariant for &'a mut Serializer<'b> {
     }
 
     fn end(self) -> Result<()> {
+        self.track.pop();
         Ok(())
     }
 }
 
-impl<'a, 'b> ser::SerializeStructVariant for &'a mut Serializer<'b> {
+impl<O: Output, C: Config> ser::SerializeStructVariant for &mut Serializer<O, C> {
     type Ok = ();
     type Error = Error;
 
     fn serialize_field<T: Serialize + ?Sized>(
         &mut self,
-        _key: &'static str,
+        key: &'static str,
         field: &T,
     ) -> Result<()> {
-        field.serialize(&mut **self)
+        self.track.push(key);
+        field.serialize(&mut **self)?;
+        self.track.pop();
+        Ok(())
     }
 
     fn end(self) -> Result<()> {
+        self.track.pop();
         Ok(())
     }
 }
 
-impl<'a, 'b> ser::SerializeTupleStruct for &'a mut Serializer<'b> {
+impl<O: Output, C: Config> ser::SerializeTupleStruct for &mut Serializer<O, C> {
     type Ok = ();
     type Error = Error;
 
@@ -350,20 +666,25 @@ impl<'a, 'b> ser::SerializeTupleStruct for &'a mut Serializer<'b> {
     }
 
     fn end(self) -> Result<()> {
+        // Don't let an unused `MaxLen` apply to whatever comes next.
+        self.max_len = None;
         Ok(())
     }
 }
 
-impl<'a, 'b> ser::SerializeStruct for &'a mut Serializer<'b> {
+impl<O: Output, C: Config> ser::SerializeStruct for &mut Serializer<O, C> {
     type Ok = ();
     type Error = Error;
 
     fn serialize_field<T: Serialize + ?Sized>(
         &mut self,
-        _key: &'static str,
+        key: &'static str,
         field: &T,
     ) -> Result<()> {
-        field.serialize(&mut **self)
+        self.track.push(key);
+        field.serialize(&mut **self)?;
+        self.track.pop();
+        Ok(())
     }
 
     fn end(self) -> Result<()> {
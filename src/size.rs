@@ -1,4 +1,4 @@
-//! Reasoning about the maximum encoded size of types.
+//! Reasoning about the encoded size of types.
 
 /// The `SerializedSize` trait is implemented by types that have a predictable
 /// maximum size when encoded using `hubpack`.
@@ -8,6 +8,116 @@
 pub trait SerializedSize {
     /// Maximum encoded size of `Self`, in bytes.
     const MAX_SIZE: usize;
+
+    /// Minimum encoded size of `Self`, in bytes. No value can be decoded from
+    /// fewer bytes than this.
+    ///
+    /// This defaults to zero, which is always correct, if not very useful.
+    const MIN_SIZE: usize = 0;
+
+    /// Whether every value of `Self` is encoded in exactly `MAX_SIZE` bytes.
+    const IS_FIXED_SIZE: bool = Self::MIN_SIZE == Self::MAX_SIZE;
+}
+
+/// Returns the number of bytes used by the length prefix in front of
+/// variable-length data, such as slices, strings, and sequences, with a
+/// declared maximum length of `max_len`.
+///
+/// The prefix is a `u8` if `max_len` fits in one, and a `u16` otherwise. This
+/// panics if `max_len` is more than 65535, which can't be encoded; as a
+/// constant, that stops compilation.
+pub const fn len_prefix_size(max_len: usize) -> usize {
+    if max_len <= u8::MAX as usize {
+        1
+    } else if max_len <= u16::MAX as usize {
+        2
+    } else {
+        panic!("hubpack maximum lengths can be at most 65535")
+    }
+}
+
+/// The `BoundedSize` trait is implemented by variable-length types, such as
+/// slices and strings, which don't have a predictable maximum size on their
+/// own, but do once you pick a maximum length.
+///
+/// When deriving `SerializedSize`, fields of these types must declare their
+/// maximum length using [`MaxLen`](crate::MaxLen):
+///
+/// ```
+/// # use hubpack::SerializedSize;
+/// #[derive(serde::Serialize, SerializedSize)]
+/// struct Message<'a> {
+///     #[serde(with = "hubpack::MaxLen::<32>")]
+///     name: &'a str,
+/// }
+///
+/// assert_eq!(Message::MAX_SIZE, 1 + 32);
+/// ```
+pub trait BoundedSize {
+    /// Maximum encoded size of each element of `Self`, in bytes.
+    const ELEMENT_MAX_SIZE: usize;
+}
+
+impl BoundedSize for str {
+    const ELEMENT_MAX_SIZE: usize = 1;
+}
+
+impl<T: SerializedSize> BoundedSize for [T] {
+    const ELEMENT_MAX_SIZE: usize = T::MAX_SIZE;
+}
+
+impl<T: BoundedSize + ?Sized> BoundedSize for &T {
+    const ELEMENT_MAX_SIZE: usize = T::ELEMENT_MAX_SIZE;
+}
+
+// Smart pointers and friends are encoded exactly like what they point to.
+#[cfg(feature = "alloc")]
+mod alloc_impls {
+    use super::{BoundedSize, SerializedSize};
+    use alloc::borrow::{Cow, ToOwned};
+    use alloc::boxed::Box;
+    use alloc::rc::Rc;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    #[cfg(target_has_atomic = "ptr")]
+    use alloc::sync::Arc;
+
+    macro_rules! pointer_impls {
+        ($($p:ident),*) => {
+            $(
+                impl<T: SerializedSize + ?Sized> SerializedSize for $p<T> {
+                    const MAX_SIZE: usize = T::MAX_SIZE;
+                    const MIN_SIZE: usize = T::MIN_SIZE;
+                }
+
+                impl<T: BoundedSize + ?Sized> BoundedSize for $p<T> {
+                    const ELEMENT_MAX_SIZE: usize = T::ELEMENT_MAX_SIZE;
+                }
+            )*
+        };
+    }
+
+    pointer_impls!(Box, Rc);
+
+    #[cfg(target_has_atomic = "ptr")]
+    pointer_impls!(Arc);
+
+    impl<T: SerializedSize + ToOwned + ?Sized> SerializedSize for Cow<'_, T> {
+        const MAX_SIZE: usize = T::MAX_SIZE;
+        const MIN_SIZE: usize = T::MIN_SIZE;
+    }
+
+    impl<T: BoundedSize + ToOwned + ?Sized> BoundedSize for Cow<'_, T> {
+        const ELEMENT_MAX_SIZE: usize = T::ELEMENT_MAX_SIZE;
+    }
+
+    impl<T: SerializedSize> BoundedSize for Vec<T> {
+        const ELEMENT_MAX_SIZE: usize = T::MAX_SIZE;
+    }
+
+    impl BoundedSize for String {
+        const ELEMENT_MAX_SIZE: usize = 1;
+    }
 }
 
 macro_rules! size_derives {
@@ -15,6 +125,15 @@ macro_rules! size_derives {
         $(
             impl SerializedSize for $t {
                 const MAX_SIZE: usize = $n;
+                const MIN_SIZE: usize = $n;
+            }
+
+            impl crate::packed::Packable for $t {
+                type Buffer = [u8; $n];
+            }
+
+            impl crate::packed::Packable for Option<$t> {
+                type Buffer = [u8; 1 + $n];
             }
         )*
     };
@@ -35,6 +154,11 @@ size_derives! {
     i64 = 8;
     i128 = 16;
 
+    // These are always encoded as 64-bit values, regardless of the platform's
+    // pointer width.
+    usize = 8;
+    isize = 8;
+
     f32 = 4;
     f64 = 8;
 
@@ -46,26 +170,57 @@ const fn const_max(a: usize, b: usize) -> usize {
     if a > b { a } else { b }
 }
 
+const fn const_min(a: usize, b: usize) -> usize {
+    if a < b { a } else { b }
+}
+
+impl<T: SerializedSize + ?Sized> SerializedSize for &T {
+    const MAX_SIZE: usize = T::MAX_SIZE;
+    const MIN_SIZE: usize = T::MIN_SIZE;
+}
+
 impl<T: SerializedSize> SerializedSize for Option<T> {
     const MAX_SIZE: usize = 1 + T::MAX_SIZE;
+    // `None` is just the tag.
+    const MIN_SIZE: usize = 1;
 }
 
 impl<T: SerializedSize, E: SerializedSize> SerializedSize for Result<T, E> {
     const MAX_SIZE: usize = 1 + const_max(T::MAX_SIZE, E::MAX_SIZE);
+    const MIN_SIZE: usize = 1 + const_min(T::MIN_SIZE, E::MIN_SIZE);
 }
 
 impl<T: SerializedSize, const N: usize> SerializedSize for [T; N] {
     const MAX_SIZE: usize = N * T::MAX_SIZE;
+    const MIN_SIZE: usize = N * T::MIN_SIZE;
+}
+
+/// A `heapless::Vec` is encoded as a length prefix followed by its elements.
+/// Like other sequences, it's only encoded as a field declared with
+/// [`MaxLen`](crate::MaxLen), since the encoder can't see its capacity.
+#[cfg(feature = "heapless")]
+impl<T: SerializedSize, const N: usize> BoundedSize for heapless::Vec<T, N> {
+    const ELEMENT_MAX_SIZE: usize = T::MAX_SIZE;
+}
+
+/// A `heapless::String` is encoded as a length prefix followed by its UTF-8
+/// bytes, and has to be declared with [`MaxLen`](crate::MaxLen) like a
+/// `heapless::Vec`.
+#[cfg(feature = "heapless")]
+impl<const N: usize> BoundedSize for heapless::String<N> {
+    const ELEMENT_MAX_SIZE: usize = 1;
 }
 
 impl<A: SerializedSize> SerializedSize for (A,) {
     const MAX_SIZE: usize = A::MAX_SIZE;
+    const MIN_SIZE: usize = A::MIN_SIZE;
 }
 
 macro_rules! tuple_impl {
     ($a:ident, $($rest:ident),+) => {
         impl<$a: SerializedSize, $($rest: SerializedSize),+> SerializedSize for ($a, $($rest),+) {
             const MAX_SIZE: usize = $a::MAX_SIZE + <($($rest,)*)>::MAX_SIZE;
+            const MIN_SIZE: usize = $a::MIN_SIZE + <($($rest,)*)>::MIN_SIZE;
         }
     };
 }